@@ -33,6 +33,17 @@ void rinode(uint64_t inum, DInode *ip);
 void rsect(uint64_t sec, void *buf);
 uint64_t ialloc(uint32_t typ);
 void iappend(uint64_t inum, void *p, size_t n);
+uint64_t subdir(uint64_t parent, const char *name);
+void dirent(uint64_t dir, uint64_t inum, const char *name);
+void dirsize(uint64_t inum);
+
+// Subdirectories of the root created for "dir/file" arguments.
+#define NSUBDIR 16
+struct {
+  char name[DIRSIZ+1];
+  uint64_t inum;
+} subdirs[NSUBDIR];
+size_t nsubdir;
 
 // convert to intel byte order
 uint32_t
@@ -71,10 +82,10 @@ int
 main(int argc, char *argv[])
 {
   int i, cc, fd;
-  uint64_t rootino, inum, off;
+  uint64_t rootino, dirino, inum;
+  char *name, *slash;
   Dirent de;
   char buf[BSIZE];
-  DInode din;
 
   if(argc < 2){
     fprintf(stderr, "Usage: mkfs fs.img files...\n");
@@ -117,37 +128,36 @@ main(int argc, char *argv[])
   rootino = ialloc(FILETYPE_DIR);
   assert(rootino == ROOTINO);
 
-  memset(&de, 0, sizeof(de));
-  de.inum = xuint64(rootino);
-  de.name[0] = '.';
-  iappend(rootino, &de, sizeof(de));
-
-  memset(&de, 0, sizeof(de));
-  de.inum = xuint64(rootino);
-  de.name[0] = de.name[1] = '.';
-  iappend(rootino, &de, sizeof(de));
+  dirent(rootino, rootino, ".");
+  dirent(rootino, rootino, "..");
 
   for(i = 2; i < argc; i++){
-    assert(index(argv[i], '/') == 0);
-
     if((fd = open(argv[i], 0)) < 0){
       perror(argv[i]);
       exit(1);
     }
 
+    // A file named "dir/file" goes into the directory "/dir",
+    // which is created the first time it is seen.  Only one
+    // level of directories is supported.
+    dirino = rootino;
+    name = argv[i];
+    if((slash = strchr(name, '/')) != NULL){
+      *slash = '\0';
+      dirino = subdir(rootino, name);
+      name = slash + 1;
+      assert(strchr(name, '/') == NULL);
+    }
+
     // Skip leading _ in name when writing to file system.
     // The binaries are named _rm, _cat, etc. to keep the
     // build operating system from trying to execute them
     // in place of system binaries like rm and cat.
-    if(argv[i][0] == '_')
-      ++argv[i];
+    if(name[0] == '_')
+      ++name;
 
     inum = ialloc(FILETYPE_FILE);
-
-    memset(&de, 0, sizeof(de));
-    de.inum = xuint64(inum);
-    strncpy((char *)de.name, argv[i], DIRSIZ);
-    iappend(rootino, &de, sizeof(de));
+    dirent(dirino, inum, name);
 
     while((cc = read(fd, buf, sizeof(buf))) > 0)
       iappend(inum, buf, cc);
@@ -155,12 +165,10 @@ main(int argc, char *argv[])
     close(fd);
   }
 
-  // fix size of root inode dir
-  rinode(rootino, &din);
-  off = xuint64(din.size);
-  off = ((off/BSIZE) + 1) * BSIZE;
-  din.size = xuint64(off);
-  winode(rootino, &din);
+  // fix size of directories
+  dirsize(rootino);
+  for(i = 0; i < nsubdir; i++)
+    dirsize(subdirs[i].inum);
 
   balloc(freeblock);
 
@@ -308,3 +316,59 @@ iappend(uint64_t inum, void *xp, size_t n)
   din.size = xuint64(off);
   winode(inum, &din);
 }
+
+void
+dirent(uint64_t dir, uint64_t inum, const char *name)
+{
+  Dirent de;
+
+  memset(&de, 0, sizeof(de));
+  de.inum = xuint64(inum);
+  strncpy((char *)de.name, name, DIRSIZ);
+  iappend(dir, &de, sizeof(de));
+}
+
+// Returns the inode of the directory `name` in `parent`,
+// creating it if this is the first time it has been named.
+uint64_t
+subdir(uint64_t parent, const char *name)
+{
+  uint64_t inum;
+  size_t i;
+  DInode din;
+
+  for(i = 0; i < nsubdir; i++)
+    if(strncmp(subdirs[i].name, name, DIRSIZ) == 0)
+      return subdirs[i].inum;
+  assert(nsubdir < NSUBDIR);
+
+  inum = ialloc(FILETYPE_DIR);
+  dirent(inum, inum, ".");
+  dirent(inum, parent, "..");
+  dirent(parent, inum, name);
+
+  // The new directory's ".." is a link to its parent.
+  rinode(parent, &din);
+  din.nlink = xuint32(xuint32(din.nlink) + 1);
+  winode(parent, &din);
+
+  strncpy(subdirs[nsubdir].name, name, DIRSIZ);
+  subdirs[nsubdir].inum = inum;
+  nsubdir++;
+
+  return inum;
+}
+
+// Rounds the size of a directory up to a whole block.
+void
+dirsize(uint64_t inum)
+{
+  uint64_t off;
+  DInode din;
+
+  rinode(inum, &din);
+  off = xuint64(din.size);
+  off = ((off/BSIZE) + 1) * BSIZE;
+  din.size = xuint64(off);
+  winode(inum, &din);
+}
@@ -1,8 +1,10 @@
+#include <errno.h>
 #include <fcntl.h>
 #include <stddef.h>
 #include <stdint.h>
 #include <stdio.h>
 #include <stdlib.h>
+#include <string.h>
 #include <unistd.h>
 
 char buf[512];
@@ -36,7 +38,7 @@ main(int argc, char *argv[])
 
   for(i = 1; i < argc; i++){
     if((fd = open(argv[i], O_RDONLY)) < 0){
-      dprintf(2, "cat: cannot open %s\n", argv[i]);
+      dprintf(2, "cat: cannot open %s: %s\n", argv[i], strerror(errno));
       exit(1);
     }
     cat(fd);
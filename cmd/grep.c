@@ -1,5 +1,6 @@
 // Simple grep.  Only supports ^ . * $ operators.
 
+#include <errno.h>
 #include <fcntl.h>
 #include <stddef.h>
 #include <stdio.h>
@@ -57,7 +58,7 @@ main(int argc, char *argv[])
 
   for(i = 2; i < argc; i++){
     if((fd = open(argv[i], O_RDONLY)) < 0){
-      dprintf(2, "grep: cannot open %s\n", argv[i]);
+      dprintf(2, "grep: cannot open %s: %s\n", argv[i], strerror(errno));
       exit(1);
     }
     grep(pattern, fd);
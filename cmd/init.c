@@ -1,4 +1,10 @@
 // init: The initial user-level program
+//
+// Runs the program named by its first argument, or sh, on the
+// console, and sh on each of the other virtual consoles,
+// restarting each whenever it exits.  Each runs in a session of
+// its own, with its console as the controlling terminal; init
+// itself has none.
 
 #include <sys/stat.h>
 #include <sys/wait.h>
@@ -10,33 +16,87 @@
 #include <stdlib.h>
 #include <unistd.h>
 
-char *argv[] = { "sh", "hi", "there", "test", NULL };
+#define NTTY 6
 
+char *shargv[] = { "sh", "hi", "there", "test", NULL };
+char *ttysh[] = { "sh", NULL };
+
+// Device names for the virtual consoles: minor n is ttys[n].
+char *ttys[NTTY] = { "console", "tty1", "tty2", "tty3", "tty4", "tty5" };
+
+// Open tty as file descriptors 0, 1 and 2.
 int
-main(void)
+opentty(int tty, int flags)
 {
-  int pid, wpid;
-
-  if(open("console", O_RDWR) < 0){
-    mknod("console", 0, 0);
-    open("console", O_RDWR);
+  if(open(ttys[tty], O_RDWR|flags) < 0){
+    mknod(ttys[tty], 0, tty);
+    if(open(ttys[tty], O_RDWR|flags) < 0)
+      return -1;
   }
   dup(0);  // stdout
   dup(0);  // stderr
+  return 0;
+}
 
-  for(;;){
-    printf("init: starting sh\n");
-    pid = fork();
-    if(pid < 0){
-      printf("init: fork failed\n");
-      exit(1);
-    }
-    if(pid == 0){
-      execvp("sh", argv);
-      printf("init: exec sh failed\n");
+// Start prog on tty, returning its pid.
+int
+start(int tty, char *prog, char **argv)
+{
+  int pid;
+
+  printf("init: starting %s on %s\n", prog, ttys[tty]);
+  pid = fork();
+  if(pid < 0){
+    printf("init: fork failed\n");
+    exit(1);
+  }
+  if(pid == 0){
+    setsid();
+    close(0);
+    close(1);
+    close(2);
+    if(opentty(tty, 0) < 0)
       exit(1);
-    }
-    while((wpid=wait(NULL)) >= 0 && wpid != pid)
+    execvp(prog, argv);
+    printf("init: exec %s failed\n", prog);
+    exit(1);
+  }
+  return pid;
+}
+
+int
+main(int argc, char *argv[])
+{
+  int pids[NTTY];
+  int tty, wpid;
+  char *sh, **shv;
+  char *progv[2];
+
+  sh = "sh";
+  shv = shargv;
+  if(argc > 1){
+    sh = argv[1];
+    progv[0] = sh;
+    progv[1] = NULL;
+    shv = progv;
+  }
+
+  opentty(0, O_NOCTTY);
+
+  pids[0] = start(0, sh, shv);
+  for(tty = 1; tty < NTTY; tty++)
+    pids[tty] = start(tty, "sh", ttysh);
+
+  for(;;){
+    if((wpid = wait(NULL)) < 0)
+      continue;
+    for(tty = 0; tty < NTTY && pids[tty] != wpid; tty++)
+      ;
+    if(tty == NTTY)
       printf("zombie!\n");
+    else if(tty == 0)
+      pids[tty] = start(tty, sh, shv);
+    else
+      pids[tty] = start(tty, "sh", ttysh);
   }
 }
@@ -1,3 +1,7 @@
+// kill: send a signal, SIGTERM unless another is given by
+// number, to each of the processes named; a negative pid names
+// a process group, and -1 every process but init.
+
 #include <signal.h>
 #include <stddef.h>
 #include <stdio.h>
@@ -6,13 +10,25 @@
 int
 main(int argc, char *argv[])
 {
-  int i;
+  int i, pid, sig;
 
-  if(argc < 2){
-    dprintf(2, "usage: kill pid...\n");
+  sig = SIGTERM;
+  i = 1;
+  if(argc > 1 && argv[1][0] == '-'){
+    sig = atoi(argv[1] + 1);
+    i++;
+  }
+  if(i >= argc || sig <= 0 || sig >= NSIG){
+    dprintf(2, "usage: kill [-signal] [-]pid...\n");
     exit(1);
   }
-  for(i=1; i<argc; i++)
-    kill(atoi(argv[i]), SIGTERM);
+  for(; i<argc; i++){
+    if(argv[i][0] == '-')
+      pid = -atoi(argv[i] + 1);
+    else
+      pid = atoi(argv[i]);
+    if(kill(pid, sig) < 0)
+      dprintf(2, "kill: %s: no such process\n", argv[i]);
+  }
   exit(0);
 }
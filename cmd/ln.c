@@ -1,6 +1,8 @@
+#include <errno.h>
 #include <stddef.h>
 #include <stdio.h>
 #include <stdlib.h>
+#include <string.h>
 #include <unistd.h>
 
 int
@@ -11,6 +13,6 @@ main(int argc, char *argv[])
     exit(1);
   }
   if(link(argv[1], argv[2]) < 0)
-    dprintf(2, "link %s %s: failed\n", argv[1], argv[2]);
+    dprintf(2, "link %s %s: %s\n", argv[1], argv[2], strerror(errno));
   exit(0);
 }
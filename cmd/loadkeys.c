@@ -0,0 +1,48 @@
+// loadkeys: change the keyboard layout.
+//
+//   loadkeys name      use a built-in keymap: us or trq
+//   loadkeys -f file   load a keymap from file, which holds
+//                      a Keymap (see keymap.h) as raw bytes
+
+#include <fcntl.h>
+#include <keymap.h>
+#include <stddef.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+
+Keymap map;
+
+int
+main(int argc, char *argv[])
+{
+  int fd, n;
+
+  if(argc == 2){
+    if(keymap(KEYMAP_SELECT, argv[1]) < 0){
+      dprintf(2, "loadkeys: no keymap %s\n", argv[1]);
+      exit(1);
+    }
+    exit(0);
+  }
+  if(argc != 3 || strcmp(argv[1], "-f") != 0){
+    dprintf(2, "usage: loadkeys name | loadkeys -f file\n");
+    exit(1);
+  }
+  if((fd = open(argv[2], O_RDONLY)) < 0){
+    dprintf(2, "loadkeys: cannot open %s\n", argv[2]);
+    exit(1);
+  }
+  n = read(fd, &map, sizeof(map));
+  close(fd);
+  if(n != sizeof(map)){
+    dprintf(2, "loadkeys: %s: not a keymap\n", argv[2]);
+    exit(1);
+  }
+  if(keymap(KEYMAP_LOAD, &map) < 0){
+    dprintf(2, "loadkeys: cannot load %s\n", argv[2]);
+    exit(1);
+  }
+  exit(0);
+}
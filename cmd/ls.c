@@ -1,5 +1,6 @@
 #include <sys/stat.h>
 
+#include <errno.h>
 #include <fcntl.h>
 #include <stddef.h>
 #include <stdint.h>
@@ -50,12 +51,12 @@ ls(char *path)
   Stat st;
 
   if((fd = open(path, O_RDONLY)) < 0){
-    dprintf(2, "ls: cannot open %s\n", path);
+    dprintf(2, "ls: cannot open %s: %s\n", path, strerror(errno));
     return;
   }
 
   if(fstat(fd, &st) < 0){
-    dprintf(2, "ls: cannot stat %s\n", path);
+    dprintf(2, "ls: cannot stat %s: %s\n", path, strerror(errno));
     close(fd);
     return;
   }
@@ -79,7 +80,7 @@ ls(char *path)
       memmove(p, de.name, DIRSIZ);
       p[DIRSIZ] = 0;
       if(stat(buf, &st) < 0){
-        dprintf(2, "ls: cannot stat %s\n", buf);
+        dprintf(2, "ls: cannot stat %s: %s\n", buf, strerror(errno));
         continue;
       }
       printf("%s %d %d %d\n", fmtname(buf), st.typ, st.ino, st.size);
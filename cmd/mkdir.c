@@ -1,9 +1,11 @@
 #include <sys/stat.h>
 
+#include <errno.h>
 #include <stddef.h>
 #include <stdint.h>
 #include <stdio.h>
 #include <stdlib.h>
+#include <string.h>
 
 int
 main(int argc, char *argv[])
@@ -17,7 +19,7 @@ main(int argc, char *argv[])
 
   for(i = 1; i < argc; i++){
     if(mkdir(argv[i], 0755) < 0){
-      dprintf(2, "mkdir: %s failed to create\n", argv[i]);
+      dprintf(2, "mkdir: %s: %s\n", argv[i], strerror(errno));
       exit(1);
     }
   }
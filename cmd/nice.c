@@ -0,0 +1,34 @@
+// nice: run a command with its nice value raised, by 10 unless
+// another increment is given.  A negative one runs it sooner.
+
+#include <sys/resource.h>
+
+#include <stddef.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <unistd.h>
+
+int
+main(int argc, char *argv[])
+{
+  int i, inc;
+
+  inc = 10;
+  i = 1;
+  if(argc > 1 && argv[1][0] == '-'){
+    if(argv[1][1] == '-')
+      inc = -atoi(argv[1] + 2);
+    else
+      inc = atoi(argv[1] + 1);
+    i++;
+  }
+  if(i >= argc){
+    dprintf(2, "usage: nice [-inc | --inc] command [arg...]\n");
+    exit(1);
+  }
+  if(setpriority(PRIO_PROCESS, 0, getpriority(PRIO_PROCESS, 0) + inc) < 0)
+    dprintf(2, "nice: cannot set priority\n");
+  execvp(argv[i], argv + i);
+  dprintf(2, "nice: cannot run %s\n", argv[i]);
+  exit(1);
+}
@@ -0,0 +1,332 @@
+// portfolio: show pages of text on the console display.
+// It draws on the console open on its standard output.
+//
+// Each command names a page, a file in /pages that describes
+// one screenful.  Pages are lines of text, each a directive:
+//
+//   # comment
+//   text X Y string     write string at column X, row Y
+//   center Y string     write string centered on row Y
+//   box X Y W H         draw a W by H box at X, Y
+//   title string        a boxed heading at the top of the page
+//   footer Y string     a boxed note beginning on row Y
+//   prompt X Y          put the command prompt at X, Y
+//   sysinfo X Y         describe the running system in six
+//                       lines beginning at X, Y
+//
+// Strings run to the end of the line, and begin after the
+// single space that separates them from the last number, so
+// leading spaces are preserved.  Text and boxes that do not fit
+// are clipped to the screen.  The prompt should not be on the
+// last row: the newline that ends a command would scroll the
+// screen.  Page `main' is shown at start up and by `exit'.
+
+#include <fcntl.h>
+#include <screen.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sysinfo.h>
+#include <unistd.h>
+
+#define MAXPAGE 4096
+#define MAXLINE 128
+#define PROMPTX 5
+#define PROMPTY 22
+
+static const char PROMPT[] = "uzeyiros> ";
+
+char page[MAXPAGE+1];
+int promptx = PROMPTX, prompty = PROMPTY;
+
+void
+text(int x, int y, const char *s)
+{
+  ScreenReq r = { x, y, 0, 0, s };
+
+  screen(1, SCREEN_TEXT, &r);
+}
+
+void
+center(int y, const char *s)
+{
+  int n;
+
+  n = strlen(s);
+  text(n < SCREEN_WIDTH ? (SCREEN_WIDTH - n) / 2 : 0, y, s);
+}
+
+void
+box(int x, int y, int w, int h)
+{
+  ScreenReq r = { x, y, w, h, NULL };
+
+  screen(1, SCREEN_BOX, &r);
+}
+
+void
+cursor(int x, int y)
+{
+  ScreenReq r = { x, y, 0, 0, NULL };
+
+  screen(1, SCREEN_CURSOR, &r);
+}
+
+void
+clearrow(int y)
+{
+  char blanks[SCREEN_WIDTH+1];
+
+  memset(blanks, ' ', SCREEN_WIDTH);
+  blanks[SCREEN_WIDTH] = '\0';
+  text(0, y, blanks);
+}
+
+// Show a message on the row below the prompt.
+void
+message(const char *s, const char *arg)
+{
+  char buf[SCREEN_WIDTH+1];
+  size_t n;
+
+  n = strlcpy(buf, s, sizeof(buf));
+  if(arg != NULL && n < sizeof(buf))
+    strlcpy(buf + n, arg, sizeof(buf) - n);
+  text(promptx, prompty + 1, buf);
+}
+
+// Parse a number from *sp, leaving *sp past it and the
+// single space that follows.  Returns -1 on error.
+int
+number(char **sp)
+{
+  char *s;
+  int n;
+
+  s = *sp;
+  if(*s < '0' || *s > '9')
+    return -1;
+  for(n = 0; *s >= '0' && *s <= '9'; s++)
+    n = n*10 + *s - '0';
+  if(*s == ' ')
+    s++;
+  else if(*s != '\0')
+    return -1;
+  *sp = s;
+  return n;
+}
+
+// Append the decimal form of n to the string s of size sz.
+void
+appendnum(char *s, size_t sz, uint64_t n)
+{
+  char buf[21], *p;
+
+  p = buf + sizeof(buf) - 1;
+  *p = '\0';
+  do
+    *--p = '0' + n % 10;
+  while((n /= 10) != 0);
+  strlcat(s, p, sz);
+}
+
+// Write what the kernel reports about the system at x, y.
+int
+sysinfotext(int x, int y)
+{
+  static Sysinfo info;
+  char line[SCREEN_WIDTH+1];
+
+  if(sysinfo(SYSINFO_SYSTEM, &info, 0) < 0)
+    return -1;
+  strlcpy(line, "Uzeyiros v", sizeof(line));
+  strlcat(line, info.version, sizeof(line));
+  text(x, y++, line);
+  strlcpy(line, "CPUs: ", sizeof(line));
+  appendnum(line, sizeof(line), info.ncpu);
+  text(x, y++, line);
+  strlcpy(line, "Memory: ", sizeof(line));
+  appendnum(line, sizeof(line), info.free_pages * info.page_size / 1024);
+  strlcat(line, " KB free of ", sizeof(line));
+  appendnum(line, sizeof(line), info.total_pages * info.page_size / 1024);
+  strlcat(line, " KB", sizeof(line));
+  text(x, y++, line);
+  strlcpy(line, "Disk: ", sizeof(line));
+  if(info.disk_sectors != 0){
+    strlcat(line, info.disk_model, sizeof(line));
+    strlcat(line, ", ", sizeof(line));
+    appendnum(line, sizeof(line), info.disk_sectors / 2048);
+    strlcat(line, " MB", sizeof(line));
+  } else
+    strlcat(line, "none", sizeof(line));
+  text(x, y++, line);
+  strlcpy(line, "Uptime: ", sizeof(line));
+  appendnum(line, sizeof(line), info.uptime / info.hz);
+  strlcat(line, " seconds", sizeof(line));
+  text(x, y++, line);
+  strlcpy(line, "Processes: ", sizeof(line));
+  appendnum(line, sizeof(line), info.nproc);
+  text(x, y, line);
+  return 0;
+}
+
+int
+directive(char *line)
+{
+  char *args;
+  int x, y, w, h;
+
+  if((args = strchr(line, ' ')) != NULL)
+    *args++ = '\0';
+  else
+    args = line + strlen(line);
+
+  if(strcmp(line, "text") == 0){
+    if((x = number(&args)) < 0 || (y = number(&args)) < 0)
+      return -1;
+    text(x, y, args);
+  } else if(strcmp(line, "center") == 0){
+    if((y = number(&args)) < 0)
+      return -1;
+    center(y, args);
+  } else if(strcmp(line, "box") == 0){
+    if((x = number(&args)) < 0 || (y = number(&args)) < 0 ||
+       (w = number(&args)) < 0 || (h = number(&args)) < 0)
+      return -1;
+    box(x, y, w, h);
+  } else if(strcmp(line, "title") == 0){
+    box(5, 2, 70, 3);
+    center(3, args);
+  } else if(strcmp(line, "footer") == 0){
+    if((y = number(&args)) < 0)
+      return -1;
+    box(5, y, 70, 3);
+    center(y + 1, args);
+  } else if(strcmp(line, "prompt") == 0){
+    if((x = number(&args)) < 0 || (y = number(&args)) < 0)
+      return -1;
+    if(y >= SCREEN_HEIGHT - 1 || x + sizeof(PROMPT) >= SCREEN_WIDTH)
+      return -1;
+    promptx = x;
+    prompty = y;
+  } else if(strcmp(line, "sysinfo") == 0){
+    if((x = number(&args)) < 0 || (y = number(&args)) < 0)
+      return -1;
+    if(sysinfotext(x, y) < 0)
+      text(x, y, "System information unavailable");
+  } else
+    return -1;
+  return 0;
+}
+
+// Read the named page into `page'.  Returns -1 if there
+// is no such page.
+int
+load(const char *name)
+{
+  char path[MAXLINE];
+  const char *s;
+  int fd, n, cc;
+
+  // Page names are simple words; anything else could name
+  // some other file.
+  if(*name == '\0')
+    return -1;
+  for(s = name; *s != '\0'; s++)
+    if(!((*s >= 'a' && *s <= 'z') || (*s >= '0' && *s <= '9') || *s == '-'))
+      return -1;
+
+  strlcpy(path, "/pages/", sizeof(path));
+  strlcpy(path + 7, name, sizeof(path) - 7);
+  if((fd = open(path, O_RDONLY)) < 0)
+    return -1;
+  for(n = 0; n < MAXPAGE && (cc = read(fd, page + n, MAXPAGE - n)) > 0; n += cc)
+    ;
+  close(fd);
+  page[n] = '\0';
+  return 0;
+}
+
+// Draw the named page.  Returns -1 if there is no such page.
+int
+show(const char *name)
+{
+  char *line, *next;
+  int lineno;
+
+  if(load(name) < 0)
+    return -1;
+  screen(1, SCREEN_CLEAR, NULL);
+  promptx = PROMPTX;
+  prompty = PROMPTY;
+  lineno = 1;
+  for(line = page; line != NULL; line = next, lineno++){
+    if((next = strchr(line, '\n')) != NULL)
+      *next++ = '\0';
+    if(*line == '\0' || *line == '#')
+      continue;
+    if(directive(line) < 0)
+      dprintf(2, "portfolio: %s:%d: bad directive\n", name, lineno);
+  }
+  return 0;
+}
+
+// Read a command typed at the prompt, without the newline.
+// Returns -1 at end of file.
+int
+getcmd(char *buf, int nbuf)
+{
+  int n;
+
+  clearrow(prompty);
+  text(promptx, prompty, PROMPT);
+  cursor(promptx + sizeof(PROMPT) - 1, prompty);
+  n = read(0, buf, nbuf - 1);
+  if(n <= 0)
+    return -1;
+  if(buf[n-1] == '\n')
+    n--;
+  buf[n] = '\0';
+  return 0;
+}
+
+int
+main(void)
+{
+  static char buf[MAXLINE];
+  char *cmd, *arg;
+  char *sh[] = { "sh", NULL };
+
+  // Without a display there is nothing to show: fall back
+  // to the shell, so that the console remains useful.
+  if(screen(1, SCREEN_CLEAR, NULL) < 0){
+    printf("portfolio: no display; starting sh\n");
+    execvp("sh", sh);
+    exit(1);
+  }
+  if(show("main") < 0)
+    message("portfolio: no page /pages/main", NULL);
+  while(getcmd(buf, sizeof(buf)) >= 0){
+    for(cmd = buf; *cmd == ' '; cmd++)
+      ;
+    if((arg = strchr(cmd, ' ')) != NULL)
+      *arg++ = '\0';
+    clearrow(prompty + 1);
+    clearrow(prompty + 2);
+    if(*cmd == '\0')
+      continue;
+    if(strcmp(cmd, "exit") == 0 || strcmp(cmd, "clear") == 0)
+      show("main");
+    else if(strcmp(cmd, "echo") == 0 && arg != NULL)
+      message("Echo: ", arg);
+    else if(strcmp(cmd, "echo") == 0)
+      message("Echo usage: echo <message>", NULL);
+    else if(show(cmd) < 0){
+      message("Unknown command: ", cmd);
+      text(promptx, prompty + 2, "Type 'help' to see available commands.");
+    }
+  }
+  exit(0);
+}
@@ -1,7 +1,9 @@
+#include <errno.h>
 #include <stddef.h>
 #include <stdint.h>
 #include <stdio.h>
 #include <stdlib.h>
+#include <string.h>
 #include <unistd.h>
 
 int
@@ -16,7 +18,7 @@ main(int argc, char *argv[])
 
   for(i = 1; i < argc; i++){
     if(unlink(argv[i]) < 0){
-      dprintf(2, "rm: %s failed to delete\n", argv[i]);
+      dprintf(2, "rm: %s: %s\n", argv[i], strerror(errno));
       exit(1);
     }
   }
@@ -2,12 +2,15 @@
 
 #include <sys/wait.h>
 
+#include <errno.h>
 #include <fcntl.h>
+#include <signal.h>
 #include <stddef.h>
 #include <stdint.h>
 #include <stdio.h>
 #include <stdlib.h>
 #include <string.h>
+#include <termios.h>
 #include <unistd.h>
 
 // Parsed command representation
@@ -82,14 +85,14 @@ runcmd(struct cmd *cmd)
     if(ecmd->argv[0] == 0)
       exit(1);
     execvp(ecmd->argv[0], ecmd->argv);
-    dprintf(2, "exec %s failed\n", ecmd->argv[0]);
+    dprintf(2, "exec %s: %s\n", ecmd->argv[0], strerror(errno));
     break;
 
   case REDIR:
     rcmd = (struct redircmd*)cmd;
     close(rcmd->fd);
     if(open(rcmd->file, rcmd->mode) < 0){
-      dprintf(2, "open %s failed\n", rcmd->file);
+      dprintf(2, "open %s: %s\n", rcmd->file, strerror(errno));
       exit(1);
     }
     runcmd(rcmd->cmd);
@@ -155,13 +158,48 @@ gets(char *buf, size_t max)
   return buf;
 }
 
+// Set when ^C or ^\ interrupts the shell, which carries on.
+volatile int interrupted;
+
+void
+onintr(int sig)
+{
+  (void)sig;
+  interrupted = 1;
+}
+
+// The process group of the job last stopped by ^Z, if any.
+int stopped;
+
+// Gives the terminal to process group pid and waits for its
+// leader to exit or stop, then takes the terminal back.
+void
+waitfg(int pid)
+{
+  int status;
+
+  status = 0;
+  tcsetpgrp(0, pid);
+  // Wait again if a signal cut the wait short.
+  interrupted = 0;
+  while(waitpid(pid, &status, WUNTRACED) < 0 && interrupted)
+    interrupted = 0;
+  tcsetpgrp(0, getpgrp());
+  if(WIFSTOPPED(status)){
+    printf("[%d] Stopped\n", pid);
+    stopped = pid;
+  } else if(pid == stopped)
+    stopped = 0;
+}
+
 int
 getcmd(char *buf, int nbuf)
 {
   printf("$ ");
   memset(buf, 0, nbuf);
+  interrupted = 0;
   gets(buf, nbuf);
-  if(buf[0] == 0) // EOF
+  if(buf[0] == 0 && !interrupted) // EOF
     return -1;
   return 0;
 }
@@ -170,7 +208,7 @@ int
 main(void)
 {
   static char buf[100];
-  int fd;
+  int fd, pid;
 
   // Ensure that three file descriptors are open.
   while((fd = open("console", O_RDWR)) >= 0){
@@ -180,18 +218,45 @@ main(void)
     }
   }
 
+  signal(SIGINT, onintr);
+  signal(SIGQUIT, onintr);
+  signal(SIGTSTP, SIG_IGN);
+
   // Read and run input commands.
   while(getcmd(buf, sizeof(buf)) >= 0){
+    if(buf[0] == 0)
+      continue;
     if(buf[0] == 'c' && buf[1] == 'd' && buf[2] == ' '){
       // Chdir must be called by the parent, not the child.
       buf[strlen(buf)-1] = 0;  // chop \n
       if(chdir(buf+3) < 0)
-        dprintf(2, "cannot cd %s\n", buf+3);
+        dprintf(2, "cannot cd %s: %s\n", buf+3, strerror(errno));
       continue;
     }
-    if(fork1() == 0)
+    if(strcmp(buf, "fg\n") == 0){
+      // Continue the stopped job in the foreground.
+      if(stopped == 0 || kill(-stopped, SIGCONT) < 0){
+        dprintf(2, "fg: no stopped job\n");
+        stopped = 0;
+        continue;
+      }
+      waitfg(stopped);
+      continue;
+    }
+    // Each command runs in a process group of its own, put in
+    // the foreground of the terminal, so that the signals typed
+    // at it go to the command rather than the shell.  Both
+    // parent and child set this up, as either may run first.
+    if((pid = fork1()) == 0){
+      setpgid(0, 0);
+      tcsetpgrp(0, getpid());
+      signal(SIGINT, SIG_DFL);
+      signal(SIGQUIT, SIG_DFL);
+      signal(SIGTSTP, SIG_DFL);
       runcmd(parsecmd(buf));
-    wait(NULL);
+    }
+    setpgid(pid, pid);
+    waitfg(pid);
   }
   exit(1);
 }
@@ -0,0 +1,72 @@
+// sysinfo: describe the running system: the kernel version,
+// CPUs, memory, the disk and uptime, followed by the process
+// table, with the clock ticks each process has used.
+
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <sysinfo.h>
+
+#define NPROC 256
+
+static const char *memtypes[] = {
+  [MEM_USABLE] "usable",
+  [MEM_SYSTEM] "system",
+  [MEM_RESERVED] "reserved",
+};
+
+static const char *states[] = {
+  [PROC_EMBRYO] "embryo",
+  [PROC_SLEEPING] "sleeping",
+  [PROC_RUNNABLE] "ready",
+  [PROC_RUNNING] "running",
+  [PROC_ZOMBIE] "zombie",
+  [PROC_STOPPED] "stopped",
+};
+
+Sysinfo info;
+ProcInfo procs[NPROC];
+
+int
+main(void)
+{
+  const char *type;
+  int i, n, secs;
+
+  if(sysinfo(SYSINFO_SYSTEM, &info, 0) < 0){
+    dprintf(2, "sysinfo: cannot get system information\n");
+    exit(1);
+  }
+  secs = info.uptime / info.hz;
+  printf("UzeyirOS v%s\n", info.version);
+  printf("cpus: %d\n", info.ncpu);
+  printf("uptime: %d:%d%d:%d%d\n", secs / 3600,
+         secs / 600 % 6, secs / 60 % 10, secs % 60 / 10, secs % 10);
+  printf("memory: %d KB free of %d KB\n",
+         (int)(info.free_pages * info.page_size / 1024),
+         (int)(info.total_pages * info.page_size / 1024));
+  for(i = 0; i < info.nregions; i++){
+    type = info.regions[i].type <= MEM_RESERVED ? memtypes[info.regions[i].type] : "?";
+    printf("  %x-%x %s\n", info.regions[i].start, info.regions[i].end, type);
+  }
+  if(info.disk_sectors != 0)
+    printf("disk: %s (serial %s), %d MB\n", info.disk_model,
+           info.disk_serial, (int)(info.disk_sectors / 2048));
+  else
+    printf("disk: none\n");
+
+  if((n = sysinfo(SYSINFO_PROCS, procs, NPROC)) < 0){
+    dprintf(2, "sysinfo: cannot get process table\n");
+    exit(1);
+  }
+  printf("processes: %d\n", n);
+  printf("PID\tPPID\tSTATE\tNI\tLEVEL\tSIZE\tUTIME\tSTIME\tNAME\n");
+  for(i = 0; i < n; i++)
+    printf("%d\t%d\t%s\t%d\t%d\t%d\t%d\t%d\t%s\n", procs[i].pid, procs[i].ppid,
+           procs[i].state <= PROC_STOPPED ? states[procs[i].state] : "?",
+           procs[i].nice, procs[i].level,
+           (int)procs[i].size, (int)procs[i].utime, (int)procs[i].stime,
+           procs[i].name);
+  exit(0);
+}
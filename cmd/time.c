@@ -0,0 +1,62 @@
+// time: run a command and report the time it took, the time it
+// spent in user space and in the kernel, and the most memory it
+// used.
+
+#include <sys/resource.h>
+#include <sys/times.h>
+#include <sys/wait.h>
+
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <sysinfo.h>
+#include <unistd.h>
+
+Sysinfo info;
+
+// Prints ticks as seconds, to the hundredth.
+void
+prticks(const char *what, uint64_t ticks)
+{
+  uint64_t c;
+
+  c = ticks * 100 / info.hz;
+  dprintf(2, "%s\t%d.%d%d\n", what, (int)(c / 100), (int)(c / 10 % 10), (int)(c % 10));
+}
+
+int
+main(int argc, char *argv[])
+{
+  struct rusage ru;
+  clock_t start;
+  int pid, status;
+
+  if(argc < 2){
+    dprintf(2, "usage: time command [arg...]\n");
+    exit(1);
+  }
+  if(sysinfo(SYSINFO_SYSTEM, &info, 0) < 0){
+    dprintf(2, "time: cannot get system information\n");
+    exit(1);
+  }
+  start = times(NULL);
+  if((pid = fork()) < 0){
+    dprintf(2, "time: fork failed\n");
+    exit(1);
+  }
+  if(pid == 0){
+    execvp(argv[1], argv + 1);
+    dprintf(2, "time: cannot run %s\n", argv[1]);
+    exit(1);
+  }
+  if(wait4(pid, &status, 0, &ru) < 0){
+    dprintf(2, "time: wait failed\n");
+    exit(1);
+  }
+  prticks("real", times(NULL) - start);
+  prticks("user", ru.ru_utime);
+  prticks("sys", ru.ru_stime);
+  dprintf(2, "maxrss\t%d KB\n", (int)ru.ru_maxrss);
+  exit(WIFEXITED(status) ? WEXITSTATUS(status) : 1);
+}
@@ -1,6 +1,8 @@
+#include <sys/mman.h>
 #include <sys/stat.h>
 #include <sys/wait.h>
 
+#include <errno.h>
 #include <fcntl.h>
 #include <signal.h>
 #include <stddef.h>
@@ -8,6 +10,7 @@
 #include <stdio.h>
 #include <stdlib.h>
 #include <string.h>
+#include <sysinfo.h>
 #include <unistd.h>
 
 #define MAXARG 32
@@ -436,6 +439,54 @@ exitwait(void)
   printf("exitwait ok\n");
 }
 
+// waitpid for a given child, WNOHANG, exit and signal statuses,
+// and stopped children.
+void
+waitpidtest(void)
+{
+  int pid1, pid2, status;
+  struct rusage ru;
+
+  printf("waitpid test\n");
+  pid1 = fork();
+  if(pid1 == 0){
+    sleep(1);
+    exit(3);
+  }
+  pid2 = fork();
+  if(pid2 == 0){
+    for(;;)
+      ;
+  }
+  if(pid1 < 0 || pid2 < 0){
+    printf("waitpid: fork failed\n");
+    exit(1);
+  }
+  if(waitpid(pid2, &status, WNOHANG) != 0){
+    printf("waitpid: WNOHANG did not return 0\n");
+    exit(1);
+  }
+  if(wait4(pid1, &status, 0, &ru) != pid1 || !WIFEXITED(status) || WEXITSTATUS(status) != 3){
+    printf("waitpid: wrong exit status %x\n", status);
+    exit(1);
+  }
+  kill(pid2, SIGSTOP);
+  if(waitpid(pid2, &status, WUNTRACED) != pid2 || !WIFSTOPPED(status) || WSTOPSIG(status) != SIGSTOP){
+    printf("waitpid: wrong stop status %x\n", status);
+    exit(1);
+  }
+  kill(pid2, SIGKILL);
+  if(waitpid(pid2, &status, 0) != pid2 || !WIFSIGNALED(status) || WTERMSIG(status) != SIGKILL){
+    printf("waitpid: wrong signal status %x\n", status);
+    exit(1);
+  }
+  if(waitpid(-1, &status, WNOHANG) >= 0){
+    printf("waitpid: no children but found one\n");
+    exit(1);
+  }
+  printf("waitpid ok\n");
+}
+
 void
 mem(void)
 {
@@ -1430,6 +1481,365 @@ forktest(void)
   printf("fork test OK\n");
 }
 
+// Pages shared copy-on-write after fork: a write by either side
+// must not be seen by the other, including writes the kernel makes
+// on behalf of a process, as read() does.
+void
+cowtest(void)
+{
+  enum { NPAGE = 64, NCHILD = 8 };
+  int fds[2], i, k, pid, status;
+  char *a;
+
+  printf("cow test\n");
+  a = sbrk(NPAGE * 4096);
+  if(a == (char*)-1){
+    printf("cow: sbrk failed\n");
+    exit(1);
+  }
+  for(k = 0; k < NPAGE * 4096; k++)
+    a[k] = k % 251;
+  for(i = 0; i < NCHILD; i++){
+    pid = fork();
+    if(pid < 0){
+      printf("cow: fork failed\n");
+      exit(1);
+    }
+    if(pid == 0){
+      for(k = 0; k < NPAGE * 4096; k++){
+        if(a[k] != (char)(k % 251)){
+          printf("cow: child %d saw a change at %d\n", i, k);
+          exit(1);
+        }
+      }
+      for(k = i; k < NPAGE * 4096; k += 4096 / 2)
+        a[k] = i + 1;
+      if(fork() == 0){
+        // A grandchild shares the child's copies.
+        if(a[i] != i + 1)
+          exit(1);
+        a[i] = 0;
+        exit(0);
+      }
+      if(wait(&status) < 0 || !WIFEXITED(status) || WEXITSTATUS(status) != 0 || a[i] != i + 1){
+        printf("cow: grandchild of %d failed\n", i);
+        exit(1);
+      }
+      exit(0);
+    }
+  }
+  for(i = 0; i < NCHILD; i++){
+    if(wait(&status) < 0 || !WIFEXITED(status) || WEXITSTATUS(status) != 0){
+      printf("cow: child failed\n");
+      exit(1);
+    }
+  }
+  for(k = 0; k < NPAGE * 4096; k++){
+    if(a[k] != (char)(k % 251)){
+      printf("cow: parent saw a change at %d\n", k);
+      exit(1);
+    }
+  }
+
+  // The kernel writing into a shared page must copy it, too.
+  a[4] = 0;
+  if(pipe(fds) != 0){
+    printf("cow: pipe failed\n");
+    exit(1);
+  }
+  pid = fork();
+  if(pid == 0){
+    close(fds[0]);
+    write(fds[1], "cow!", 4);
+    exit(0);
+  }
+  close(fds[1]);
+  if(read(fds[0], a, 4) != 4 || strcmp(a, "cow!") != 0){
+    printf("cow: read into shared page failed\n");
+    exit(1);
+  }
+  close(fds[0]);
+  wait(NULL);
+  pid = fork();
+  if(pid == 0)
+    exit(strcmp(a, "cow!") == 0 ? 0 : 1);
+  if(wait(&status) < 0 || WEXITSTATUS(status) != 0){
+    printf("cow: child did not see parent's read\n");
+    exit(1);
+  }
+
+  sbrk(-(NPAGE * 4096));
+  printf("cow ok\n");
+}
+
+// Many processes forking at once, each from a large address
+// space that is rarely written, as shells running commands do.
+void
+forkstorm(void)
+{
+  enum { SIZE = 8 * 1024 * 1024, NFORK = 20, ROUNDS = 10 };
+  int i, r, pid, status;
+  char *a;
+
+  printf("fork storm test\n");
+  a = sbrk(SIZE);
+  if(a == (char*)-1){
+    printf("fork storm: sbrk failed\n");
+    exit(1);
+  }
+  for(i = 0; i < SIZE; i += 4096)
+    a[i] = 1;
+  for(r = 0; r < ROUNDS; r++){
+    for(i = 0; i < NFORK; i++){
+      pid = fork();
+      if(pid < 0){
+        printf("fork storm: fork %d of round %d failed\n", i, r);
+        exit(1);
+      }
+      if(pid == 0){
+        a[i * 4096] = 2;
+        exit(a[(i + 1) * 4096] == 1 ? 0 : 1);
+      }
+    }
+    for(i = 0; i < NFORK; i++){
+      if(wait(&status) < 0 || WEXITSTATUS(status) != 0){
+        printf("fork storm: child failed\n");
+        exit(1);
+      }
+    }
+  }
+  for(i = 0; i < SIZE; i += 4096){
+    if(a[i] != 1){
+      printf("fork storm: parent page %d changed\n", i / 4096);
+      exit(1);
+    }
+  }
+  sbrk(-SIZE);
+  printf("fork storm ok\n");
+}
+
+static uint64_t
+freepages(void)
+{
+  Sysinfo si;
+
+  if(sysinfo(SYSINFO_SYSTEM, &si, sizeof(si)) < 0){
+    printf("sysinfo failed\n");
+    exit(1);
+  }
+  return si.free_pages;
+}
+
+// uses about n bytes of stack, then returns their sum.
+int
+deep(int n)
+{
+  char frame[1024];
+  int i, sum;
+
+  for(i = 0; i < sizeof(frame); i++)
+    frame[i] = i;
+  sum = n <= 0 ? 0 : deep(n - sizeof(frame));
+  for(i = 0; i < sizeof(frame); i++)
+    sum += frame[i];
+  return sum;
+}
+
+// does sbrk() map pages only when they are touched? does the
+// stack grow as needed, and does overflowing it fault?
+void
+lazytest(void)
+{
+  enum { SIZE = 64*1024*1024 };
+  uint64_t before;
+  int pid, status;
+  char *a;
+
+  printf("lazy test\n");
+  before = freepages();
+  a = sbrk(SIZE);
+  if(a == (char*)-1){
+    printf("lazy: sbrk failed\n");
+    exit(1);
+  }
+  if(before - freepages() > 16){
+    printf("lazy: sbrk allocated %d pages\n", (int)(before - freepages()));
+    exit(1);
+  }
+  if(a[0] != 0 || a[SIZE/2] != 0){
+    printf("lazy: fresh page not zero\n");
+    exit(1);
+  }
+  a[SIZE-1] = 1;
+  if(a[SIZE-1] != 1){
+    printf("lazy: write lost\n");
+    exit(1);
+  }
+  sbrk(-SIZE);
+
+  pid = fork();
+  if(pid == 0){
+    deep(1024*1024);
+    exit(0);
+  }
+  if(wait(&status) != pid || !WIFEXITED(status) || WEXITSTATUS(status) != 0){
+    printf("lazy: stack did not grow\n");
+    exit(1);
+  }
+
+  pid = fork();
+  if(pid == 0){
+    deep(64*1024*1024);
+    printf("lazy: stack overflow not caught\n");
+    exit(0);
+  }
+  if(wait(&status) != pid || !WIFSIGNALED(status) || WTERMSIG(status) != SIGSEGV){
+    printf("lazy: stack overflow not fatal\n");
+    exit(1);
+  }
+  printf("lazy test ok\n");
+}
+
+// does touching `p` for writing, in a child, kill it with SIGSEGV?
+int
+faults(char *p)
+{
+  int pid, status;
+
+  pid = fork();
+  if(pid == 0){
+    *p = 1;
+    exit(0);
+  }
+  return wait(&status) == pid && WIFSIGNALED(status) && WTERMSIG(status) == SIGSEGV;
+}
+
+// anonymous private and shared mappings, read-only file mappings,
+// mprotect and munmap.
+void
+mmaptest(void)
+{
+  enum { SIZE = 4 * 4096 };
+  int fd, i, pid, status;
+  char *p, *q;
+
+  printf("mmap test\n");
+  p = mmap(0, SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  q = mmap(0, SIZE, PROT_READ | PROT_WRITE, MAP_SHARED | MAP_ANONYMOUS, -1, 0);
+  if(p == MAP_FAILED || q == MAP_FAILED || p == q){
+    printf("mmap: anonymous mmap failed\n");
+    exit(1);
+  }
+  for(i = 0; i < SIZE; i++){
+    if(p[i] != 0 || q[i] != 0){
+      printf("mmap: fresh mapping not zero\n");
+      exit(1);
+    }
+  }
+  p[0] = 'p';
+  q[0] = 'q';
+  pid = fork();
+  if(pid == 0){
+    p[0] = 'c';
+    q[0] = 'c';
+    q[SIZE - 1] = 'c';
+    exit(0);
+  }
+  wait(&status);
+  if(p[0] != 'p' || q[0] != 'c' || q[SIZE - 1] != 'c'){
+    printf("mmap: private %c shared %c %c after child wrote\n", p[0], q[0], q[SIZE - 1]);
+    exit(1);
+  }
+
+  if(mprotect(p, SIZE, PROT_READ) < 0 || !faults(p) || p[0] != 'p'){
+    printf("mmap: mprotect read-only failed\n");
+    exit(1);
+  }
+  if(mprotect(p, 4096, PROT_READ | PROT_WRITE) < 0 || faults(p) || !faults(p + 4096)){
+    printf("mmap: mprotect of part failed\n");
+    exit(1);
+  }
+  if(munmap(p, SIZE) < 0 || munmap(q, SIZE) < 0 || !faults(p) || !faults(q)){
+    printf("mmap: munmap failed\n");
+    exit(1);
+  }
+  if(mprotect(p, SIZE, PROT_READ) == 0){
+    printf("mmap: mprotect of unmapped memory succeeded\n");
+    exit(1);
+  }
+
+  fd = open("mmapfile", O_CREATE | O_RDWR);
+  for(i = 0; i < sizeof(buf); i++)
+    buf[i] = 'a' + i % 26;
+  if(fd < 0 || write(fd, buf, 5000) != 5000){
+    printf("mmap: cannot write mmapfile\n");
+    exit(1);
+  }
+  close(fd);
+  fd = open("mmapfile", O_RDONLY);
+  if(mmap(0, 8192, PROT_READ | PROT_WRITE, MAP_PRIVATE, fd, 0) != MAP_FAILED){
+    printf("mmap: writable file mapping succeeded\n");
+    exit(1);
+  }
+  p = mmap(0, 8192, PROT_READ, MAP_PRIVATE, fd, 0);
+  close(fd);
+  unlink("mmapfile");
+  if(p == MAP_FAILED){
+    printf("mmap: file mmap failed\n");
+    exit(1);
+  }
+  for(i = 0; i < 8192; i++){
+    if(p[i] != (i < 5000 ? buf[i] : 0)){
+      printf("mmap: file mapping differs at %d\n", i);
+      exit(1);
+    }
+  }
+  if(!faults(p)){
+    printf("mmap: wrote to read-only file mapping\n");
+    exit(1);
+  }
+  munmap(p, 8192);
+  printf("mmap test ok\n");
+}
+
+// failed system calls return -1 and say why in errno.
+void
+errnotest(void)
+{
+  int pid, status;
+
+  printf("errno test\n");
+  if(open("nonexistent", O_RDONLY) >= 0 || errno != ENOENT){
+    printf("errno: open of nonexistent file: %s\n", strerror(errno));
+    exit(1);
+  }
+  if(close(100) >= 0 || errno != EBADF){
+    printf("errno: close of bad descriptor: %s\n", strerror(errno));
+    exit(1);
+  }
+  if(write(stdout, (void*)0xffff800000000000, 10) >= 0 || errno != EFAULT){
+    printf("errno: write from kernel address: %s\n", strerror(errno));
+    exit(1);
+  }
+  if(mkdir("errnodir", 0755) < 0 || mkdir("errnodir", 0755) >= 0 || errno != EEXIST){
+    printf("errno: mkdir of existing directory: %s\n", strerror(errno));
+    exit(1);
+  }
+  unlink("errnodir");
+  pid = fork();
+  if(pid == 0)
+    exit(wait(0) < 0 && errno == ECHILD ? 0 : 1);
+  if(pid < 0 || wait(&status) != pid || !WIFEXITED(status) || WEXITSTATUS(status) != 0){
+    printf("errno: wait with no children did not fail with ECHILD\n");
+    exit(1);
+  }
+  if(strcmp(strerror(ENOENT), "No such file or directory") != 0){
+    printf("errno: strerror(ENOENT) is %s\n", strerror(ENOENT));
+    exit(1);
+  }
+  printf("errno test ok\n");
+}
+
 void
 sbrktest(void)
 {
@@ -1814,6 +2224,7 @@ main(int argc, char *argv[])
   pipe1();
   preempt();
   exitwait();
+  waitpidtest();
 
   rmdot();
   twentyfour();
@@ -1824,6 +2235,11 @@ main(int argc, char *argv[])
   dirfile();
   iref();
   forktest();
+  cowtest();
+  forkstorm();
+  lazytest();
+  mmaptest();
+  errnotest();
   bigdir(); // slow
 
   uio();
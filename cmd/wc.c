@@ -1,3 +1,4 @@
+#include <errno.h>
 #include <fcntl.h>
 #include <stddef.h>
 #include <stdint.h>
@@ -48,7 +49,7 @@ main(int argc, char *argv[])
 
   for(i = 1; i < argc; i++){
     if((fd = open(argv[i], 0)) < 0){
-      dprintf(2, "wc: cannot open %s\n", argv[i]);
+      dprintf(2, "wc: cannot open %s: %s\n", argv[i], strerror(errno));
       exit(1);
     }
     wc(fd, argv[i]);
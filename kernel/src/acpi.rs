@@ -312,6 +312,7 @@ mod mcfg {
     }
 }
 
+pub use madt::cpus;
 #[cfg(not(any(test, clippy)))]
-pub use madt::{cpus, ioapics};
+pub use madt::ioapics;
 pub use mcfg::configs as pci_configs;
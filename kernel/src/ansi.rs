@@ -0,0 +1,288 @@
+// A parser for the subset of ANSI X3.64 (VT100) escape
+// sequences understood by the console display.
+//
+// Bytes are fed in one at a time; each either completes an
+// `Action` for the display to carry out, or is absorbed into a
+// partially parsed sequence.  The parser knows nothing about the
+// display itself: interpreting the actions, e.g. moving the
+// cursor for `ESC [ 5 ; 10 H`, is up to the caller.  Graphic
+// rendition (`ESC [ ... m`) is the exception, as it is just
+// arithmetic on attribute bits: see `Attr::apply`.
+
+const ESC: u8 = 0x1b;
+const MAXPARAMS: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+    // A CSI sequence we will discard, such as `ESC [ ? 25 l`.
+    CsiIgnore,
+}
+
+// The numeric parameters of a control sequence.  Omitted
+// parameters are 0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Params {
+    vals: [u16; MAXPARAMS],
+    len: usize,
+}
+
+impl Params {
+    const fn new() -> Params {
+        Params {
+            vals: [0; MAXPARAMS],
+            len: 0,
+        }
+    }
+
+    pub fn as_slice(&self) -> &[u16] {
+        &self.vals[..self.len]
+    }
+
+    // Returns parameter `k`, or `default` if it was omitted or 0.
+    pub fn get(&self, k: usize, default: u16) -> u16 {
+        match self.as_slice().get(k) {
+            Some(&0) | None => default,
+            Some(&n) => n,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    // A printable byte, or a control character other than ESC.
+    Print(u8),
+    // `ESC c`, `ESC 7` and so on.
+    Escape(u8),
+    // A control sequence: its final byte, and its parameters.
+    Csi(u8, Params),
+}
+
+#[derive(Debug)]
+pub struct Parser {
+    state: State,
+    params: Params,
+}
+
+impl Parser {
+    pub const fn new() -> Parser {
+        Parser {
+            state: State::Ground,
+            params: Params::new(),
+        }
+    }
+
+    pub fn feed(&mut self, b: u8) -> Option<Action> {
+        match (self.state, b) {
+            // CAN and SUB abort any sequence in progress.
+            (_, 0x18 | 0x1a) => {
+                self.state = State::Ground;
+                None
+            }
+            (_, ESC) => {
+                self.state = State::Escape;
+                None
+            }
+            (State::Ground, _) => Some(Action::Print(b)),
+            (State::Escape, b'[') => {
+                self.state = State::Csi;
+                self.params = Params::new();
+                None
+            }
+            (State::Escape, _) => {
+                self.state = State::Ground;
+                Some(Action::Escape(b))
+            }
+            (State::Csi | State::CsiIgnore, 0x40..=0x7e) => {
+                let ignore = self.state == State::CsiIgnore;
+                self.state = State::Ground;
+                if ignore {
+                    return None;
+                }
+                let params = &mut self.params;
+                params.len = params.len.clamp(1, MAXPARAMS);
+                Some(Action::Csi(b, *params))
+            }
+            (State::Csi, b'0'..=b'9') => {
+                let params = &mut self.params;
+                params.len = params.len.max(1);
+                if let Some(p) = params.vals.get_mut(params.len - 1) {
+                    *p = p.saturating_mul(10).saturating_add(u16::from(b - b'0'));
+                }
+                None
+            }
+            (State::Csi, b';') => {
+                // Parameters beyond MAXPARAMS are dropped.
+                let params = &mut self.params;
+                params.len = (params.len.max(1) + 1).min(MAXPARAMS + 1);
+                None
+            }
+            (State::Csi, 0x20..=0x3f) => {
+                // Private parameters (`?`) and intermediates.
+                self.state = State::CsiIgnore;
+                None
+            }
+            // Control characters are executed even in the middle
+            // of a sequence, as on a real terminal.
+            (State::Csi | State::CsiIgnore, 0..=0x1f) => Some(Action::Print(b)),
+            (State::Csi | State::CsiIgnore, _) => None,
+        }
+    }
+}
+
+// CGA colours are numbered IRGB, ANSI colours BGR.
+const CGA_COLOUR: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+
+// Graphic rendition, as it becomes a CGA attribute byte:
+// background colour in the high nibble, foreground in the low.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attr {
+    fg: u8,
+    bg: u8,
+    bold: bool,
+    blink: bool,
+    reverse: bool,
+}
+
+impl Attr {
+    pub const fn new() -> Attr {
+        Attr {
+            fg: 7,
+            bg: 0,
+            bold: false,
+            blink: false,
+            reverse: false,
+        }
+    }
+
+    pub fn byte(&self) -> u8 {
+        let (fg, bg) = if self.reverse {
+            (self.bg, self.fg)
+        } else {
+            (self.fg, self.bg)
+        };
+        let bright = if self.bold { 0x08 } else { 0 };
+        let blink = if self.blink { 0x80 } else { 0 };
+        blink | (bg & 0x07) << 4 | bright | fg
+    }
+
+    // Applies the parameters of an SGR (`ESC [ ... m`) sequence.
+    // Bright colours (90-97, 100-107) are approximated with the
+    // intensity bit for foreground colours; CGA has no bright
+    // backgrounds while blinking is enabled.
+    pub fn apply(&mut self, params: &[u16]) {
+        for &p in params {
+            match p {
+                0 => *self = Attr::new(),
+                1 => self.bold = true,
+                5 => self.blink = true,
+                7 => self.reverse = true,
+                22 => self.bold = false,
+                25 => self.blink = false,
+                27 => self.reverse = false,
+                30..=37 => self.fg = CGA_COLOUR[usize::from(p - 30)],
+                39 => self.fg = Attr::new().fg,
+                40..=47 => self.bg = CGA_COLOUR[usize::from(p - 40)],
+                49 => self.bg = Attr::new().bg,
+                90..=97 => {
+                    self.fg = CGA_COLOUR[usize::from(p - 90)];
+                    self.bold = true;
+                }
+                100..=107 => self.bg = CGA_COLOUR[usize::from(p - 100)],
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod ansi_tests {
+    use super::{Action, Attr, Parser};
+
+    fn feed_all(parser: &mut Parser, bytes: &[u8]) -> Option<(u8, Vec<u16>)> {
+        let mut last = None;
+        for &b in bytes {
+            last = match parser.feed(b) {
+                Some(Action::Csi(f, params)) => Some((f, params.as_slice().to_vec())),
+                Some(action) => panic!("unexpected {action:?}"),
+                None => None,
+            };
+        }
+        last
+    }
+
+    #[test]
+    fn prints_plain_bytes() {
+        let mut parser = Parser::new();
+        assert_eq!(parser.feed(b'a'), Some(Action::Print(b'a')));
+        assert_eq!(parser.feed(b'\n'), Some(Action::Print(b'\n')));
+    }
+
+    #[test]
+    fn parses_csi() {
+        let mut parser = Parser::new();
+        assert_eq!(
+            feed_all(&mut parser, b"\x1b[5;10H"),
+            Some((b'H', vec![5, 10]))
+        );
+        assert_eq!(feed_all(&mut parser, b"\x1b[H"), Some((b'H', vec![0])));
+        assert_eq!(feed_all(&mut parser, b"\x1b[;3H"), Some((b'H', vec![0, 3])));
+        assert_eq!(feed_all(&mut parser, b"\x1b[2J"), Some((b'J', vec![2])));
+        assert_eq!(parser.feed(b'x'), Some(Action::Print(b'x')));
+    }
+
+    #[test]
+    fn parses_escape() {
+        let mut parser = Parser::new();
+        assert_eq!(parser.feed(0x1b), None);
+        assert_eq!(parser.feed(b'7'), Some(Action::Escape(b'7')));
+    }
+
+    #[test]
+    fn ignores_private_and_cancelled_sequences() {
+        let mut parser = Parser::new();
+        assert_eq!(feed_all(&mut parser, b"\x1b[?25l"), None);
+        assert_eq!(feed_all(&mut parser, b"\x1b[12\x18"), None);
+        assert_eq!(parser.feed(b'm'), Some(Action::Print(b'm')));
+    }
+
+    #[test]
+    fn defaults_params() {
+        let mut parser = Parser::new();
+        for &b in b"\x1b[;4" {
+            assert_eq!(parser.feed(b), None);
+        }
+        let Some(Action::Csi(b'H', params)) = parser.feed(b'H') else {
+            panic!("no CSI");
+        };
+        assert_eq!(params.get(0, 1), 1);
+        assert_eq!(params.get(1, 1), 4);
+        assert_eq!(params.get(2, 1), 1);
+    }
+
+    #[test]
+    fn drops_extra_params() {
+        let mut parser = Parser::new();
+        let seq = b"\x1b[1;2;3;4;5;6;7;8;9;10m";
+        assert_eq!(
+            feed_all(&mut parser, seq),
+            Some((b'm', vec![1, 2, 3, 4, 5, 6, 7, 8]))
+        );
+    }
+
+    #[test]
+    fn maps_sgr_to_cga() {
+        let mut attr = Attr::new();
+        assert_eq!(attr.byte(), 0x07);
+        attr.apply(&[1, 31, 44]);
+        assert_eq!(attr.byte(), 0x1c);
+        attr.apply(&[7]);
+        assert_eq!(attr.byte(), 0x49);
+        attr.apply(&[27, 22, 39]);
+        assert_eq!(attr.byte(), 0x17);
+        attr.apply(&[0]);
+        assert_eq!(attr.byte(), 0x07);
+    }
+}
@@ -8,6 +8,7 @@ use crate::spinlock::SpinMutex as Mutex;
 use bitflags::bitflags;
 use core::cell::{Cell, RefCell};
 use core::ptr::null_mut;
+use syslib::errno::Errno;
 
 bitflags! {
     #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -253,7 +254,7 @@ fn bget(dev: u32, blockno: u64) -> Result<&'static Buf> {
             }
             p = b.meta.borrow().prev;
         }
-        Err("bget: no buffers")
+        Err(Errno::ENOMEM)
     })?;
     buf.lock.acquire();
     Ok(buf)
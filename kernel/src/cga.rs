@@ -1,6 +1,6 @@
+use crate::ansi::{Action, Attr, Params, Parser};
 use crate::volatile;
 use crate::x86_64::outb;
-use core::ptr::NonNull;
 
 const BASE_ADDR: usize = 0xffff_8000_000b_8000;
 const DISPLAY_HEIGHT: usize = 25;
@@ -8,11 +8,39 @@ const DISPLAY_WIDTH: usize = 80;
 const DISPLAY_LINE_SIZE: usize = DISPLAY_WIDTH * 2;
 const DISPLAY_SIZE: usize = DISPLAY_LINE_SIZE * DISPLAY_HEIGHT;
 const ATTRIBUTE: u8 = 0x07u8;
+const HISTORY_LINES: usize = 400;
 
+// The display interprets a subset of the VT100's escape
+// sequences (see `ansi.rs`): cursor movement and positioning,
+// erasing in the line and display, colours and other graphic
+// rendition, and saving and restoring the cursor.  Unlike a
+// VT100, backspace erases the character before the cursor, as
+// the console's line editing expects.
+//
+// Each virtual console has a `Cga` of its own.  Only the one
+// that is showing draws in display memory; the others draw in
+// a shadow buffer, which is copied in and out of display memory
+// as consoles are switched.
+//
+// Lines scrolled off the top of the screen are kept in a ring
+// of scrollback history, which the user may page through.  While
+// the view is scrolled back, the live screen is kept in the
+// shadow buffer, and any output returns the view to it.
 pub struct Cga {
     line: usize,
     column: usize,
-    buffer: NonNull<[u8; DISPLAY_SIZE]>,
+    attr: Attr,
+    saved: (usize, usize, Attr),
+    parser: Parser,
+    visible: bool,
+    shadow: [u8; DISPLAY_SIZE],
+    history: [u8; HISTORY_LINES * DISPLAY_LINE_SIZE],
+    // The number of lines of history, and the slot that the
+    // next line scrolled off the screen will go into.
+    saved_lines: usize,
+    next_line: usize,
+    // How many lines the view is scrolled back; 0 when live.
+    view: usize,
 }
 
 fn bshift(dst: &mut [u8], offset: usize) {
@@ -29,34 +57,148 @@ impl Cga {
         Cga {
             line: 0,
             column: 0,
-            buffer: unsafe { NonNull::new_unchecked(BASE_ADDR as *mut _) },
+            attr: Attr::new(),
+            saved: (0, 0, Attr::new()),
+            parser: Parser::new(),
+            visible: false,
+            shadow: [0; DISPLAY_SIZE],
+            history: [0; HISTORY_LINES * DISPLAY_LINE_SIZE],
+            saved_lines: 0,
+            next_line: 0,
+            view: 0,
         }
     }
 
+    fn display() -> &'static mut [u8; DISPLAY_SIZE] {
+        unsafe { &mut *(BASE_ADDR as *mut [u8; DISPLAY_SIZE]) }
+    }
+
+    // The live screen.  Writing to it returns the view to it.
     fn buffer_mut_slice(&mut self) -> &mut [u8] {
-        unsafe { self.buffer.as_mut() }
+        self.set_view(0);
+        if self.visible {
+            Self::display()
+        } else {
+            &mut self.shadow
+        }
+    }
+
+    // Puts this console on the display.
+    pub fn show(&mut self) {
+        if !self.visible {
+            volatile::copy_slice(Self::display(), &self.shadow);
+            self.visible = true;
+        }
+        self.set_cursor();
+    }
+
+    // Takes this console off the display, preserving its contents.
+    pub fn hide(&mut self) {
+        if self.visible {
+            if self.view == 0 {
+                volatile::copy_slice(&mut self.shadow, Self::display());
+            }
+            self.view = 0;
+            self.visible = false;
+        }
+    }
+
+    // Pages the view back through the scrollback history.
+    pub fn page_up(&mut self) {
+        self.set_view(self.view + DISPLAY_HEIGHT - 1);
+    }
+
+    // Pages the view forward towards the live screen.
+    pub fn page_down(&mut self) {
+        self.set_view(self.view.saturating_sub(DISPLAY_HEIGHT - 1));
+    }
+
+    // Scrolls the view back `view` lines from the live screen.
+    fn set_view(&mut self, view: usize) {
+        let view = view.min(self.saved_lines);
+        if !self.visible || view == self.view {
+            return;
+        }
+        if self.view == 0 {
+            volatile::copy_slice(&mut self.shadow, Self::display());
+        }
+        self.view = view;
+        if view == 0 {
+            volatile::copy_slice(Self::display(), &self.shadow);
+        } else {
+            let display = Self::display();
+            for k in 0..DISPLAY_HEIGHT {
+                let off = k * DISPLAY_LINE_SIZE;
+                let line = self.line_at(self.saved_lines - view + k);
+                volatile::copy_slice(&mut display[off..off + DISPLAY_LINE_SIZE], line);
+            }
+        }
+        self.set_cursor();
+    }
+
+    // Returns line `k` of the history followed by the live
+    // screen, counting from the oldest line of history.  The live
+    // screen must be in the shadow buffer.
+    fn line_at(&self, k: usize) -> &[u8] {
+        let (buffer, line) = if k < self.saved_lines {
+            let slot = (self.next_line + HISTORY_LINES - self.saved_lines + k) % HISTORY_LINES;
+            (&self.history[..], slot)
+        } else {
+            (&self.shadow[..], k - self.saved_lines)
+        };
+        let off = line * DISPLAY_LINE_SIZE;
+        &buffer[off..off + DISPLAY_LINE_SIZE]
+    }
+
+    // Adds the top line of the live screen to the history.
+    fn save_top_line(&mut self) {
+        let mut top = [0u8; DISPLAY_LINE_SIZE];
+        volatile::copy_slice(&mut top, &self.buffer_mut_slice()[..DISPLAY_LINE_SIZE]);
+        let off = self.next_line * DISPLAY_LINE_SIZE;
+        self.history[off..off + DISPLAY_LINE_SIZE].copy_from_slice(&top);
+        self.next_line = (self.next_line + 1) % HISTORY_LINES;
+        self.saved_lines = (self.saved_lines + 1).min(HISTORY_LINES);
     }
 
     pub fn blank(&mut self) {
         self.line = 0;
         self.column = 0;
-        volatile::zero_slice(self.buffer_mut_slice());
+        self.erase(0, DISPLAY_WIDTH * DISPLAY_HEIGHT);
+    }
+
+    // Blanks the character cells from `start` up to `end`,
+    // counting from the top left of the display, leaving them in
+    // the current background colour.
+    fn erase(&mut self, start: usize, end: usize) {
+        let blank = [b' ', self.attr.byte() & 0x70 | ATTRIBUTE];
+        let buffer = self.buffer_mut_slice();
+        for off in (start * 2..end * 2).step_by(2) {
+            volatile::copy_slice(&mut buffer[off..off + 2], &blank);
+        }
     }
 
     fn scroll(&mut self) {
+        self.save_top_line();
         let len = DISPLAY_SIZE - DISPLAY_LINE_SIZE;
         let buffer = self.buffer_mut_slice();
         bshift(buffer, DISPLAY_LINE_SIZE);
-        volatile::zero_slice(&mut buffer[len..len + DISPLAY_LINE_SIZE]);
+        let last = len / 2;
+        self.erase(last, last + DISPLAY_WIDTH);
         self.line -= 1;
         self.set_cursor();
     }
 
     fn set_cursor(&mut self) {
-        let off = self.line * DISPLAY_LINE_SIZE + self.column * 2;
-        let buf = [b' ', 0x07u8];
-        volatile::copy_slice(&mut self.buffer_mut_slice()[off..off + 2], &buf);
-        let pos = self.line * DISPLAY_WIDTH + self.column;
+        if !self.visible {
+            return;
+        }
+        // While scrolled back, the cursor is moved off the screen,
+        // which hides it.
+        let pos = if self.view == 0 {
+            self.line * DISPLAY_WIDTH + self.column
+        } else {
+            DISPLAY_WIDTH * DISPLAY_HEIGHT
+        };
         const INDEX_REG: u16 = 0x3d4;
         const DATA_REG: u16 = 0x3d5;
         const CURSOR_LOC_HI: u8 = 0x0E;
@@ -78,10 +220,21 @@ impl Cga {
     }
 
     pub fn putb(&mut self, b: u8) {
+        self.set_view(0);
+        match self.parser.feed(b) {
+            Some(Action::Print(b)) => self.put(b),
+            Some(Action::Escape(b)) => self.escape(b),
+            Some(Action::Csi(f, params)) => self.csi(f, &params),
+            None => {}
+        }
+        self.set_cursor();
+    }
+
+    fn put(&mut self, b: u8) {
         const BS: u8 = b'\x08'; // Backspace.
         match b {
             b'\n' => self.newline(),
-            b'\r' => {}
+            b'\r' => self.column = 0,
             b'\t' => {
                 let mut tabstop = self.column + 8 - (self.column % 8);
                 if tabstop >= DISPLAY_WIDTH {
@@ -89,27 +242,89 @@ impl Cga {
                     tabstop -= DISPLAY_WIDTH
                 }
                 while self.column < tabstop {
-                    self.putb(b' ')
+                    self.put(b' ')
                 }
             }
             BS => {
                 if self.column > 0 {
                     self.column -= 1;
-                    self.putb(b' ');
+                    self.put(b' ');
                     self.column -= 1;
                 }
             }
+            // Other control characters, such as BEL, are ignored.
+            0..=0x1f => {}
             _ => {
-                let buf = [b, ATTRIBUTE];
+                let buf = [b, self.attr.byte()];
                 let off = self.column * 2 + (self.line * DISPLAY_LINE_SIZE);
                 volatile::copy_slice(&mut self.buffer_mut_slice()[off..off + 2], &buf);
                 self.column += 1;
                 if self.column >= DISPLAY_WIDTH {
-                    self.putb(b'\n');
+                    self.newline();
                 }
             }
         }
-        self.set_cursor();
+    }
+
+    fn escape(&mut self, b: u8) {
+        match b {
+            b'7' => self.save_cursor(),
+            b'8' => self.restore_cursor(),
+            b'c' => {
+                self.attr = Attr::new();
+                self.blank();
+            }
+            _ => {}
+        }
+    }
+
+    fn csi(&mut self, f: u8, params: &Params) {
+        let n = usize::from(params.get(0, 1));
+        match f {
+            b'A' => self.line = self.line.saturating_sub(n),
+            b'B' => self.line = (self.line + n).min(DISPLAY_HEIGHT - 1),
+            b'C' => self.column = (self.column + n).min(DISPLAY_WIDTH - 1),
+            b'D' => self.column = self.column.saturating_sub(n),
+            b'G' => self.column = n.min(DISPLAY_WIDTH) - 1,
+            b'd' => self.line = n.min(DISPLAY_HEIGHT) - 1,
+            b'H' | b'f' => {
+                let column = usize::from(params.get(1, 1));
+                self.line = n.min(DISPLAY_HEIGHT) - 1;
+                self.column = column.min(DISPLAY_WIDTH) - 1;
+            }
+            b'J' => {
+                let cursor = self.line * DISPLAY_WIDTH + self.column;
+                let end = DISPLAY_WIDTH * DISPLAY_HEIGHT;
+                match params.get(0, 0) {
+                    0 => self.erase(cursor, end),
+                    1 => self.erase(0, cursor + 1),
+                    2 => self.erase(0, end),
+                    _ => {}
+                }
+            }
+            b'K' => {
+                let start = self.line * DISPLAY_WIDTH;
+                let cursor = start + self.column;
+                match params.get(0, 0) {
+                    0 => self.erase(cursor, start + DISPLAY_WIDTH),
+                    1 => self.erase(start, cursor + 1),
+                    2 => self.erase(start, start + DISPLAY_WIDTH),
+                    _ => {}
+                }
+            }
+            b'm' => self.attr.apply(params.as_slice()),
+            b's' => self.save_cursor(),
+            b'u' => self.restore_cursor(),
+            _ => {}
+        }
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved = (self.line, self.column, self.attr);
+    }
+
+    fn restore_cursor(&mut self) {
+        (self.line, self.column, self.attr) = self.saved;
     }
 
     pub fn puts(&mut self, s: &str) {
@@ -151,9 +366,57 @@ impl Cga {
         }
     }
 
-    pub fn put_string_at(&mut self, x: usize, y: usize, s: &str) {
-        for (i, b) in s.bytes().enumerate() {
+    pub fn put_string_at(&mut self, x: usize, y: usize, s: &[u8]) {
+        for (i, &b) in s.iter().enumerate() {
             self.put_char_at(x + i, y, b);
         }
     }
+
+    // Moves the cursor, and so the point at which `putb` will
+    // next write, to column `x` of line `y`.
+    pub fn move_to(&mut self, x: usize, y: usize) {
+        self.column = x.min(DISPLAY_WIDTH - 1);
+        self.line = y.min(DISPLAY_HEIGHT - 1);
+        self.set_cursor();
+    }
+}
+
+#[cfg(test)]
+mod cga_tests {
+    use super::{Cga, DISPLAY_HEIGHT, HISTORY_LINES};
+
+    fn line_text(cga: &Cga, k: usize) -> Vec<u8> {
+        let line: Vec<u8> = cga.line_at(k).iter().step_by(2).copied().collect();
+        line.trim_ascii_end().to_vec()
+    }
+
+    #[test]
+    fn keeps_scrolled_lines() {
+        let mut cga = Box::new(Cga::new());
+        cga.blank();
+        for k in 0..DISPLAY_HEIGHT + 5 {
+            for b in format!("line {k}\n").bytes() {
+                cga.putb(b);
+            }
+        }
+        assert_eq!(cga.saved_lines, 6);
+        assert_eq!(line_text(&cga, 0), b"line 0");
+        assert_eq!(line_text(&cga, 5), b"line 5");
+        assert_eq!(line_text(&cga, 6), b"line 6");
+    }
+
+    #[test]
+    fn history_wraps() {
+        let mut cga = Box::new(Cga::new());
+        cga.blank();
+        let total = HISTORY_LINES + DISPLAY_HEIGHT + 10;
+        for k in 0..total {
+            for b in format!("{k}\n").bytes() {
+                cga.putb(b);
+            }
+        }
+        assert_eq!(cga.saved_lines, HISTORY_LINES);
+        let oldest = total + 1 - DISPLAY_HEIGHT - HISTORY_LINES;
+        assert_eq!(line_text(&cga, 0), oldest.to_string().as_bytes());
+    }
 }
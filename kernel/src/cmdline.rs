@@ -0,0 +1,318 @@
+use crate::keymap;
+use crate::kmem;
+use crate::param;
+use core::cell::SyncUnsafeCell;
+use core::cmp;
+use syslib::keymap::Keymap;
+
+// The kernel command line is a sequence of whitespace-separated
+// `key=value` options, for example:
+//
+//   init=/bin/sh root=sd0p1 console=serial mode=portfolio keymap=trq
+//
+// It is parsed once, early in boot, into a static `Cmdline`.
+// The boot loader leaves the string in low memory that is later
+// handed to the page allocator, so we keep a copy of anything we
+// need.  Words that are not options we know about (such as the
+// kernel file name that QEMU prepends) are ignored; options we
+// know with values we cannot make sense of are reported and
+// left at their defaults.
+
+// Message levels, as in Unix: lower numbers are more urgent.
+pub const LOG_INFO: u8 = 6;
+const DEFAULT_LOGLEVEL: u8 = 7;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Console {
+    Serial,
+    Cga,
+    Both,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    // `/init` runs `sh` on the console.
+    Xv6,
+    // `/init` runs `portfolio`, which draws pages from `/pages`
+    // on the console display, in place of `sh`.
+    Portfolio,
+}
+
+const MAXLINE: usize = 256;
+const MAXPATH: usize = 64;
+const DEFAULT_INIT: &[u8] = b"/init";
+
+#[derive(Debug)]
+pub struct Cmdline {
+    line: [u8; MAXLINE],
+    line_len: usize,
+    init: [u8; MAXPATH],
+    init_len: usize,
+    root: u32,
+    console: Console,
+    mode: Option<Mode>,
+    keymap: &'static Keymap,
+    loglevel: u8,
+}
+
+impl Cmdline {
+    pub const fn new() -> Cmdline {
+        let mut init = [0u8; MAXPATH];
+        let mut k = 0;
+        while k < DEFAULT_INIT.len() {
+            init[k] = DEFAULT_INIT[k];
+            k += 1;
+        }
+        Cmdline {
+            line: [0; MAXLINE],
+            line_len: 0,
+            init,
+            init_len: DEFAULT_INIT.len(),
+            root: param::ROOTDEV,
+            console: Console::Both,
+            mode: None,
+            keymap: &keymap::US,
+            loglevel: DEFAULT_LOGLEVEL,
+        }
+    }
+
+    // Parses `line`, calling `bad` with each recognized option
+    // whose value is invalid.  Only as much of the line as we keep
+    // is parsed, so that `report` sees the same options.
+    pub fn parse<F: FnMut(&[u8])>(line: &[u8], mut bad: F) -> Cmdline {
+        let mut cmdline = Cmdline::new();
+        let line = &line[..cmp::min(line.len(), MAXLINE)];
+        cmdline.line[..line.len()].copy_from_slice(line);
+        cmdline.line_len = line.len();
+        for word in line.split(|b| b.is_ascii_whitespace()) {
+            let Some(eq) = word.iter().position(|&b| b == b'=') else {
+                continue;
+            };
+            let (key, value) = (&word[..eq], &word[eq + 1..]);
+            let ok = match key {
+                b"init" => cmdline.set_init(value),
+                b"root" => parse_root(value).map(|dev| cmdline.root = dev).is_some(),
+                b"console" => parse_console(value)
+                    .map(|console| cmdline.console = console)
+                    .is_some(),
+                b"mode" => parse_mode(value)
+                    .map(|mode| cmdline.mode = Some(mode))
+                    .is_some(),
+                b"keymap" => keymap::find(value)
+                    .map(|keymap| cmdline.keymap = keymap)
+                    .is_some(),
+                b"loglevel" => parse_u8(value)
+                    .map(|level| cmdline.loglevel = level)
+                    .is_some(),
+                _ => true,
+            };
+            if !ok {
+                bad(word);
+            }
+        }
+        cmdline
+    }
+
+    fn set_init(&mut self, path: &[u8]) -> bool {
+        if path.first() != Some(&b'/') || path.len() >= MAXPATH {
+            return false;
+        }
+        self.init[..path.len()].copy_from_slice(path);
+        self.init_len = path.len();
+        true
+    }
+
+    pub fn line(&self) -> &[u8] {
+        &self.line[..self.line_len]
+    }
+
+    pub fn init(&self) -> &[u8] {
+        &self.init[..self.init_len]
+    }
+
+    pub fn root(&self) -> u32 {
+        self.root
+    }
+
+    pub fn console(&self) -> Console {
+        self.console
+    }
+
+    pub fn mode(&self) -> Option<Mode> {
+        self.mode
+    }
+
+    pub fn keymap(&self) -> &'static Keymap {
+        self.keymap
+    }
+
+    pub fn loglevel(&self) -> u8 {
+        self.loglevel
+    }
+}
+
+// Root devices are named for the disk they live on: `sd0` is
+// the first (and, for now, only) AHCI drive.  The file system
+// occupies the whole disk, which is thus its one partition, so
+// `sd0p1` names it too; other disks and partitions do not exist.
+fn parse_root(value: &[u8]) -> Option<u32> {
+    match value {
+        b"sd0" | b"sd0p1" => Some(param::ROOTDEV),
+        _ => None,
+    }
+}
+
+fn parse_console(value: &[u8]) -> Option<Console> {
+    match value {
+        b"serial" => Some(Console::Serial),
+        b"cga" => Some(Console::Cga),
+        b"both" => Some(Console::Both),
+        _ => None,
+    }
+}
+
+fn parse_mode(value: &[u8]) -> Option<Mode> {
+    match value {
+        b"xv6" => Some(Mode::Xv6),
+        b"portfolio" => Some(Mode::Portfolio),
+        _ => None,
+    }
+}
+
+fn parse_u8(value: &[u8]) -> Option<u8> {
+    if value.is_empty() || value.len() > 3 {
+        return None;
+    }
+    let mut n = 0u32;
+    for &b in value {
+        if !b.is_ascii_digit() {
+            return None;
+        }
+        n = n * 10 + u32::from(b - b'0');
+    }
+    n.try_into().ok()
+}
+
+static CMDLINE: SyncUnsafeCell<Cmdline> = SyncUnsafeCell::new(Cmdline::new());
+
+fn cmdline() -> &'static Cmdline {
+    unsafe { &*CMDLINE.get() }
+}
+
+// Called once on the boot CPU, before anything consults the
+// command line and, in particular, before the console is set up.
+// Complaints are therefore deferred until `report`.
+pub unsafe fn init(boot_info_phys: u64) {
+    let line = unsafe { kmem::boot_cmdline(boot_info_phys) };
+    unsafe {
+        *CMDLINE.get() = Cmdline::parse(line, |_| {});
+    }
+}
+
+// Prints the command line and any options we could not use.
+pub fn report() {
+    let line = cmdline().line();
+    crate::println!("cmdline: {}", core::str::from_utf8(line).unwrap_or("?"));
+    Cmdline::parse(line, |word| {
+        let word = core::str::from_utf8(word).unwrap_or("?");
+        crate::println!("cmdline: ignoring bad option '{word}'");
+    });
+}
+
+// Records the boot mode when it was chosen some other way than
+// on the command line, e.g. from the boot menu.
+pub unsafe fn set_mode(mode: Mode) {
+    unsafe {
+        (*CMDLINE.get()).mode = Some(mode);
+    }
+}
+
+pub fn init_path() -> &'static [u8] {
+    cmdline().init()
+}
+
+// The argument passed to init, naming the program it should
+// run on the console.  Init runs `sh` when given none.
+pub fn init_arg() -> Option<&'static [u8]> {
+    match mode() {
+        Some(Mode::Portfolio) => Some(b"portfolio"),
+        _ => None,
+    }
+}
+
+pub fn root_dev() -> u32 {
+    cmdline().root()
+}
+
+pub fn console() -> Console {
+    cmdline().console()
+}
+
+pub fn mode() -> Option<Mode> {
+    cmdline().mode()
+}
+
+// The keyboard layout, `us` unless given as e.g. `keymap=trq`.
+pub fn keymap() -> &'static Keymap {
+    cmdline().keymap()
+}
+
+pub fn log_enabled(level: u8) -> bool {
+    level <= cmdline().loglevel()
+}
+
+#[cfg(test)]
+mod parse_tests {
+    use super::{Cmdline, Console, Mode};
+    use crate::{keymap, param};
+    use core::ptr;
+
+    #[test]
+    fn defaults() {
+        let c = Cmdline::parse(b"", |_| panic!("bad option"));
+        assert_eq!(c.init(), b"/init");
+        assert_eq!(c.root(), param::ROOTDEV);
+        assert_eq!(c.console(), Console::Both);
+        assert_eq!(c.mode(), None);
+        assert!(ptr::eq(c.keymap(), &keymap::US));
+        assert_eq!(c.loglevel(), 7);
+    }
+
+    #[test]
+    fn parses_options() {
+        let line = b"rxv64.elf32 init=/bin/sh root=sd0p1 console=serial mode=portfolio keymap=trq loglevel=4";
+        let c = Cmdline::parse(line, |_| panic!("bad option"));
+        assert_eq!(c.init(), b"/bin/sh");
+        assert_eq!(c.root(), param::ROOTDEV);
+        assert_eq!(c.console(), Console::Serial);
+        assert_eq!(c.mode(), Some(Mode::Portfolio));
+        assert!(ptr::eq(c.keymap(), &keymap::TRQ));
+        assert_eq!(c.loglevel(), 4);
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        let mut nbad = 0;
+        let c = Cmdline::parse(
+            b"init=sh root=sd1 console=vga mode=gui keymap=dvorak loglevel=999 root=sd0p2 quiet",
+            |_| nbad += 1,
+        );
+        assert_eq!(nbad, 7);
+        assert_eq!(c.init(), b"/init");
+        assert_eq!(c.root(), param::ROOTDEV);
+        assert_eq!(c.console(), Console::Both);
+        assert_eq!(c.mode(), None);
+        assert_eq!(c.loglevel(), 7);
+    }
+
+    #[test]
+    fn parses_what_it_keeps() {
+        let mut line = [b' '; super::MAXLINE + 16];
+        line[super::MAXLINE - 9..][..10].copy_from_slice(b"loglevel=4");
+        let mut bad = Vec::new();
+        let c = Cmdline::parse(&line, |word| bad.push(word.to_vec()));
+        assert_eq!(c.line().len(), super::MAXLINE);
+        assert_eq!(bad, [b"loglevel=".to_vec()]);
+        assert_eq!(c.loglevel(), 7);
+    }
+}
@@ -1,11 +1,19 @@
 use crate::Result;
+use crate::ansi::{Action, Parser};
 use crate::cga::Cga;
+use crate::cmdline;
 use crate::file::{self, File};
+use crate::param;
 use crate::proc;
 use crate::spinlock::SpinMutex as Mutex;
+use crate::trap;
 use crate::uart::Uart;
 use core::fmt;
+use syslib::errno::Errno;
+use syslib::screen;
+use syslib::signal::{SIGCONT, SIGHUP, SIGINT, SIGQUIT, SIGTSTP, SIGTTIN, sigbit};
 use syslib::stat::{FileType, Stat};
+use syslib::termios::{self, Termios, Winsize};
 
 const fn ctrl(b: u8) -> u8 {
     b - b'@'
@@ -16,15 +24,30 @@ const DELETE: u8 = 0x7F;
 const CTLD: u8 = ctrl(b'D');
 const CTLP: u8 = ctrl(b'P');
 const CTLU: u8 = ctrl(b'U');
+const CTLW: u8 = ctrl(b'W');
+const CTLA: u8 = ctrl(b'A');
+const CTLC: u8 = ctrl(b'C');
+const CTLBACKSLASH: u8 = ctrl(b'\\');
+const CTLE: u8 = ctrl(b'E');
+const CTLZ: u8 = ctrl(b'Z');
+
+// The number of virtual consoles.  Each is a minor device of
+// CONSOLE_MAJOR with its own screen and input queue.  The
+// keyboard feeds whichever is showing on the display; console
+// 0 is also connected to the serial line, and is where kernel
+// messages go.
+pub const NVT: usize = 6;
 
 pub struct Writers {
     uart: Option<Uart>,
-    cga: Option<Cga>,
+    vts: [Cga; NVT],
+    active: usize,
+    display: bool,
 }
 
 impl Writers {
-    fn putb(&mut self, b: u8) {
-        if let Some(uart) = self.uart.as_mut() {
+    fn putb(&mut self, vt: usize, b: u8) {
+        if let Some(uart) = self.uart.as_mut().filter(|_| vt == 0) {
             if b == b'\n' {
                 uart.putb(b'\r');
             } else if b == BACKSPACE {
@@ -33,16 +56,56 @@ impl Writers {
             }
             uart.putb(b);
         }
-        if let Some(cga) = self.cga.as_mut() {
-            cga.putb(b);
+        self.vts[vt].putb(b);
+    }
+
+    fn write(&mut self, vt: usize, s: &[u8]) {
+        for &b in s {
+            self.putb(vt, b);
+        }
+    }
+
+    fn switch(&mut self, vt: usize) {
+        if !self.display || vt == self.active {
+            return;
+        }
+        self.vts[self.active].hide();
+        self.vts[vt].show();
+        self.active = vt;
+    }
+
+    // Draws on the screen of console `vt` on behalf of a user
+    // program.  Text and boxes are clipped to the screen; moving
+    // the cursor also determines where subsequent output,
+    // including the echo of typed input, will appear.
+    fn draw(&mut self, vt: usize, op: usize, req: &screen::Request, text: &[u8]) -> Result<()> {
+        const WIDTH: usize = screen::WIDTH as usize;
+        const HEIGHT: usize = screen::HEIGHT as usize;
+        if !self.display {
+            return Err(Errno::ENODEV);
+        }
+        let (x, y) = (req.x as usize, req.y as usize);
+        let (w, h) = (req.w as usize, req.h as usize);
+        let cga = &mut self.vts[vt];
+        match op {
+            screen::CLEAR => cga.blank(),
+            screen::TEXT => cga.put_string_at(x, y, text),
+            screen::BOX if (2..=WIDTH).contains(&w) && (2..=HEIGHT).contains(&h) => {
+                cga.draw_box(x, y, w, h)
+            }
+            screen::BOX => return Err(Errno::EINVAL),
+            screen::CURSOR => cga.move_to(x, y),
+            _ => return Err(Errno::EINVAL),
         }
+        Ok(())
     }
 }
 
+// Kernel output goes to console 0.
 impl fmt::Write for Writers {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         for b in s.bytes() {
-            self.putb(b);
+            self.putb(0, b);
         }
         Ok(())
     }
@@ -52,7 +115,9 @@ pub static WRITER: Mutex<Writers> = Mutex::new(
     "cons",
     Writers {
         uart: Some(Uart::uart0()),
-        cga: Some(Cga::new()),
+        vts: [const { Cga::new() }; NVT],
+        active: 0,
+        display: true,
     },
 );
 
@@ -61,18 +126,47 @@ pub static WRITER: Mutex<Writers> = Mutex::new(
 pub fn puts(s: &[u8]) {
     let mut writer = WRITER.lock();
     for &b in s {
-        writer.putb(b);
+        writer.putb(0, b);
     }
-    writer.putb(b'\n');
+    writer.putb(0, b'\n');
 }
 
 pub unsafe fn init() {
     let mut writer = WRITER.lock();
-    if let Some(cga) = writer.cga.as_mut() {
-        cga.blank();
+    match cmdline::console() {
+        cmdline::Console::Serial => writer.display = false,
+        cmdline::Console::Cga => writer.uart = None,
+        cmdline::Console::Both => {}
+    }
+    if writer.display {
+        writer.vts[0].show();
+    }
+    for vt in writer.vts.iter_mut() {
+        vt.blank();
+    }
+}
+
+// Brings console `vt` to the display, e.g. on Alt+F<vt + 1>.
+pub fn switch(vt: usize) {
+    if vt < NVT {
+        WRITER.lock().switch(vt);
     }
 }
 
+// Pages the console on the display through its scrollback
+// history, e.g. on Shift+PgUp and Shift+PgDn.
+pub fn page(up: bool) {
+    let mut writer = WRITER.lock();
+    let active = writer.active;
+    let cga = &mut writer.vts[active];
+    if up { cga.page_up() } else { cga.page_down() }
+}
+
+// The console that keyboard input goes to.
+pub fn active() -> usize {
+    WRITER.lock().active
+}
+
 // The standard kernel println!() is protected by a mutex.
 #[cfg(not(test))]
 #[macro_export]
@@ -135,77 +229,427 @@ macro_rules! panic_print {
 /// that it is always greater than or equal to the write pointer.
 /// Once the user hits "Return", the write pointer is advanced
 /// to the edit pointer.
+///
+/// The line being edited also has a cursor, between the write
+/// and edit pointers, at which typing inserts.  The cursor is
+/// moved with the arrow keys, Home and End, or ^A and ^E; ^W
+/// deletes the word before it, and Delete the byte under it.
+/// The arrow keys arrive as escape sequences, which we parse
+/// as the display does.  Up and down recall lines from the
+/// console's history of those entered before.  Echo, including
+/// the redrawing of the rest of the line after an insertion or
+/// deletion, is written to `out`.
+///
+/// All of this is canonical mode, the default.  Programs that
+/// want each keystroke as it is typed turn it off with the IOCTL
+/// system call, and may turn off echo as well: see `Termios`.
 
 const CAPACITY: usize = 256;
+const NHISTORY: usize = 16;
+const MAXHISTLINE: usize = 128;
+
+struct History {
+    lines: [[u8; MAXHISTLINE]; NHISTORY],
+    lens: [usize; NHISTORY],
+    // The number of lines kept, and the slot for the next.
+    len: usize,
+    next: usize,
+}
+
+impl History {
+    const fn new() -> History {
+        History {
+            lines: [[0; MAXHISTLINE]; NHISTORY],
+            lens: [0; NHISTORY],
+            len: 0,
+            next: 0,
+        }
+    }
+
+    // Adds a line, unless it is empty, too long, or the same as
+    // the last.
+    fn add(&mut self, line: &[u8]) {
+        if line.is_empty() || line.len() > MAXHISTLINE || self.get(1) == Some(line) {
+            return;
+        }
+        self.lines[self.next][..line.len()].copy_from_slice(line);
+        self.lens[self.next] = line.len();
+        self.next = (self.next + 1) % NHISTORY;
+        self.len = (self.len + 1).min(NHISTORY);
+    }
+
+    // Returns the `k`th most recent line, counting from 1.
+    fn get(&self, k: usize) -> Option<&[u8]> {
+        if k == 0 || k > self.len {
+            return None;
+        }
+        let slot = (self.next + NHISTORY - k) % NHISTORY;
+        Some(&self.lines[slot][..self.lens[slot]])
+    }
+}
+
+type Echo<'a> = &'a mut dyn FnMut(&[u8]);
+
+// Moves the terminal's cursor `n` places in direction `dir`: `C`
+// for right, `D` for left.
+fn move_cursor(out: Echo, n: usize, dir: u8) {
+    if n == 0 {
+        return;
+    }
+    let mut seq = *b"\x1b[000D";
+    seq[2] = b'0' + (n / 100 % 10) as u8;
+    seq[3] = b'0' + (n / 10 % 10) as u8;
+    seq[4] = b'0' + (n % 10) as u8;
+    seq[5] = dir;
+    out(&seq);
+}
 
 struct Reader {
     buffer: [u8; CAPACITY],
     read_index: usize,
     write_index: usize,
     edit_index: usize,
+    cursor: usize,
+    parser: Parser,
+    // Whether the last input was `ESC O`, which some terminals
+    // send before the arrow and function keys.
+    ss3: bool,
+    history: History,
+    // Which line of history is being edited, if not 0; the line
+    // that was being typed is kept as the draft meanwhile.
+    recalled: usize,
+    draft: [u8; MAXHISTLINE],
+    draft_len: usize,
+    termios: Termios,
 }
 
 impl Reader {
-    fn len(&self) -> usize {
-        self.write_index.wrapping_sub(self.read_index)
+    const fn new() -> Reader {
+        Reader {
+            buffer: [0u8; CAPACITY],
+            read_index: 0,
+            write_index: 0,
+            edit_index: 0,
+            cursor: 0,
+            parser: Parser::new(),
+            ss3: false,
+            history: History::new(),
+            recalled: 0,
+            draft: [0; MAXHISTLINE],
+            draft_len: 0,
+            termios: Termios::new(),
+        }
     }
 
     fn is_empty(&self) -> bool {
         self.read_index == self.write_index
     }
 
+    fn canonical(&self) -> bool {
+        self.termios.lflag & termios::ICANON != 0
+    }
+
+    fn set_termios(&mut self, t: &Termios) {
+        // Anything typed but not yet entered becomes available
+        // as is when canonical mode is turned off.
+        if t.lflag & termios::ICANON == 0 {
+            self.cursor = self.edit_index;
+            self.write_index = self.edit_index;
+            self.parser = Parser::new();
+            self.ss3 = false;
+            self.recalled = 0;
+        }
+        self.termios = *t;
+    }
+
+    // Throws away everything typed and not yet read.
+    fn discard(&mut self) {
+        self.read_index = self.edit_index;
+        self.write_index = self.edit_index;
+        self.cursor = self.edit_index;
+        self.parser = Parser::new();
+        self.ss3 = false;
+        self.recalled = 0;
+    }
+
+    // Returns the signal to send for `b`, if it is one of the
+    // characters that send signals.
+    fn signal(&self, b: u8) -> Option<u32> {
+        if self.termios.lflag & termios::ISIG == 0 {
+            return None;
+        }
+        match b {
+            CTLC => Some(SIGINT),
+            CTLBACKSLASH => Some(SIGQUIT),
+            CTLZ => Some(SIGTSTP),
+            _ => None,
+        }
+    }
+
+    // Discards the line being edited, along with any input not
+    // yet read, echoing the control character that did so.
+    fn flush(&mut self, b: u8, out: Echo) {
+        self.read_index = self.write_index;
+        self.edit_index = self.write_index;
+        self.cursor = self.write_index;
+        self.recalled = 0;
+        self.parser = Parser::new();
+        self.ss3 = false;
+        if self.termios.lflag & termios::ECHO != 0 {
+            out(&[b'^', b + b'@', b'\n']);
+        }
+    }
+
     fn is_full(&self) -> bool {
         self.edit_index.wrapping_sub(self.read_index) == CAPACITY
     }
 
-    fn backspace(&mut self) {
-        if self.edit_index != self.write_index {
-            self.edit_index = self.edit_index.wrapping_sub(1);
-            WRITER.lock().putb(BACKSPACE);
+    fn at(&self, index: usize) -> u8 {
+        self.buffer[index % CAPACITY]
+    }
+
+    // Copies the line being edited into `line`, returning its
+    // length.
+    fn copy_line(&self, line: &mut [u8]) -> usize {
+        let len = self
+            .edit_index
+            .wrapping_sub(self.write_index)
+            .min(line.len());
+        for (k, b) in line[..len].iter_mut().enumerate() {
+            *b = self.at(self.write_index.wrapping_add(k));
         }
+        len
     }
 
-    fn kill(&mut self) {
-        while self.edit_index != self.write_index {
-            self.backspace();
+    // Echoes the line from the cursor on, followed by `erase`
+    // blanks to cover what it used to occupy, then returns the
+    // terminal's cursor to the reader's.
+    fn redraw_tail(&self, erase: usize, out: Echo) {
+        let mut index = self.cursor;
+        while index != self.edit_index {
+            out(&[self.at(index)]);
+            index = index.wrapping_add(1);
         }
+        for _ in 0..erase {
+            out(b" ");
+        }
+        let tail = self.edit_index.wrapping_sub(self.cursor);
+        move_cursor(out, tail + erase, b'D');
+    }
+
+    fn left(&mut self, out: Echo) {
+        if self.cursor != self.write_index {
+            self.cursor = self.cursor.wrapping_sub(1);
+            move_cursor(out, 1, b'D');
+        }
+    }
+
+    fn right(&mut self, out: Echo) {
+        if self.cursor != self.edit_index {
+            self.cursor = self.cursor.wrapping_add(1);
+            move_cursor(out, 1, b'C');
+        }
+    }
+
+    fn home(&mut self, out: Echo) {
+        move_cursor(out, self.cursor.wrapping_sub(self.write_index), b'D');
+        self.cursor = self.write_index;
+    }
+
+    fn end(&mut self, out: Echo) {
+        move_cursor(out, self.edit_index.wrapping_sub(self.cursor), b'C');
+        self.cursor = self.edit_index;
     }
 
-    pub fn put(&mut self, b: u8) -> Result<usize> {
+    fn insert(&mut self, b: u8, out: Echo) {
+        if self.is_full() {
+            return;
+        }
+        let mut index = self.edit_index;
+        while index != self.cursor {
+            let prev = index.wrapping_sub(1);
+            self.buffer[index % CAPACITY] = self.at(prev);
+            index = prev;
+        }
+        self.buffer[self.cursor % CAPACITY] = b;
+        self.edit_index = self.edit_index.wrapping_add(1);
+        self.cursor = self.cursor.wrapping_add(1);
+        out(&[b]);
+        self.redraw_tail(0, out);
+    }
+
+    // Removes the byte at the cursor, closing the gap.
+    fn remove(&mut self) {
+        let mut index = self.cursor;
+        while index.wrapping_add(1) != self.edit_index {
+            self.buffer[index % CAPACITY] = self.at(index.wrapping_add(1));
+            index = index.wrapping_add(1);
+        }
+        self.edit_index = self.edit_index.wrapping_sub(1);
+    }
+
+    fn backspace(&mut self, out: Echo) {
+        if self.cursor != self.write_index {
+            self.cursor = self.cursor.wrapping_sub(1);
+            self.remove();
+            out(&[BACKSPACE]);
+            let erase = usize::from(self.cursor != self.edit_index);
+            self.redraw_tail(erase, out);
+        }
+    }
+
+    fn delete(&mut self, out: Echo) {
+        if self.cursor != self.edit_index {
+            self.remove();
+            self.redraw_tail(1, out);
+        }
+    }
+
+    fn kill(&mut self, out: Echo) {
+        self.end(out);
+        while self.cursor != self.write_index {
+            self.backspace(out);
+        }
+    }
+
+    fn kill_word(&mut self, out: Echo) {
+        let before = |r: &Reader| r.at(r.cursor.wrapping_sub(1));
+        while self.cursor != self.write_index && before(self) == b' ' {
+            self.backspace(out);
+        }
+        while self.cursor != self.write_index && before(self) != b' ' {
+            self.backspace(out);
+        }
+    }
+
+    // Replaces the line being edited with the `k`th most recent
+    // line of history, or with the draft if `k` is 0.
+    fn recall(&mut self, k: usize, out: Echo) {
+        let mut line = [0u8; MAXHISTLINE];
+        let len = if k == 0 {
+            line = self.draft;
+            self.draft_len
+        } else {
+            let Some(old) = self.history.get(k) else {
+                return;
+            };
+            line[..old.len()].copy_from_slice(old);
+            old.len()
+        };
+        if self.recalled == 0 {
+            let mut draft = [0u8; MAXHISTLINE];
+            self.draft_len = self.copy_line(&mut draft);
+            self.draft = draft;
+        }
+        self.recalled = k;
+        self.kill(out);
+        for &b in &line[..len] {
+            self.insert(b, out);
+        }
+    }
+
+    // Makes the line being edited, followed by `end`, available
+    // to readers.
+    fn commit(&mut self, end: Option<u8>, out: Echo) {
+        self.end(out);
+        if end == Some(b'\n') {
+            let mut line = [0u8; MAXHISTLINE + 1];
+            let len = self.copy_line(&mut line);
+            if len <= MAXHISTLINE {
+                self.history.add(&line[..len]);
+            }
+            out(b"\n");
+        }
+        if let Some(b) = end {
+            self.buffer[self.edit_index % CAPACITY] = b;
+            self.edit_index = self.edit_index.wrapping_add(1);
+        }
+        self.cursor = self.edit_index;
+        self.write_index = self.edit_index;
+        self.recalled = 0;
+    }
+
+    fn key(&mut self, b: u8, out: Echo) -> bool {
         match b {
-            BACKSPACE | DELETE => {
-                self.backspace();
+            BACKSPACE | DELETE => self.backspace(out),
+            CTLU => self.kill(out),
+            CTLW => self.kill_word(out),
+            CTLA => self.home(out),
+            CTLE => self.end(out),
+            b'\r' | b'\n' | CTLD if self.is_full() => {
+                return false;
             }
-            CTLP => {
-                proc::dump();
+            b'\r' | b'\n' => {
+                self.commit(Some(b'\n'), out);
+                return true;
             }
-            CTLU => {
-                // Kill line.
-                self.kill();
+            CTLD => {
+                self.commit(Some(CTLD), out);
+                return true;
             }
-            _ => {
-                if self.is_full() {
-                    return Err("console overflow");
-                }
-                let b = if b == b'\r' { b'\n' } else { b };
-                self.buffer[self.edit_index % CAPACITY] = b;
-                self.edit_index = self.edit_index.wrapping_add(1);
-                WRITER.lock().putb(b);
-                if b == b'\n'
-                    || b == CTLD
-                    || self.edit_index == self.read_index.wrapping_add(CAPACITY)
-                {
-                    self.write_index = self.edit_index;
-                    proc::wakeup(self.read_chan());
-                }
+            _ => self.insert(b, out),
+        }
+        // A full buffer is made available even without a newline,
+        // as there is nothing else its reader could do.
+        if self.is_full() && self.edit_index != self.write_index {
+            self.commit(None, out);
+            return true;
+        }
+        false
+    }
+
+    // Handles the arrows and the editing keys, which arrive as
+    // `ESC [ final` or `ESC [ n ~`, or as `ESC O final`.
+    fn edit_key(&mut self, f: u8, n: u16, out: Echo) {
+        match (f, n) {
+            (b'A', _) => self.recall(self.recalled + 1, out),
+            (b'B', _) if self.recalled > 0 => self.recall(self.recalled - 1, out),
+            (b'C', _) => self.right(out),
+            (b'D', _) => self.left(out),
+            (b'H', _) | (b'~', 1 | 7) => self.home(out),
+            (b'F', _) | (b'~', 4 | 8) => self.end(out),
+            (b'~', 3) => self.delete(out),
+            _ => {}
+        }
+    }
+
+    // Puts a byte of input, returning true if it made input
+    // available to readers.
+    fn put(&mut self, b: u8, out: Echo) -> bool {
+        let mut quiet = |_: &[u8]| {};
+        let out: Echo = if self.termios.lflag & termios::ECHO != 0 {
+            out
+        } else {
+            &mut quiet
+        };
+        if !self.canonical() {
+            if self.is_full() {
+                return false;
             }
+            self.buffer[self.edit_index % CAPACITY] = b;
+            self.edit_index = self.edit_index.wrapping_add(1);
+            self.cursor = self.edit_index;
+            self.write_index = self.edit_index;
+            out(&[b]);
+            return true;
+        }
+        if self.ss3 {
+            self.ss3 = false;
+            self.edit_key(b, 0, out);
+            return false;
+        }
+        match self.parser.feed(b) {
+            Some(Action::Print(b)) => return self.key(b, out),
+            Some(Action::Escape(b'O')) => self.ss3 = true,
+            Some(Action::Csi(f, params)) => self.edit_key(f, params.get(0, 0), out),
+            Some(Action::Escape(_)) | None => {}
         }
-        Ok(self.len())
+        false
     }
 
     pub fn peek(&self) -> Result<u8> {
         if self.is_empty() {
-            return Err("console underflow");
+            return Err(Errno::EAGAIN);
         }
         Ok(self.buffer[self.read_index % CAPACITY])
     }
@@ -221,31 +665,183 @@ impl Reader {
     }
 }
 
-static READER: Mutex<Reader> = Mutex::new(
-    "input",
-    Reader {
-        buffer: [0u8; CAPACITY],
-        read_index: 0,
-        write_index: 0,
-        edit_index: 0,
-    },
+static READERS: Mutex<[Reader; NVT]> = Mutex::new("input", [const { Reader::new() }; NVT]);
+
+// The session, if any, of which a console is the controlling
+// terminal, and the process group in the foreground, to which
+// its signals are sent and which alone may read from it.
+#[derive(Clone, Copy)]
+struct Control {
+    session: u32,
+    pgrp: u32,
+}
+
+static CONTROL: Mutex<[Control; NVT]> = Mutex::new(
+    "control",
+    [Control {
+        session: 0,
+        pgrp: 0,
+    }; NVT],
 );
 
-pub struct Console {}
+// Makes console `vt` the controlling terminal of `proc`'s session
+// if `proc` leads a session without one and the console is free.
+pub fn attach(proc: &proc::Proc, vt: usize) {
+    if !proc.is_session_leader() || proc.tty().is_some() {
+        return;
+    }
+    let mut control = CONTROL.lock();
+    let Some(c) = control.get_mut(vt) else {
+        return;
+    };
+    if c.session == 0 {
+        *c = Control {
+            session: proc.sid(),
+            pgrp: proc.pgid(),
+        };
+        proc.set_tty(Some(vt));
+    }
+}
+
+// Detaches console `vt` from `session`, whose leader has exited,
+// hanging up on its foreground process group.
+pub fn hangup(vt: usize, session: u32) {
+    let pgrp = CONTROL.with_lock(|control| {
+        let c = &mut control[vt];
+        if c.session != session {
+            return None;
+        }
+        let pgrp = c.pgrp;
+        c.session = 0;
+        c.pgrp = 0;
+        Some(pgrp)
+    });
+    if let Some(pgrp) = pgrp {
+        proc::kill_pgrp(pgrp, SIGHUP);
+        proc::kill_pgrp(pgrp, SIGCONT);
+    }
+}
+
+// Sends `sig` to the foreground process group of console `vt`.
+fn signal_foreground(vt: usize, sig: u32) {
+    let c = CONTROL.lock()[vt];
+    if c.session != 0 {
+        proc::kill_pgrp(c.pgrp, sig);
+    }
+}
+
+pub struct Console {
+    vt: usize,
+}
+
+impl Console {
+    // A process in the background of its controlling terminal
+    // that tries to read from it is stopped with SIGTTIN, unless
+    // it would not stop, in which case the read fails.
+    fn check_foreground(&self) -> Result<()> {
+        let proc = proc::myproc();
+        let c = CONTROL.lock()[self.vt];
+        if proc.tty() != Some(self.vt) || c.session != proc.sid() || c.pgrp == proc.pgid() {
+            return Ok(());
+        }
+        let signals = proc.signals();
+        if signals.blocked() & sigbit(SIGTTIN) != 0 || signals.ignores(SIGTTIN) {
+            return Err(Errno::EIO);
+        }
+        proc::kill_pgrp(proc.pgid(), SIGTTIN);
+        Err(Errno::EINTR)
+    }
+
+    fn get_pgrp(&self) -> Result<u32> {
+        let proc = proc::myproc();
+        let c = CONTROL.lock()[self.vt];
+        if proc.tty() != Some(self.vt) || c.session != proc.sid() {
+            return Err(Errno::ENOTTY);
+        }
+        Ok(c.pgrp)
+    }
+
+    // Puts process group `pgrp`, which must be in the caller's
+    // session, in the foreground.
+    fn set_pgrp(&self, pgrp: u32) -> Result<()> {
+        let proc = proc::myproc();
+        if proc.tty() != Some(self.vt) {
+            return Err(Errno::ENOTTY);
+        }
+        if !proc::pgrp_in_session(pgrp, proc.sid()) {
+            return Err(Errno::EPERM);
+        }
+        let mut control = CONTROL.lock();
+        let c = &mut control[self.vt];
+        if c.session != proc.sid() {
+            return Err(Errno::ENOTTY);
+        }
+        c.pgrp = pgrp;
+        Ok(())
+    }
+
+    // Reads outside canonical mode, following `termios.vmin` and
+    // `termios.vtime`.  While a timer is running, we sleep on the
+    // clock rather than on input, and look for input at each tick.
+    fn read_raw(&self, buf: &mut [u8], termios: &Termios) -> Result<usize> {
+        let vmin = usize::from(termios.vmin).min(buf.len());
+        let timeout = u64::from(termios.vtime) * param::HZ / 10;
+        let mut start = trap::ticks();
+        let mut n = 0;
+        let mut readers = READERS.lock();
+        loop {
+            let reader = &mut readers[self.vt];
+            let n0 = n;
+            while n < buf.len() {
+                let Ok(b) = reader.get() else {
+                    break;
+                };
+                buf[n] = b;
+                n += 1;
+            }
+            if n > n0 && vmin > 0 {
+                start = trap::ticks();
+            }
+            let timed = timeout > 0 && (vmin == 0 || n > 0);
+            if n >= vmin.max(1)
+                || (timeout == 0 && vmin == 0)
+                || (timed && trap::ticks().wrapping_sub(start) >= timeout)
+            {
+                return Ok(n);
+            }
+            if proc::myproc().interrupted() {
+                return Err(Errno::EINTR);
+            }
+            let chan = if timed {
+                trap::wake_at(trap::ticks() + 1);
+                trap::tickchan()
+            } else {
+                reader.read_chan()
+            };
+            proc::myproc().sleep(chan, &READERS);
+        }
+    }
+}
 
 impl file::Like for Console {
     fn close(&self) {}
 
     fn read(&self, _: &File, buf: &mut [u8]) -> Result<usize> {
+        self.check_foreground()?;
+        let t = READERS.lock()[self.vt].termios;
+        if t.lflag & termios::ICANON == 0 {
+            return self.read_raw(buf, &t);
+        }
         let mut n = 0;
         while n < buf.len() {
-            let mut reader = READER.lock();
+            let mut readers = READERS.lock();
+            let reader = &mut readers[self.vt];
             while reader.is_empty() {
-                if proc::myproc().dead() {
-                    return Err("killed");
+                if proc::myproc().interrupted() {
+                    return Err(Errno::EINTR);
                 }
                 let rchan = reader.read_chan();
-                proc::myproc().sleep(rchan, &READER);
+                proc::myproc().sleep(rchan, &READERS);
             }
             let b = reader.peek().expect("console buffer empty");
             if b == CTLD {
@@ -262,13 +858,33 @@ impl file::Like for Console {
     }
 
     fn write(&self, _: &File, buf: &[u8]) -> Result<usize> {
-        let mut writer = WRITER.lock();
-        for &b in buf {
-            writer.putb(b);
-        }
+        WRITER.lock().write(self.vt, buf);
         Ok(buf.len())
     }
 
+    fn screen(&self, op: usize, req: &screen::Request, text: &[u8]) -> Result<()> {
+        WRITER.lock().draw(self.vt, op, req, text)
+    }
+
+    fn ioctl(&self, req: file::Ioctl) -> Result<()> {
+        match req {
+            file::Ioctl::GetTermios(t) => *t = READERS.lock()[self.vt].termios,
+            file::Ioctl::SetTermios(t) => READERS.lock()[self.vt].set_termios(t),
+            file::Ioctl::DiscardInput => READERS.lock()[self.vt].discard(),
+            file::Ioctl::GetWinsize(ws) => {
+                *ws = Winsize {
+                    rows: screen::HEIGHT as u16,
+                    cols: screen::WIDTH as u16,
+                    xpixel: 0,
+                    ypixel: 0,
+                }
+            }
+            file::Ioctl::GetPgrp(pgrp) => *pgrp = self.get_pgrp()?,
+            file::Ioctl::SetPgrp(pgrp) => self.set_pgrp(pgrp)?,
+        }
+        Ok(())
+    }
+
     fn stat(&self) -> Result<Stat> {
         Ok(Stat {
             typ: FileType::Dev,
@@ -280,17 +896,150 @@ impl file::Like for Console {
     }
 }
 
-static CONSOLE: Console = Console {};
+static CONSOLES: [Console; NVT] = {
+    let mut consoles = [const { Console { vt: 0 } }; NVT];
+    let mut vt = 0;
+    while vt < NVT {
+        consoles[vt].vt = vt;
+        vt += 1;
+    }
+    consoles
+};
 
-pub fn interrupt<F: FnMut() -> Option<u8>>(mut getb: F) {
+// Feeds input from `getb` to console `vt`.
+pub fn interrupt<F: FnMut() -> Option<u8>>(vt: usize, mut getb: F) {
     while let Some(b) = getb() {
-        let mut reader = READER.lock();
-        let _ = reader.put(b);
+        let mut readers = READERS.lock();
+        let reader = &mut readers[vt];
+        if b == CTLP && reader.canonical() {
+            proc::dump();
+            continue;
+        }
+        if let Some(sig) = reader.signal(b) {
+            reader.flush(b, &mut |s| WRITER.lock().write(vt, s));
+            signal_foreground(vt, sig);
+            continue;
+        }
+        if reader.put(b, &mut |s| WRITER.lock().write(vt, s)) {
+            proc::wakeup(reader.read_chan());
+        }
     }
 }
 
 pub const CONSOLE_MAJOR: u32 = 0;
 
-pub fn consdev() -> &'static dyn file::Like {
-    &CONSOLE
+pub fn consdev(minor: u32) -> Result<&'static dyn file::Like> {
+    let console = CONSOLES.get(minor as usize).ok_or(Errno::ENXIO)?;
+    Ok(console)
+}
+
+#[cfg(test)]
+mod reader_tests {
+    use super::Reader;
+    use syslib::signal::{SIGINT, SIGTSTP};
+    use syslib::termios::{self, Termios};
+
+    fn type_in(reader: &mut Reader, input: &[u8]) -> (bool, Vec<u8>) {
+        let mut echo = Vec::new();
+        let mut ready = false;
+        for &b in input {
+            ready |= reader.put(b, &mut |s| echo.extend_from_slice(s));
+        }
+        (ready, echo)
+    }
+
+    fn read_line(reader: &mut Reader) -> Vec<u8> {
+        let mut line = Vec::new();
+        while let Ok(b) = reader.get() {
+            line.push(b);
+        }
+        line
+    }
+
+    #[test]
+    fn edits_in_the_middle() {
+        let mut reader = Box::new(Reader::new());
+        let (ready, echo) = type_in(&mut reader, b"helo\x1b[Dl");
+        assert!(!ready);
+        assert_eq!(echo, b"helo\x1b[001Dlo\x1b[001D");
+        let (ready, _) = type_in(&mut reader, b"\x1b[H\x1b[3~H\x1bOF!\x08\r");
+        assert!(ready);
+        assert_eq!(read_line(&mut reader), b"Hello\n");
+    }
+
+    #[test]
+    fn deletes_words_and_lines() {
+        let mut reader = Box::new(Reader::new());
+        type_in(&mut reader, b"echo hello  world  \x17\x17there\n");
+        assert_eq!(read_line(&mut reader), b"echo there\n");
+        type_in(&mut reader, b"junk\x01\x15ls\x04");
+        assert_eq!(read_line(&mut reader), b"ls\x04");
+    }
+
+    #[test]
+    fn passes_raw_input() {
+        let mut reader = Box::new(Reader::new());
+        type_in(&mut reader, b"ab");
+        let raw = Termios {
+            lflag: termios::ECHO,
+            vmin: 1,
+            vtime: 0,
+        };
+        reader.set_termios(&raw);
+        assert_eq!(read_line(&mut reader), b"ab");
+        let (ready, echo) = type_in(&mut reader, b"\x1b[A\x08");
+        assert!(ready);
+        assert_eq!(echo, b"\x1b[A\x08");
+        assert_eq!(read_line(&mut reader), b"\x1b[A\x08");
+        reader.set_termios(&Termios {
+            lflag: termios::ICANON,
+            ..raw
+        });
+        let (ready, echo) = type_in(&mut reader, b"hi\n");
+        assert!(ready);
+        assert!(echo.is_empty());
+        assert_eq!(read_line(&mut reader), b"hi\n");
+    }
+
+    #[test]
+    fn discards_unread_input() {
+        let mut reader = Box::new(Reader::new());
+        type_in(&mut reader, b"lost\nhalf a li");
+        reader.discard();
+        assert_eq!(read_line(&mut reader), b"");
+        type_in(&mut reader, b"kept\n");
+        assert_eq!(read_line(&mut reader), b"kept\n");
+    }
+
+    #[test]
+    fn interrupts_discard_input() {
+        let mut reader = Box::new(Reader::new());
+        type_in(&mut reader, b"ls\nhalf a li");
+        assert_eq!(reader.signal(0x03), Some(SIGINT));
+        assert_eq!(reader.signal(0x1a), Some(SIGTSTP));
+        assert_eq!(reader.signal(b'c'), None);
+        let mut echo = Vec::new();
+        reader.flush(0x03, &mut |s| echo.extend_from_slice(s));
+        assert_eq!(echo, b"^C\n");
+        assert_eq!(read_line(&mut reader), b"");
+        type_in(&mut reader, b"ok\n");
+        assert_eq!(read_line(&mut reader), b"ok\n");
+        reader.set_termios(&Termios {
+            lflag: termios::ICANON,
+            ..Termios::new()
+        });
+        assert_eq!(reader.signal(0x03), None);
+    }
+
+    #[test]
+    fn recalls_history() {
+        let mut reader = Box::new(Reader::new());
+        type_in(&mut reader, b"one\ntwo\ntwo\n\nthr");
+        read_line(&mut reader);
+        type_in(&mut reader, b"\x1b[A\x1b[A");
+        type_in(&mut reader, b"\x1b[A\x1b[A\x1b[B!\n");
+        assert_eq!(read_line(&mut reader), b"two!\n");
+        type_in(&mut reader, b"thr\x1b[A\x1b[B\x1b[Bee\n");
+        assert_eq!(read_line(&mut reader), b"three\n");
+    }
 }
@@ -8,6 +8,7 @@ use crate::vm;
 use core::cmp;
 use core::mem;
 use core::slice;
+use syslib::errno::Errno;
 
 const NIDENT: usize = 16;
 
@@ -45,26 +46,26 @@ impl ELFHeader {
     fn read(ip: &fs::Inode) -> Result<ELFHeader> {
         let mut header = [ELFHeader::default(); 1];
         if ip.readi(&mut header[..], 0)? != mem::size_of::<ELFHeader>() {
-            return Err("exec: short ELF file");
+            return Err(Errno::ENOEXEC);
         }
         Ok(header[0])
     }
 
     fn validate(&self) -> Result<()> {
         if &self.ident[..4] != b"\x7FELF" {
-            return Err("Bad magic ELF value");
+            return Err(Errno::ENOEXEC);
         }
         const CLASS_64_BIT: u8 = 2;
         if self.ident[4] != CLASS_64_BIT {
-            return Err("Not a 64-bit object file");
+            return Err(Errno::ENOEXEC);
         }
         const OBJECT_FILE_TYPE_EXEC: u16 = 2;
         if self.object_file_type != OBJECT_FILE_TYPE_EXEC {
-            return Err("Not an executable ELF file");
+            return Err(Errno::ENOEXEC);
         }
         const MACHINE_X86_64: u16 = 62;
         if self.machine != MACHINE_X86_64 {
-            return Err("Wrong ELF executable architecture");
+            return Err(Errno::ENOEXEC);
         }
         Ok(())
     }
@@ -88,20 +89,20 @@ impl ProgramHeader {
     fn read(ip: &fs::Inode, off: u64) -> Result<ProgramHeader> {
         let mut header = [ProgramHeader::default(); 1];
         if ip.readi(&mut header[..], off)? != PH_SIZE {
-            return Err("exec: short program header read");
+            return Err(Errno::ENOEXEC);
         }
         Ok(header[0])
     }
 
     fn validate(&self) -> Result<()> {
         if self.mem_size < self.file_size {
-            return Err("exec: file and memory size mismatch");
+            return Err(Errno::ENOEXEC);
         }
         if !self.virt_addr.is_multiple_of(arch::PAGE_SIZE as u64) {
-            return Err("exec: misaligned section load address");
+            return Err(Errno::ENOEXEC);
         }
         if self.virt_addr.wrapping_add(self.mem_size) < self.virt_addr {
-            return Err("exec: program section too big");
+            return Err(Errno::ENOMEM);
         }
         Ok(())
     }
@@ -141,7 +142,7 @@ impl ProgramHeader {
             let page = pgtbl.user_addr_to_kern_page(va + kp)?;
             let n = cmp::min(file_size - kp, arch::PAGE_SIZE);
             if ip.readi(&mut page.as_mut()[..n], self.offset + kp as u64)? != n {
-                return Err("loaduvm: short read from file");
+                return Err(Errno::EIO);
             }
         }
         Ok(())
@@ -150,7 +151,7 @@ impl ProgramHeader {
 
 pub fn exec(proc: &proc::Proc, path: &[u8], args: &[&[u8]]) -> Result<()> {
     if args.len() > param::MAXARG {
-        return Err("exec: too many arguments");
+        return Err(Errno::E2BIG);
     }
 
     let mut pgtbl = vm::new_pgtbl()?;
@@ -177,10 +178,23 @@ pub fn exec(proc: &proc::Proc, path: &[u8], args: &[&[u8]]) -> Result<()> {
         })
     })?;
 
-    // Allocate the stack at the top of the user portion of the
-    // virtual address space.
+    if size > param::MMAPBASE {
+        return Err(Errno::ENOMEM);
+    }
+
+    // Allocate as much of the stack, at the top of the user portion
+    // of the virtual address space, as the arguments need; the rest
+    // is mapped as the program touches it.
+    // Each argument takes up to 8 bytes more than its length, for
+    // its NUL and alignment; then come its pointer, and two words.
+    let words = args.len() + 2;
+    let need =
+        args.iter().map(|arg| arg.len() + 8).sum::<usize>() + words * mem::size_of::<usize>();
+    if need > param::MAXSTACK {
+        return Err(Errno::E2BIG);
+    }
     pgtbl.alloc_user(
-        param::USERSTACK,
+        arch::page_round_down(param::USEREND - need),
         param::USEREND,
         vm::PageFlags::WRITE | vm::PageFlags::NX,
     )?;
@@ -194,9 +208,6 @@ pub fn exec(proc: &proc::Proc, path: &[u8], args: &[&[u8]]) -> Result<()> {
         sp &= !0b111;
         uargs[k] = sp;
         pgtbl.copy_out(arg, sp)?;
-        if sp < param::USERSTACK {
-            return Err("exec: arg stack overflow");
-        }
     }
 
     // Copy in the argument pointer vector.
@@ -216,11 +227,14 @@ pub fn exec(proc: &proc::Proc, path: &[u8], args: &[&[u8]]) -> Result<()> {
     sp -= bytes.len();
     pgtbl.copy_out(&bytes, sp)?;
 
-    // XXX Copy in the name
+    let name = path.rsplit(|&b| b == b'/').next().unwrap_or(path);
+    proc.set_name(name);
+    proc.reset_actions();
 
     // Commit to the new page table.
     let previous = unsafe { proc.switch_pgtbl(pgtbl) };
     proc.set_size(size);
+    proc.vmas().borrow_mut().clear();
     drop(previous);
 
     // Set up for return to userspace.
@@ -1,10 +1,14 @@
 use crate::Result;
 use crate::console;
+use crate::fs;
 use crate::param;
 use crate::spinlock::SpinMutex as Mutex;
 use core::cell::Cell;
 use core::sync::atomic::{AtomicBool, Ordering};
+use syslib::errno::Errno;
+use syslib::screen;
 use syslib::stat::Stat;
+use syslib::termios::{Termios, Winsize};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum OpenFlags {
@@ -14,19 +18,43 @@ pub enum OpenFlags {
     ReadWrite,
 }
 
+// The device controls understood by `Like::ioctl`, decoded from
+// the request and argument of the IOCTL system call.
+pub enum Ioctl<'a> {
+    GetTermios(&'a mut Termios),
+    SetTermios(&'a Termios),
+    DiscardInput,
+    GetWinsize(&'a mut Winsize),
+    GetPgrp(&'a mut u32),
+    SetPgrp(u32),
+}
+
 pub trait Like {
     fn close(&self);
 
     fn stat(&self) -> Result<Stat> {
-        Err("cannot stat")
+        Err(Errno::EINVAL)
     }
 
     fn read(&self, _file: &File, _buf: &mut [u8]) -> Result<usize> {
-        Err("unimplemented")
+        Err(Errno::EINVAL)
     }
 
     fn write(&self, _file: &File, _buf: &[u8]) -> Result<usize> {
-        Err("unimplemented")
+        Err(Errno::EINVAL)
+    }
+
+    fn screen(&self, _op: usize, _req: &screen::Request, _text: &[u8]) -> Result<()> {
+        Err(Errno::ENOTTY)
+    }
+
+    fn ioctl(&self, _req: Ioctl) -> Result<()> {
+        Err(Errno::ENOTTY)
+    }
+
+    // The inode holding the file's contents, if it is on disk.
+    fn inode(&self) -> Option<&fs::Inode> {
+        None
     }
 }
 
@@ -113,7 +141,7 @@ impl File {
 
     pub fn read(&self, buf: &mut [u8]) -> Result<usize> {
         if !self.readable() {
-            return Err("file not readable");
+            return Err(Errno::EBADF);
         }
         let fp = self.fp.get().expect("read nil file");
         fp.read(self, buf)
@@ -126,11 +154,33 @@ impl File {
 
     pub fn write(&self, buf: &[u8]) -> Result<usize> {
         if !self.writable() {
-            return Err("file not writable");
+            return Err(Errno::EBADF);
         }
         let fp = self.fp.get().expect("write nil file");
         fp.write(self, buf)
     }
+
+    pub fn screen(&self, op: usize, req: &screen::Request, text: &[u8]) -> Result<()> {
+        if !self.writable() {
+            return Err(Errno::EBADF);
+        }
+        let fp = self.fp.get().expect("screen nil file");
+        fp.screen(op, req, text)
+    }
+
+    pub fn ioctl(&self, req: Ioctl) -> Result<()> {
+        let fp = self.fp.get().expect("ioctl nil file");
+        fp.ioctl(req)
+    }
+
+    // The inode of a disk file open for reading, to map.
+    pub fn mappable(&self) -> Result<&'static fs::Inode> {
+        if !self.readable() {
+            return Err(Errno::EBADF);
+        }
+        let fp = self.fp.get().expect("mmap nil file");
+        fp.inode().ok_or(Errno::ENODEV)
+    }
 }
 
 pub fn alloc(flags: OpenFlags, fp: &'static dyn Like) -> Option<&'static File> {
@@ -164,9 +214,9 @@ impl<'a> Drop for Guard<'a> {
     }
 }
 
-pub fn devsw(major: u32) -> Result<&'static dyn Like> {
+pub fn devsw(major: u32, minor: u32) -> Result<&'static dyn Like> {
     match major {
-        console::CONSOLE_MAJOR => Ok(console::consdev()),
-        _ => Err("bad device major number"),
+        console::CONSOLE_MAJOR => console::consdev(minor),
+        _ => Err(Errno::ENXIO),
     }
 }
@@ -15,6 +15,7 @@ use core::mem;
 use core::slice;
 use core::sync::atomic::{AtomicBool, Ordering};
 use static_assertions::const_assert_eq;
+use syslib::errno::Errno;
 use syslib::stat::{FileType, Stat};
 
 // On-disk file system format.
@@ -165,7 +166,7 @@ fn balloc(dev: u32, sb: &Superblock) -> Result<u64> {
         }
         bp.relse();
     }
-    Err("balloc: out of blocks")
+    Err(Errno::ENOSPC)
 }
 
 // Free a storage block.
@@ -252,12 +253,19 @@ fn bfree(dev: u32, blockno: u64, sb: &Superblock) {
 static ICACHE: Mutex<[Inode; param::NINODE]> =
     Mutex::new("icache", [const { Inode::new() }; param::NINODE]);
 
+static mut ROOT_DEV: u32 = param::ROOTDEV;
+
 pub unsafe fn init(dev: u32) {
     unsafe {
         SUPERBLOCK = Superblock::read(dev).expect("superblock read failed");
+        ROOT_DEV = dev;
     }
 }
 
+fn root_dev() -> u32 {
+    unsafe { ROOT_DEV }
+}
+
 pub unsafe fn superblock() -> &'static Superblock {
     unsafe { &*core::ptr::addr_of!(SUPERBLOCK) }
 }
@@ -283,7 +291,7 @@ pub fn ialloc(dev: u32, typ: FileType, sb: &'static Superblock) -> Result<&'stat
         }
         bp.relse();
     }
-    Err("ialloc: no inodes")
+    Err(Errno::ENOSPC)
 }
 
 #[derive(Debug)]
@@ -384,7 +392,7 @@ impl Inode {
         nlink
     }
 
-    fn size(&self) -> u64 {
+    pub fn size(&self) -> u64 {
         self.dinode.borrow().size
     }
 
@@ -396,6 +404,10 @@ impl Inode {
         self.dinode.borrow().major
     }
 
+    pub fn minor(&self) -> u32 {
+        self.dinode.borrow().minor
+    }
+
     fn set_major(&self, major: u32) {
         self.dinode.borrow_mut().major = major;
     }
@@ -468,7 +480,7 @@ impl Inode {
             }
         }
         if empty.is_none() {
-            return Err("Inode::get: no inodes");
+            return Err(Errno::ENFILE);
         }
         let ip = empty.unwrap();
         let mut meta = ip.meta.borrow_mut();
@@ -537,7 +549,7 @@ impl Inode {
                 Ok(iaddrs[bn])
             })?;
         }
-        Err("bmap: out of range")
+        Err(Errno::EFBIG)
     }
 
     fn trunc1(&self) -> Result<()> {
@@ -594,10 +606,10 @@ impl Inode {
             slice::from_raw_parts_mut(ptr, len)
         };
         if off > self.size() {
-            return Err("offset beyond end of file");
+            return Err(Errno::EINVAL);
         }
         if off.wrapping_add(dst.len() as u64) < off {
-            return Err("offset and length wrap");
+            return Err(Errno::EINVAL);
         }
         let mut off = off as usize;
         let n = cmp::min(dst.len(), self.size() as usize - off);
@@ -618,13 +630,13 @@ impl Inode {
 
     fn writei(&self, src: &[u8], off: u64) -> Result<usize> {
         if off > self.size() {
-            return Err("offset beyond end of file");
+            return Err(Errno::EINVAL);
         }
         if off.wrapping_add(src.len() as u64) < off {
-            return Err("offset and length wrap");
+            return Err(Errno::EINVAL);
         }
         if off as usize + src.len() > MAXFILE * BSIZE {
-            return Err("write makes file too big");
+            return Err(Errno::EFBIG);
         }
         let mut off = off as usize;
         let n = src.len();
@@ -666,7 +678,7 @@ impl Inode {
                 return Ok((ip, off));
             }
         }
-        Err("file not found")
+        Err(Errno::ENOENT)
     }
 
     pub fn dir_lookup(&self, name: &[u8]) -> Result<&'static Inode> {
@@ -678,7 +690,7 @@ impl Inode {
         const INUM_SIZE: usize = mem::size_of::<u64>();
         if let Ok(ip) = self.dir_lookup(name) {
             ip.put()?;
-            return Err("file already exists");
+            return Err(Errno::EEXIST);
         }
         let mut entry = [0u8; mem::size_of::<Dirent>()];
         let mut off = 0;
@@ -706,7 +718,7 @@ impl Inode {
         ip.with_putlock(|ip| {
             assert!(ip.nlink() > 0, "unlink inode < 1 links");
             if !ip.is_unlinkable()? {
-                return Err("not linkable");
+                return Err(Errno::ENOTEMPTY);
             }
             const EMPTY: [u8; DIRENT_SIZE] = [0u8; DIRENT_SIZE];
             let n = self.writei(&EMPTY[..], offset).expect("unlink: writei");
@@ -824,7 +836,7 @@ mod skip_elem_tests {
 
 fn is_dir(ip: &Inode) -> Result<&Inode> {
     if ip.typ() != FileType::Dir {
-        return Err("not a directory");
+        return Err(Errno::ENOTDIR);
     }
     Ok(ip)
 }
@@ -832,7 +844,7 @@ fn is_dir(ip: &Inode) -> Result<&Inode> {
 pub fn namex(mut path: &[u8]) -> Result<&'static Inode> {
     let mut ip = if !path.is_empty() && path[0] == b'/' {
         let sb = unsafe { superblock() };
-        Inode::get(param::ROOTDEV, ROOTINO, sb)?
+        Inode::get(root_dev(), ROOTINO, sb)?
     } else {
         proc::myproc().cwd().dup()
     };
@@ -851,14 +863,14 @@ pub fn namex(mut path: &[u8]) -> Result<&'static Inode> {
 
 pub fn namei(path: &[u8]) -> Result<&'static Inode> {
     if path.is_empty() {
-        return Err("path empty");
+        return Err(Errno::ENOENT);
     }
     namex(path)
 }
 
 pub fn namei_parent(path: &[u8]) -> Result<(&'static Inode, &[u8])> {
     if path.is_empty() {
-        return Err("empty path");
+        return Err(Errno::ENOENT);
     }
     let (mut dir, file) = split_name(path);
     if dir.is_empty() && path[0] == b'/' {
@@ -868,7 +880,7 @@ pub fn namei_parent(path: &[u8]) -> Result<(&'static Inode, &[u8])> {
     ip.lock();
     if ip.typ() != FileType::Dir {
         let _ = ip.unlock_put();
-        return Err("not a dir");
+        return Err(Errno::ENOTDIR);
     }
     ip.unlock();
     Ok((ip, file))
@@ -906,7 +918,7 @@ pub fn create(path: &[u8], typ: CreateType) -> Result<&'static Inode> {
         mem::drop(guard);
         let guard = PutLockGuard::new(ip);
         if FileType::File != typ.into() || ip.typ() != typ.into() {
-            return Err("create mismatch type");
+            return Err(Errno::EEXIST);
         }
         guard.release();
         return Ok(ip);
@@ -955,6 +967,10 @@ impl file::Like for Inode {
         Ok(self.with_lock(Inode::stati))
     }
 
+    fn inode(&self) -> Option<&Inode> {
+        Some(self)
+    }
+
     fn read(&self, file: &File, buf: &mut [u8]) -> Result<usize> {
         self.with_lock(|ip| {
             let r = ip.readi(buf, file.off() as u64)?;
@@ -1,11 +1,12 @@
 use core::arch::global_asm;
+use core::mem::size_of;
 use core::slice;
 use syslib::syscall::{EXEC, EXIT};
 
 global_asm!(
     r#"
 .align 16
-.globl start_init, start_init_len
+.globl start_init, start_init_len, start_init_path, start_init_argv
 start_init:
 	// exec(init, argv);
 	movq	${EXEC}, %rax
@@ -18,14 +19,21 @@ start_init:
 	syscall
 	jmp	1b
 
-.align 8
-init: .string "/init\0"
 .align 8
 argv:
 	.quad init - start_init;
+	.quad 0;	// Optional argument; see `set_init_args`.
 	.quad 0;
 
-start_init_len: .quad . - start_init
+// The path to init comes last, so that the kernel may replace
+// it, and append an argument, of any reasonable length.
+init: .string "/init\0"
+init_end:
+
+.align 8
+start_init_len: .quad init_end - start_init
+start_init_path: .quad init - start_init
+start_init_argv: .quad argv - start_init
     "#,
     EXEC = const EXEC,
     EXIT = const EXIT,
@@ -35,6 +43,8 @@ start_init_len: .quad . - start_init
 unsafe extern "C" {
     fn start_init() -> !;
     static start_init_len: usize;
+    static start_init_path: usize;
+    static start_init_argv: usize;
 }
 
 pub fn start_init_slice() -> &'static [u8] {
@@ -43,3 +53,25 @@ pub fn start_init_slice() -> &'static [u8] {
     assert!(len < 200);
     unsafe { slice::from_raw_parts(start as *const u8, len) }
 }
+
+// Writes `path`, NUL-terminated, over the init path in a copy
+// of the init code, followed by `arg`, if any, which becomes
+// init's `argv[1]`.  `code` must be at least a page long.
+pub fn set_init_args(code: &mut [u8], path: &[u8], arg: Option<&[u8]>) {
+    let mut off = unsafe { start_init_path };
+    let arg = arg.unwrap_or_default();
+    assert!(
+        off + path.len() + arg.len() + 1 < code.len(),
+        "init args too long"
+    );
+    code[off..off + path.len()].copy_from_slice(path);
+    off += path.len();
+    code[off] = 0;
+    off += 1;
+    if !arg.is_empty() {
+        let argv1 = unsafe { start_init_argv } + size_of::<u64>();
+        code[argv1..argv1 + size_of::<u64>()].copy_from_slice(&(off as u64).to_le_bytes());
+        code[off..off + arg.len()].copy_from_slice(arg);
+        code[off + arg.len()] = 0;
+    }
+}
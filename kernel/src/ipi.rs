@@ -0,0 +1,140 @@
+// Interprocessor interrupts.  A CPU may kick another to make it
+// look at its run queue, stop the others when it panics, or have
+// some set of CPUs run a function and wait until all of them have,
+// as a TLB shootdown must.
+//
+// Only one cross-CPU call is in progress at a time: its caller
+// holds BUSY, and each CPU it targets has its PENDING flag set
+// until it has run the function.  A CPU waiting for BUSY answers
+// the call in progress, since its caller may be waiting for it.
+
+use crate::acpi;
+use crate::arch;
+use crate::param::NCPUMAX;
+use crate::proc;
+use crate::spinlock::{SpinMutex as Mutex, without_intrs};
+use crate::trap;
+use crate::xapic;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+pub const INTR_RESCHED: u32 = 20;
+pub const INTR_CALL: u32 = 21;
+pub const INTR_HALT: u32 = 22;
+
+// The CPUs to interrupt, by index (see `arch::mycpu_id`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)]
+pub enum Target {
+    Cpu(usize),
+    All,
+    Others,
+}
+
+impl Target {
+    // Whether CPU `cpu` is a target when `me` sends.
+    fn includes(self, cpu: usize, me: usize) -> bool {
+        match self {
+            Target::Cpu(target) => cpu == target,
+            Target::All => true,
+            Target::Others => cpu != me,
+        }
+    }
+}
+
+// A function to run on another CPU, and its argument.
+type Func = fn(usize);
+
+static BUSY: AtomicBool = AtomicBool::new(false);
+static CALL: Mutex<Option<(Func, usize)>> = Mutex::new("ipicall", None);
+static PENDING: [AtomicBool; NCPUMAX] = [const { AtomicBool::new(false) }; NCPUMAX];
+static WAITING: AtomicUsize = AtomicUsize::new(0);
+
+// Sends the interrupt on `vector`, counted from `trap::INTR0`.
+pub fn send(target: Target, vector: u32) {
+    let dest = match target {
+        Target::Cpu(cpu) => xapic::Dest::Apic(unsafe { acpi::cpus() }[cpu]),
+        Target::All => xapic::Dest::All,
+        Target::Others => xapic::Dest::Others,
+    };
+    unsafe {
+        xapic::send_ipi(dest, trap::INTR0 + vector);
+    }
+}
+
+// Kicks `cpu` to look for a process to run.
+pub fn resched(cpu: usize) {
+    send(Target::Cpu(cpu), INTR_RESCHED);
+}
+
+// Stops every other CPU, if they can be sent interrupts yet.
+pub fn halt_others() {
+    if xapic::ready() {
+        send(Target::Others, INTR_HALT);
+    }
+}
+
+// Runs `f(arg)` with interrupts off on each target CPU, and
+// returns once all have.  The caller must hold no spin lock, for
+// a target spinning for it with interrupts off would never answer.
+// Only CPUs that have come online are called, since no other would
+// answer either.
+pub fn call(target: Target, f: Func, arg: usize) {
+    assert_eq!(arch::mycpu().nintr_disable(), 0, "ipi call holding a lock");
+    without_intrs(|| {
+        let me = arch::mycpu_id() as usize;
+        while BUSY
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            interrupt();
+            arch::cpu_relax();
+        }
+        *CALL.lock() = Some((f, arg));
+        let others =
+            (0..NCPUMAX).filter(|&cpu| cpu != me && target.includes(cpu, me) && proc::online(cpu));
+        WAITING.store(others.clone().count(), Ordering::Release);
+        others.for_each(|cpu| PENDING[cpu].store(true, Ordering::Release));
+        if WAITING.load(Ordering::Acquire) != 0 {
+            let dest = match target {
+                Target::All => Target::Others,
+                target => target,
+            };
+            send(dest, INTR_CALL);
+        }
+        if target.includes(me, me) {
+            f(arg);
+        }
+        while WAITING.load(Ordering::Acquire) != 0 {
+            arch::cpu_relax();
+        }
+        *CALL.lock() = None;
+        BUSY.store(false, Ordering::Release);
+    });
+}
+
+// Answers the call in progress, if this CPU is one of its targets.
+pub fn interrupt() {
+    let me = arch::mycpu_id() as usize;
+    if PENDING[me].swap(false, Ordering::AcqRel) {
+        let (f, arg) = CALL.lock().expect("ipi call");
+        f(arg);
+        WAITING.fetch_sub(1, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod ipi_tests {
+    use super::*;
+
+    #[test]
+    fn targets() {
+        let of = |target: Target| {
+            (0..4)
+                .filter(|&cpu| target.includes(cpu, 1))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(of(Target::Cpu(2)), [2]);
+        assert_eq!(of(Target::All), [0, 1, 2, 3]);
+        assert_eq!(of(Target::Others), [0, 2, 3]);
+    }
+}
@@ -1,10 +1,24 @@
 use core::ptr;
+use core::slice;
+use core::sync::atomic::{AtomicPtr, AtomicU16, AtomicUsize, Ordering};
 
 use crate::arch::{PAGE_SIZE, Page};
+use crate::kmem;
 use crate::spinlock::SpinMutex as Mutex;
 
 static FREE_LIST: Mutex<FreeList> = Mutex::new("kmem", FreeList { next: None });
 
+// The number of pages given to the allocator, and of those free.
+static NPAGES: AtomicUsize = AtomicUsize::new(0);
+static NFREE: AtomicUsize = AtomicUsize::new(0);
+
+// For each page of physical memory, by page number, how many more
+// than one page tables map it; pages shared copy-on-write are only
+// freed when the last reference goes.  Made by `init_refs`, and
+// until then, no page can be shared.
+static REFS: AtomicPtr<AtomicU16> = AtomicPtr::new(ptr::null_mut());
+static NREFS: AtomicUsize = AtomicUsize::new(0);
+
 #[repr(align(4096))]
 struct FreeList {
     next: Option<ptr::NonNull<FreeList>>,
@@ -42,12 +56,77 @@ pub fn free_pages(pages: &mut [Page]) {
     for page in pages.iter_mut() {
         fl.put(page);
     }
+    NPAGES.fetch_add(pages.len(), Ordering::Relaxed);
+    NFREE.fetch_add(pages.len(), Ordering::Relaxed);
+}
+
+// The number of pages whose reference counts fit in one page.
+pub const REFS_PER_PAGE: usize = PAGE_SIZE / size_of::<AtomicU16>();
+
+// Keeps reference counts in `pages` for the physical pages they
+// number.
+pub unsafe fn init_refs(pages: &'static mut [Page]) {
+    pages.iter_mut().for_each(Page::clear);
+    NREFS.store(pages.len() * REFS_PER_PAGE, Ordering::Relaxed);
+    REFS.store(pages.as_mut_ptr().cast(), Ordering::Release);
+}
+
+fn refs(page: &Page) -> Option<&'static AtomicU16> {
+    let refs = REFS.load(Ordering::Acquire);
+    if refs.is_null() {
+        return None;
+    }
+    let refs = unsafe { slice::from_raw_parts(refs, NREFS.load(Ordering::Relaxed)) };
+    refs.get(kmem::ref_to_phys(page) as usize / PAGE_SIZE)
+}
+
+// Adds a reference to `page`, returning false if it cannot be
+// shared.
+pub fn share(page: &Page) -> bool {
+    let Some(refs) = refs(page) else {
+        return false;
+    };
+    let mut n = refs.load(Ordering::Acquire);
+    while n != u16::MAX {
+        match refs.compare_exchange(n, n + 1, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => return true,
+            Err(now) => n = now,
+        }
+    }
+    false
+}
+
+// Whether any reference to `page` but the caller's remains.
+pub fn shared(page: &Page) -> bool {
+    refs(page).is_some_and(|refs| refs.load(Ordering::Acquire) != 0)
 }
 
+// Drops a reference to `page`, freeing it if it was the last.
 pub fn free(page: &mut Page) {
+    if let Some(refs) = refs(page) {
+        let mut n = refs.load(Ordering::Acquire);
+        while n != 0 {
+            match refs.compare_exchange(n, n - 1, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => return,
+                Err(now) => n = now,
+            }
+        }
+    }
     FREE_LIST.lock().put(page);
+    NFREE.fetch_add(1, Ordering::Relaxed);
 }
 
 pub fn alloc() -> Option<&'static mut Page> {
-    FREE_LIST.lock().get()
+    let page = FREE_LIST.lock().get()?;
+    NFREE.fetch_sub(1, Ordering::Relaxed);
+    Some(page)
+}
+
+// Returns the number of pages the allocator manages, and how
+// many of them are free.
+pub fn stats() -> (usize, usize) {
+    (
+        NPAGES.load(Ordering::Relaxed),
+        NFREE.load(Ordering::Relaxed),
+    )
 }
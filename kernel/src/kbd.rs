@@ -1,12 +1,32 @@
+// The PS/2 keyboard.
+//
+// The controller delivers scancodes from set 1: one byte for
+// most keys, with bit 7 set when the key is released, and an
+// 0xE0 prefix for the keys added by the "enhanced" keyboard,
+// such as the arrows and the right-hand Ctrl and Alt.  We decode
+// these into key events, naming each key by its one-byte code
+// with bit 7 set for the extended keys, then translate presses
+// into input for the console.  Keys that produce characters are
+// looked up in the current keymap (see `keymap.rs`); the arrows,
+// editing keys and function keys mean the same in every layout,
+// and send the escape sequences a VT220 would.
+
+use crate::Result;
+use crate::cmdline;
 use crate::console;
+use crate::proc::Proc;
+use crate::spinlock::SpinMutex as Mutex;
 use crate::xapic;
 use bitflags::bitflags;
-use core::cell::SyncUnsafeCell;
+use core::{mem, ptr, slice};
+use syslib::errno::Errno;
+use syslib::keymap::{self, Keymap};
 
 pub const INTR: u32 = 1;
 
 pub unsafe fn init() {
     use crate::ioapic;
+    KBD.lock().keymap = *cmdline::keymap();
     unsafe {
         ioapic::enable(INTR, 0);
     }
@@ -22,215 +42,374 @@ bitflags! {
 }
 
 bitflags! {
-    pub struct Modifiers: usize {
-        const NORMAL = 0;
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct Modifiers: u8 {
         const SHIFT = 1;
         const CTL = 1 << 1;
         const ALT = 1 << 2;
-        const CAPSLOCK = 1 << 3;
-        const NUMLOCK = 1 << 4;
-        const SCROLLLOCK = 1 << 5;
-        const E0ESC = 1 << 6;
+        const ALTGR = 1 << 3;
+        const CAPSLOCK = 1 << 4;
+        const NUMLOCK = 1 << 5;
+        const SCROLLLOCK = 1 << 6;
     }
 }
 
-pub const NO: u8 = 0u8;
-pub const HOME: u8 = 0xE0;
-pub const END: u8 = 0xE1;
-pub const UP: u8 = 0xE2;
-pub const DOWN: u8 = 0xE3;
-pub const LEFT: u8 = 0xE4;
-pub const RIGHT: u8 = 0xE5;
-pub const PGUP: u8 = 0xE6;
-pub const PGDN: u8 = 0xE7;
-pub const INS: u8 = 0xE8;
-pub const DEL: u8 = 0xE9;
-
-pub const fn c(b: char) -> u8 {
-    b as u8 - b'@'
+// Key codes.  Extended keys have the EXTENDED bit set.
+pub const EXTENDED: u8 = 0x80;
+pub const LCTRL: u8 = 0x1D;
+pub const LSHIFT: u8 = 0x2A;
+pub const RSHIFT: u8 = 0x36;
+pub const LALT: u8 = 0x38;
+pub const CAPSLOCK: u8 = 0x3A;
+pub const F1: u8 = 0x3B;
+pub const F10: u8 = 0x44;
+pub const NUMLOCK: u8 = 0x45;
+pub const SCROLLLOCK: u8 = 0x46;
+pub const F11: u8 = 0x57;
+pub const F12: u8 = 0x58;
+pub const KP_ENTER: u8 = EXTENDED | 0x1C;
+pub const RCTRL: u8 = EXTENDED | 0x1D;
+pub const KP_SLASH: u8 = EXTENDED | 0x35;
+pub const RALT: u8 = EXTENDED | 0x38;
+pub const HOME: u8 = EXTENDED | 0x47;
+pub const UP: u8 = EXTENDED | 0x48;
+pub const PGUP: u8 = EXTENDED | 0x49;
+pub const LEFT: u8 = EXTENDED | 0x4B;
+pub const RIGHT: u8 = EXTENDED | 0x4D;
+pub const END: u8 = EXTENDED | 0x4F;
+pub const DOWN: u8 = EXTENDED | 0x50;
+pub const PGDN: u8 = EXTENDED | 0x51;
+pub const INS: u8 = EXTENDED | 0x52;
+pub const DEL: u8 = EXTENDED | 0x53;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyEvent {
+    pub key: u8,
+    pub pressed: bool,
+    // The modifiers in effect once the event has happened.
+    pub mods: Modifiers,
 }
 
-fn shift_code(b: u8) -> Modifiers {
-    match b {
-        0x1D | 0x9D => Modifiers::CTL,
-        0x2A | 0x36 => Modifiers::SHIFT,
-        0x38 | 0xB8 => Modifiers::ALT,
-        _ => Modifiers::empty(),
-    }
+// What a key press means.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Input {
+    Byte(u8),
+    Sequence(&'static [u8]),
+    // Alt+F<n>: bring virtual console n-1 to the display.
+    Switch(usize),
+    // Shift+PgUp and Shift+PgDn: page through scrollback.
+    Page { up: bool },
 }
 
-fn toggle_code(b: u8) -> Modifiers {
-    match b {
-        0x3A => Modifiers::CAPSLOCK,
-        0x45 => Modifiers::NUMLOCK,
-        0x46 => Modifiers::SCROLLLOCK,
-        _ => Modifiers::empty(),
-    }
+fn sequence(key: u8) -> Option<&'static [u8]> {
+    let seq: &[u8] = match key {
+        UP => b"\x1b[A",
+        DOWN => b"\x1b[B",
+        RIGHT => b"\x1b[C",
+        LEFT => b"\x1b[D",
+        HOME => b"\x1b[1~",
+        INS => b"\x1b[2~",
+        DEL => b"\x1b[3~",
+        END => b"\x1b[4~",
+        PGUP => b"\x1b[5~",
+        PGDN => b"\x1b[6~",
+        0x3B => b"\x1bOP",
+        0x3C => b"\x1bOQ",
+        0x3D => b"\x1bOR",
+        0x3E => b"\x1bOS",
+        0x3F => b"\x1b[15~",
+        0x40 => b"\x1b[17~",
+        0x41 => b"\x1b[18~",
+        0x42 => b"\x1b[19~",
+        0x43 => b"\x1b[20~",
+        F10 => b"\x1b[21~",
+        F11 => b"\x1b[23~",
+        F12 => b"\x1b[24~",
+        _ => return None,
+    };
+    Some(seq)
 }
 
-#[rustfmt::skip]
-const NORMAL_MAP: [u8; 256] = [
-    NO,      0x1B,    b'1',    b'2',    b'3',    b'4',    b'5',    b'6', // 0x00
-    b'7',    b'8',    b'9',    b'0',    b'-',    b'=',    b'\x07', b'\t',
-    b'q',    b'w',    b'e',    b'r',    b't',    b'y',    b'u',    b'i', // 0x10
-    b'o',    b'p',    b'[',    b']',    b'\n',   NO,      b'a',    b's',
-    b'd',    b'f',    b'g',    b'h',    b'j',    b'k',    b'l',    b';', // 0x20
-    b'\'',   b'`',    NO,      b'\\',   b'z',    b'x',    b'c',    b'v',
-    b'b',    b'n',    b'm',    b',',    b'.',    b'/',    NO,      b'*', // 0x30
-    NO,      b' ',    NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      b'7', // 0x40
-    b'8',    b'9',    b'-',    b'4',    b'5',    b'6',    b'+',    b'1',
-    b'2',    b'3',    b'0',    b'.',    NO,      NO,      NO,      NO,   // 0x50
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0x60
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0x70
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      b'/',    NO,      NO,   // 0x80
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0x90
-    NO,      NO,      NO,      NO,      b'\n',   NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0xa0
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0xb0
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      HOME, // 0xc0
-    UP,      PGUP,    NO,      LEFT,    NO,      RIGHT,   NO,      END,
-    DOWN,    PGDN,    INS,     DEL,     NO,      NO,      NO,      NO,   // 0xd0
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0xe0
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0xf0
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-];
-
-#[rustfmt::skip]
-const SHIFT_MAP: [u8; 256] = [
-    NO,      0o33,    b'!',    b'@',    b'#',    b'$',    b'%',    b'^',  // 0x00
-    b'&',    b'*',    b'(',    b')',    b'_',    b'+',    b'\x07', b'\t',
-    b'Q',    b'W',    b'E',    b'R',    b'T',    b'Y',    b'U',    b'I',  // 0x10
-    b'O',    b'P',    b'{',    b'}',    b'\n',    NO,     b'A',    b'S',
-    b'D',    b'F',    b'G',    b'H',    b'J',    b'K',    b'L',    b':',  // 0x20
-    b'"',    b'~',     NO,     b'|',    b'Z',    b'X',    b'C',    b'V',
-    b'B',    b'N',    b'M',    b'<',    b'>',    b'?',    NO,      b'*',  // 0x30
-    NO,      b' ',    NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      b'7',  // 0x40
-    b'8',    b'9',    b'-',    b'4',    b'5',    b'6',    b'+',    b'1',
-    b'2',    b'3',    b'0',    b'.',    NO,      NO,      NO,      NO,   // 0x50
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0x60
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0x70
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      b'/',    NO,      NO,   // 0x80
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0x90
-    NO,      NO,      NO,      NO,      b'\n',   NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0xa0
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0xb0
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      HOME, // 0xc0
-    UP,      PGUP,    NO,      LEFT,    NO,      RIGHT,   NO,      END,
-    DOWN,    PGDN,    INS,     DEL,     NO,      NO,      NO,      NO,   // 0xd0
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0xe0
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0xf0
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-];
-
-#[rustfmt::skip]
-const CTL_MAP: [u8; 256] = [
-
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0x00
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    c('Q'),  c('W'),  c('E'),  c('R'),  c('T'),  c('Y'),  c('U'),  c('I'), // 0x10
-    c('O'),  c('P'),  NO,      NO,      b'\r',   NO,      c('A'),  c('S'),
-    c('D'),  c('F'),  c('G'),  c('H'),  c('J'),  c('K'),  c('L'),  NO,   // 0x20
-    NO,      NO,      NO,      c('\\'), c('Z'),  c('X'),  c('C'),  c('V'),
-    c('B'),  c('N'),  c('M'),  NO,      NO,      b'/',    NO,      NO,   // 0x30
-    NO,      b' ',    NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      b'7', // 0x40
-    b'8',    b'9',    b'-',    b'4',    b'5',    b'6',    b'+',    b'1',
-    b'2',    b'3',    b'0',    b'.',    NO,      NO,      NO,      NO,   // 0x50
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0x60
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0x70
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      b'/',    NO,      NO,   // 0x80
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0x90
-    NO,      NO,      NO,      NO,      b'\r',   NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0xa0
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0xb0
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      HOME, // 0xc0
-    UP,      PGUP,    NO,      LEFT,    NO,      RIGHT,   NO,      END,
-    DOWN,    PGDN,    INS,     DEL,     NO,      NO,      NO,      NO,   // 0xd0
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0xe0
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,   // 0xf0
-    NO,      NO,      NO,      NO,      NO,      NO,      NO,      NO,
-];
+pub struct Keyboard {
+    // Whether the last byte was the 0xE0 prefix.
+    extended: bool,
+    // Pause sends a sequence of its own, without a release, of
+    // which this many bytes remain to be skipped.
+    skip: u8,
+    mods: Modifiers,
+    // The key last pressed and not yet released, so that the
+    // keyboard's automatic repeats can be told from presses.
+    held: Option<u8>,
+    keymap: Keymap,
+}
 
-pub fn getb() -> Option<u8> {
-    fn modkeys() -> &'static mut Modifiers {
-        static MODKEYS: SyncUnsafeCell<Modifiers> = SyncUnsafeCell::new(Modifiers::NORMAL);
-        unsafe { &mut *MODKEYS.get() }
-    }
-    use crate::x86_64::inb;
-    let status = Status::from_bits_truncate(unsafe { inb(STATUS_PORT) });
-    if !status.contains(Status::DATA_AVAIL) {
-        return None;
+impl Keyboard {
+    pub const fn new() -> Keyboard {
+        Keyboard {
+            extended: false,
+            skip: 0,
+            mods: Modifiers::NUMLOCK,
+            held: None,
+            keymap: crate::keymap::US,
+        }
     }
-    let mut data = unsafe { inb(DATA_PORT) };
-    if data == 0xE0 {
-        // ESC key
-        modkeys().insert(Modifiers::E0ESC);
-        return None;
-    } else if (data & 0b1000_0000) != 0 {
-        // Key up event
-        data = if modkeys().contains(Modifiers::E0ESC) {
-            data
+
+    // Decodes a scancode, returning the event it completes.
+    pub fn decode(&mut self, code: u8) -> Option<KeyEvent> {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return None;
+        }
+        match code {
+            0xE0 => {
+                self.extended = true;
+                return None;
+            }
+            0xE1 => {
+                self.skip = 5;
+                return None;
+            }
+            _ => {}
+        }
+        let extended = if mem::take(&mut self.extended) {
+            EXTENDED
         } else {
-            data & 0b0111_1111
+            0
         };
-        modkeys().remove(Modifiers::E0ESC | shift_code(data));
-        return None;
-    } else if modkeys().contains(Modifiers::E0ESC) {
-        data |= 0b1000_0000;
-        modkeys().remove(Modifiers::E0ESC);
+        let key = extended | code & 0x7F;
+        let pressed = code & 0x80 == 0;
+        // Some keyboards surround extended keys with presses and
+        // releases of a "fake" shift, for compatibility.
+        if key == EXTENDED | LSHIFT || key == EXTENDED | RSHIFT {
+            return None;
+        }
+        let repeat = pressed && self.held == Some(key);
+        if pressed {
+            self.held = Some(key);
+        } else if self.held == Some(key) {
+            self.held = None;
+        }
+        let modifier = match key {
+            LSHIFT | RSHIFT => Modifiers::SHIFT,
+            LCTRL | RCTRL => Modifiers::CTL,
+            LALT => Modifiers::ALT,
+            RALT => Modifiers::ALTGR,
+            _ => Modifiers::empty(),
+        };
+        self.mods.set(modifier, pressed);
+        if pressed && !repeat {
+            self.mods.toggle(match key {
+                CAPSLOCK => Modifiers::CAPSLOCK,
+                NUMLOCK => Modifiers::NUMLOCK,
+                SCROLLLOCK => Modifiers::SCROLLLOCK,
+                _ => Modifiers::empty(),
+            });
+        }
+        Some(KeyEvent {
+            key,
+            pressed,
+            mods: self.mods,
+        })
     }
-    modkeys().insert(shift_code(data));
-    modkeys().toggle(toggle_code(data));
-    let map = if modkeys().contains(Modifiers::CTL) {
-        &CTL_MAP
-    } else if modkeys().contains(Modifiers::SHIFT) {
-        &SHIFT_MAP
-    } else {
-        &NORMAL_MAP
-    };
-    let mut b = map[data as usize];
-    if modkeys().contains(Modifiers::CAPSLOCK) {
-        if b.is_ascii_lowercase() {
-            b.make_ascii_uppercase();
-        } else if b.is_ascii_uppercase() {
-            b.make_ascii_lowercase();
+
+    // Translates a key event into input, if it means any.
+    pub fn translate(&self, event: &KeyEvent) -> Option<Input> {
+        if !event.pressed {
+            return None;
         }
+        let mods = event.mods;
+        let mut key = event.key;
+        // Without Num Lock, the keypad is a second set of arrows
+        // and editing keys, whose codes are those of the extended
+        // keys in the same places.
+        if !mods.contains(Modifiers::NUMLOCK)
+            && (HOME & !EXTENDED..=DEL & !EXTENDED).contains(&key)
+            && !matches!(key, 0x4A | 0x4C | 0x4E)
+        {
+            key |= EXTENDED;
+        }
+        if mods.contains(Modifiers::ALT) && (F1..=F10).contains(&key) {
+            return Some(Input::Switch(usize::from(key - F1)));
+        }
+        if mods.contains(Modifiers::SHIFT) && (key == PGUP || key == PGDN) {
+            return Some(Input::Page { up: key == PGUP });
+        }
+        if let Some(seq) = sequence(key) {
+            return Some(Input::Sequence(seq));
+        }
+        match key {
+            KP_ENTER => return Some(Input::Byte(b'\n')),
+            KP_SLASH => return Some(Input::Byte(b'/')),
+            _ if key & EXTENDED != 0 => return None,
+            _ => {}
+        }
+        let k = usize::from(key);
+        let map = &self.keymap;
+        let letter = map.normal[k].is_ascii_lowercase() || map.normal[k] > 0x7F;
+        let shift =
+            mods.contains(Modifiers::SHIFT) != (letter && mods.contains(Modifiers::CAPSLOCK));
+        let b = if mods.contains(Modifiers::ALTGR) && map.altgr[k] != 0 {
+            map.altgr[k]
+        } else if shift {
+            map.shift[k]
+        } else {
+            map.normal[k]
+        };
+        let b = if mods.contains(Modifiers::CTL)
+            && (b.is_ascii_alphabetic() || b"@[\\]^_".contains(&b))
+        {
+            b & 0x1F
+        } else {
+            b
+        };
+        (b != 0).then_some(Input::Byte(b))
     }
-    if b == 0 {
+}
+
+static KBD: Mutex<Keyboard> = Mutex::new("kbd", Keyboard::new());
+
+fn scancode() -> Option<u8> {
+    use crate::x86_64::inb;
+    let status = Status::from_bits_truncate(unsafe { inb(STATUS_PORT) });
+    if !status.contains(Status::DATA_AVAIL) {
         return None;
     }
-    Some(b)
+    Some(unsafe { inb(DATA_PORT) })
+}
+
+// Reads the next scancode, if there is one, returning the input
+// it completes.
+fn poll() -> Option<Option<Input>> {
+    let code = scancode()?;
+    let mut kbd = KBD.lock();
+    Some(kbd.decode(code).and_then(|event| kbd.translate(&event)))
+}
+
+// Polls for a keystroke, for use before interrupts are enabled,
+// e.g. by the boot menu.  Only keys that produce a single byte
+// are returned.
+pub fn getb() -> Option<u8> {
+    match poll()? {
+        Some(Input::Byte(b)) => Some(b),
+        _ => None,
+    }
 }
 
 pub fn interrupt() {
-    console::interrupt(getb);
+    while let Some(input) = poll() {
+        let bytes = match input {
+            Some(Input::Byte(ref b)) => slice::from_ref(b),
+            Some(Input::Sequence(seq)) => seq,
+            Some(Input::Switch(vt)) => {
+                console::switch(vt);
+                continue;
+            }
+            Some(Input::Page { up }) => {
+                console::page(up);
+                continue;
+            }
+            None => continue,
+        };
+        let mut bytes = bytes.iter().copied();
+        console::interrupt(console::active(), || bytes.next());
+    }
     unsafe {
         xapic::eoi();
     }
 }
+
+// Makes a built-in keymap current, given its name, or loads one
+// from the calling process.
+pub fn keymap(proc: &Proc, op: usize, arg: usize) -> Result<()> {
+    let map = match op {
+        keymap::SELECT => {
+            let name = proc.fetch_str(arg).ok_or(Errno::EFAULT)?;
+            *crate::keymap::find(name).ok_or(Errno::ENOENT)?
+        }
+        keymap::LOAD => {
+            let bytes = proc
+                .fetch_slice(arg, mem::size_of::<Keymap>())
+                .ok_or(Errno::EFAULT)?;
+            unsafe { ptr::read_unaligned(bytes.as_ptr().cast::<Keymap>()) }
+        }
+        _ => return Err(Errno::EINVAL),
+    };
+    KBD.lock().keymap = map;
+    Ok(())
+}
+
+#[cfg(test)]
+mod kbd_tests {
+    use super::{Input, KeyEvent, Keyboard, LEFT, Modifiers};
+    use crate::keymap;
+
+    fn press(kbd: &mut Keyboard, codes: &[u8]) -> Option<Input> {
+        let mut input = None;
+        for &code in codes {
+            input = kbd.decode(code).and_then(|event| kbd.translate(&event));
+        }
+        input
+    }
+
+    #[test]
+    fn decodes_extended_keys() {
+        let mut kbd = Keyboard::new();
+        assert_eq!(kbd.decode(0xE0), None);
+        let left = KeyEvent {
+            key: LEFT,
+            pressed: true,
+            mods: Modifiers::NUMLOCK,
+        };
+        assert_eq!(kbd.decode(0x4B), Some(left));
+        assert_eq!(press(&mut kbd, &[0xE0, 0xCB]), None);
+        assert_eq!(
+            press(&mut kbd, &[0xE0, 0x53]),
+            Some(Input::Sequence(b"\x1b[3~"))
+        );
+        assert_eq!(
+            press(&mut kbd, &[0xE0, 0xD3, 0x53]),
+            Some(Input::Byte(b'.'))
+        );
+        // With Num Lock off, keypad . is Delete.
+        assert_eq!(
+            press(&mut kbd, &[0xD3, 0x45, 0xC5, 0x53]),
+            Some(Input::Sequence(b"\x1b[3~"))
+        );
+    }
+
+    #[test]
+    fn applies_modifiers() {
+        let mut kbd = Keyboard::new();
+        assert_eq!(press(&mut kbd, &[0x1E]), Some(Input::Byte(b'a')));
+        assert_eq!(press(&mut kbd, &[0x2A, 0x1E]), Some(Input::Byte(b'A')));
+        assert_eq!(press(&mut kbd, &[0xAA, 0x02]), Some(Input::Byte(b'1')));
+        assert_eq!(press(&mut kbd, &[0x1D, 0x20]), Some(Input::Byte(0x04)));
+        assert_eq!(press(&mut kbd, &[0x9D, 0x38, 0x3D]), Some(Input::Switch(2)));
+        // Caps Lock toggles once, however long it is held.
+        assert_eq!(
+            press(&mut kbd, &[0xB8, 0x3A, 0x3A, 0xBA, 0x10]),
+            Some(Input::Byte(b'Q'))
+        );
+        assert_eq!(press(&mut kbd, &[0x2A, 0x10]), Some(Input::Byte(b'q')));
+        assert_eq!(press(&mut kbd, &[0x02]), Some(Input::Byte(b'!')));
+    }
+
+    #[test]
+    fn translates_turkish_q() {
+        let mut kbd = Keyboard::new();
+        kbd.keymap = keymap::TRQ;
+        assert_eq!(press(&mut kbd, &[0x17]), Some(Input::Byte(0x8D)));
+        assert_eq!(press(&mut kbd, &[0x28]), Some(Input::Byte(b'i')));
+        assert_eq!(press(&mut kbd, &[0x2A, 0x28]), Some(Input::Byte(0x98)));
+        assert_eq!(
+            press(&mut kbd, &[0xAA, 0x3A, 0xBA, 0x17]),
+            Some(Input::Byte(b'I'))
+        );
+        assert_eq!(press(&mut kbd, &[0x34]), Some(Input::Byte(0x80)));
+        assert_eq!(
+            press(&mut kbd, &[0xE0, 0x38, 0x10]),
+            Some(Input::Byte(b'@'))
+        );
+        assert_eq!(press(&mut kbd, &[0x19]), Some(Input::Byte(b'P')));
+    }
+}
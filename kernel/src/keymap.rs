@@ -0,0 +1,132 @@
+// The built-in keymaps.
+//
+// Each is written as runs of keys with consecutive scancodes,
+// following the rows of the keyboard, e.g. `(0x10, b"qwerty")`
+// for the top row of letters.  The keypad is included so that
+// it works with Num Lock on; see `kbd.rs` for the keys that do
+// not depend on the layout.
+
+use syslib::keymap::{Keymap, NKEYS};
+
+type Rows<'a> = &'a [(usize, &'a [u8])];
+
+const fn keys(rows: Rows) -> [u8; NKEYS] {
+    let mut map = [0; NKEYS];
+    let mut r = 0;
+    while r < rows.len() {
+        let (start, bytes) = rows[r];
+        let mut k = 0;
+        while k < bytes.len() {
+            map[start + k] = bytes[k];
+            k += 1;
+        }
+        r += 1;
+    }
+    map
+}
+
+const fn keymap(normal: Rows, shift: Rows, altgr: Rows) -> Keymap {
+    Keymap {
+        normal: keys(normal),
+        shift: keys(shift),
+        altgr: keys(altgr),
+    }
+}
+
+const BS: u8 = 0x08;
+const ESC: u8 = 0x1b;
+
+pub static US: Keymap = keymap(
+    &[
+        (0x01, &[ESC]),
+        (0x02, b"1234567890-="),
+        (0x0E, &[BS, b'\t']),
+        (0x10, b"qwertyuiop[]\n"),
+        (0x1E, b"asdfghjkl;'`"),
+        (0x2B, b"\\zxcvbnm,./"),
+        (0x37, b"*"),
+        (0x39, b" "),
+        (0x47, b"789-456+1230."),
+        (0x56, b"\\"),
+    ],
+    &[
+        (0x01, &[ESC]),
+        (0x02, b"!@#$%^&*()_+"),
+        (0x0E, &[BS, b'\t']),
+        (0x10, b"QWERTYUIOP{}\n"),
+        (0x1E, b"ASDFGHJKL:\"~"),
+        (0x2B, b"|ZXCVBNM<>?"),
+        (0x37, b"*"),
+        (0x39, b" "),
+        (0x47, b"789-456+1230."),
+        (0x56, b"|"),
+    ],
+    &[],
+);
+
+// Turkish letters in code page 857.
+const CCEDIL: u8 = 0x87;
+const CCEDIL_UP: u8 = 0x80;
+const GBREVE: u8 = 0xA7;
+const GBREVE_UP: u8 = 0xA6;
+const DOTLESS_I: u8 = 0x8D;
+const DOTTED_I_UP: u8 = 0x98;
+const OUML: u8 = 0x94;
+const OUML_UP: u8 = 0x99;
+const SCEDIL: u8 = 0x9F;
+const SCEDIL_UP: u8 = 0x9E;
+const UUML: u8 = 0x81;
+const UUML_UP: u8 = 0x9A;
+const EACUTE: u8 = 0x82;
+const POUND: u8 = 0x9C;
+const HALF: u8 = 0xAB;
+
+#[rustfmt::skip]
+pub static TRQ: Keymap = keymap(
+    &[
+        (0x01, &[ESC]),
+        (0x02, b"1234567890*-"),
+        (0x0E, &[BS, b'\t']),
+        (0x10, &[b'q', b'w', b'e', b'r', b't', b'y', b'u', DOTLESS_I, b'o', b'p',
+                 GBREVE, UUML, b'\n']),
+        (0x1E, &[b'a', b's', b'd', b'f', b'g', b'h', b'j', b'k', b'l', SCEDIL,
+                 b'i', b'"']),
+        (0x2B, &[b',', b'z', b'x', b'c', b'v', b'b', b'n', b'm', OUML, CCEDIL, b'.']),
+        (0x37, b"*"),
+        (0x39, b" "),
+        (0x47, b"789-456+1230,"),
+        (0x56, b"<"),
+    ],
+    &[
+        (0x01, &[ESC]),
+        (0x02, b"!'^+%&/()=?_"),
+        (0x0E, &[BS, b'\t']),
+        (0x10, &[b'Q', b'W', b'E', b'R', b'T', b'Y', b'U', b'I', b'O', b'P',
+                 GBREVE_UP, UUML_UP, b'\n']),
+        (0x1E, &[b'A', b'S', b'D', b'F', b'G', b'H', b'J', b'K', b'L', SCEDIL_UP,
+                 DOTTED_I_UP, EACUTE]),
+        (0x2B, &[b';', b'Z', b'X', b'C', b'V', b'B', b'N', b'M', OUML_UP, CCEDIL_UP,
+                 b':']),
+        (0x37, b"*"),
+        (0x39, b" "),
+        (0x47, b"789-456+1230,"),
+        (0x56, b">"),
+    ],
+    &[
+        (0x02, &[b'>', POUND, b'#', b'$', HALF, 0, b'{', b'[', b']', b'}', b'\\', b'|']),
+        (0x10, b"@"),
+        (0x1B, b"~"),
+        (0x29, b"<"),
+        (0x2B, b"`"),
+        (0x56, b"|"),
+    ],
+);
+
+static KEYMAPS: [(&[u8], &Keymap); 2] = [(b"us", &US), (b"trq", &TRQ)];
+
+pub fn find(name: &[u8]) -> Option<&'static Keymap> {
+    KEYMAPS
+        .iter()
+        .find(|&&(n, _)| n == name)
+        .map(|&(_, keymap)| keymap)
+}
@@ -0,0 +1,244 @@
+// The kernel heap, which lets the kernel use `alloc`'s `Box`,
+// `Vec` and `BTreeMap`.  Small allocations come from caches of
+// objects of a few sizes, each carved from pages from `kalloc`;
+// larger ones take a page of their own.  kalloc hands out pages
+// one at a time, so nothing larger than a page can be allocated.
+//
+// When there is no memory, the allocator returns null.  Code that
+// can fail gracefully should use the fallible interfaces, such as
+// `Box::try_new` and `Vec::try_reserve`, and return ENOMEM; the
+// others panic.
+//
+// Each page of a cache, a slab, starts with a `Slab` header, and
+// its free objects are linked through their first word.  A cache
+// keeps a list of the slabs with free objects, and gives a slab's
+// page back to kalloc once all of its objects are free, unless it
+// is the only one, so that allocating and freeing an object does
+// not churn pages.
+
+use crate::arch::{PAGE_SIZE, Page};
+use crate::kalloc;
+use crate::spinlock::SpinMutex as Mutex;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::{self, NonNull};
+
+// The caches hold objects of 16, 32, ... 1024 bytes.
+const MINSIZE: usize = 16;
+const NCACHE: usize = 7;
+
+// Space for the `Slab` header at the start of each page, and so
+// the largest alignment that a cache can give its objects.
+const HEADER: usize = 64;
+
+#[repr(C)]
+struct Slab {
+    next: Option<NonNull<Slab>>,
+    free: Option<NonNull<Object>>,
+    used: usize,
+}
+const _: () = assert!(size_of::<Slab>() <= HEADER);
+
+struct Object {
+    next: Option<NonNull<Object>>,
+}
+
+pub struct Cache {
+    size: usize,
+    slabs: Option<NonNull<Slab>>,
+}
+unsafe impl Send for Cache {}
+
+impl Cache {
+    pub const fn new(size: usize) -> Cache {
+        Cache { size, slabs: None }
+    }
+
+    fn nobjects(&self) -> usize {
+        (PAGE_SIZE - HEADER) / self.size
+    }
+
+    // Takes a free object, making a slab of the page that `grow`
+    // returns if there is none.
+    pub fn alloc<G>(&mut self, grow: G) -> Option<NonNull<u8>>
+    where
+        G: FnOnce() -> Option<&'static mut Page>,
+    {
+        let mut slab = match self.slabs {
+            Some(slab) => slab,
+            None => {
+                let slab = self.carve(grow()?);
+                self.slabs = Some(slab);
+                slab
+            }
+        };
+        let slab = unsafe { slab.as_mut() };
+        let obj = slab.free.expect("full slab in cache");
+        slab.free = unsafe { obj.as_ref().next };
+        slab.used += 1;
+        if slab.free.is_none() {
+            self.slabs = slab.next.take();
+        }
+        Some(obj.cast())
+    }
+
+    fn carve(&self, page: &'static mut Page) -> NonNull<Slab> {
+        let base = page.as_mut().as_mut_ptr();
+        let mut free = None;
+        for k in (0..self.nobjects()).rev() {
+            let obj = unsafe { base.add(HEADER + k * self.size) }.cast::<Object>();
+            unsafe {
+                ptr::write(obj, Object { next: free });
+            }
+            free = NonNull::new(obj);
+        }
+        let slab = base.cast::<Slab>();
+        unsafe {
+            ptr::write(
+                slab,
+                Slab {
+                    next: None,
+                    free,
+                    used: 0,
+                },
+            );
+        }
+        NonNull::new(slab).unwrap()
+    }
+
+    // Returns the object at `ptr` to its slab, handing the slab's
+    // page to `release` if that leaves it empty.
+    pub fn dealloc<R>(&mut self, ptr: NonNull<u8>, release: R)
+    where
+        R: FnOnce(&'static mut Page),
+    {
+        let page = ptr.as_ptr().map_addr(|a| a & !(PAGE_SIZE - 1));
+        let mut slab = NonNull::new(page.cast::<Slab>()).unwrap();
+        let s = unsafe { slab.as_mut() };
+        let obj = ptr.cast::<Object>();
+        unsafe {
+            ptr::write(obj.as_ptr(), Object { next: s.free });
+        }
+        let was_full = s.free.replace(obj).is_none();
+        s.used -= 1;
+        if was_full {
+            s.next = self.slabs.replace(slab);
+        }
+        if s.used == 0 && self.unlink_spare(slab) {
+            release(unsafe { slab.cast::<Page>().as_mut() });
+        }
+    }
+
+    // Removes the empty `slab` from the list unless it is alone
+    // there, returning whether it did.
+    fn unlink_spare(&mut self, slab: NonNull<Slab>) -> bool {
+        if self.slabs == Some(slab) && unsafe { slab.as_ref().next }.is_none() {
+            return false;
+        }
+        let mut link = &mut self.slabs;
+        while let Some(mut s) = *link {
+            if s == slab {
+                *link = unsafe { slab.as_ref().next };
+                return true;
+            }
+            link = unsafe { &mut s.as_mut().next };
+        }
+        unreachable!("empty slab not in cache");
+    }
+}
+
+// The cache for objects of `layout`, if it is not too large or too
+// strictly aligned for one.
+fn cache_for(layout: Layout) -> Option<usize> {
+    if layout.align() > HEADER {
+        return None;
+    }
+    let size = layout.size().max(layout.align()).max(MINSIZE);
+    let k = size.next_power_of_two().trailing_zeros() - MINSIZE.trailing_zeros();
+    (k < NCACHE as u32).then_some(k as usize)
+}
+
+static CACHES: Mutex<[Cache; NCACHE]> = Mutex::new("kheap", {
+    let mut caches = [const { Cache::new(0) }; NCACHE];
+    let mut k = 0;
+    while k < NCACHE {
+        caches[k] = Cache::new(MINSIZE << k);
+        k += 1;
+    }
+    caches
+});
+
+pub struct Heap;
+
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let p = match cache_for(layout) {
+            Some(k) => CACHES.lock()[k].alloc(kalloc::alloc),
+            None if layout.size() <= PAGE_SIZE && layout.align() <= PAGE_SIZE => {
+                kalloc::alloc().map(|page| NonNull::from(page).cast())
+            }
+            None => None,
+        };
+        p.map_or(ptr::null_mut(), NonNull::as_ptr)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let ptr = NonNull::new(ptr).expect("freeing null");
+        match cache_for(layout) {
+            Some(k) => CACHES.lock()[k].dealloc(ptr, kalloc::free),
+            None => kalloc::free(unsafe { ptr.cast::<Page>().as_mut() }),
+        }
+    }
+}
+
+#[cfg(not(test))]
+#[global_allocator]
+static HEAP: Heap = Heap;
+
+#[cfg(test)]
+mod kheap_tests {
+    use super::*;
+    use std::boxed::Box;
+    use std::cell::Cell;
+
+    fn page() -> Option<&'static mut Page> {
+        Some(Box::leak(Box::new(Page::empty())))
+    }
+
+    #[test]
+    fn picks_cache() {
+        let of = |size, align| cache_for(Layout::from_size_align(size, align).unwrap());
+        assert_eq!(of(1, 1), Some(0));
+        assert_eq!(of(16, 8), Some(0));
+        assert_eq!(of(17, 1), Some(1));
+        assert_eq!(of(8, 64), Some(2));
+        assert_eq!(of(1024, 8), Some(NCACHE - 1));
+        assert_eq!(of(1025, 8), None);
+        assert_eq!(of(8, 128), None);
+    }
+
+    #[test]
+    fn slabs_fill_and_empty() {
+        let mut cache = Cache::new(256);
+        let n = cache.nobjects();
+        let pages = Cell::new(0);
+        let grow = || {
+            pages.set(pages.get() + 1);
+            page()
+        };
+        let objs = (0..2 * n)
+            .map(|_| cache.alloc(grow).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(pages.get(), 2);
+        for (k, obj) in objs.iter().enumerate() {
+            assert_eq!(obj.addr().get() % 64, 0);
+            assert_eq!(obj.addr().get() % PAGE_SIZE, HEADER + k % n * 256);
+        }
+        let released = Cell::new(0);
+        for obj in &objs {
+            cache.dealloc(*obj, |_| released.set(released.get() + 1));
+        }
+        // One slab is kept for the next allocation.
+        assert_eq!(released.get(), 1);
+        cache.alloc(|| panic!("grew with a free slab")).unwrap();
+    }
+}
@@ -72,6 +72,24 @@ unsafe fn addr_to_boot_info(addr: usize) -> &'static BootInfo {
     unsafe { &*(addr as *const BootInfo) }
 }
 
+// Returns the kernel command line passed by the boot loader,
+// or an empty slice if there is none.  The string lives in low
+// memory that is eventually given to the page allocator, so
+// callers must copy anything they want to keep.
+pub unsafe fn boot_cmdline(boot_info_phys: u64) -> &'static [u8] {
+    let boot_info_addr = phys_to_addr(boot_info_phys);
+    let boot_info = unsafe { addr_to_boot_info(boot_info_addr) };
+    if boot_info.flags & (1 << 2) == 0 || boot_info.cmdline == 0 {
+        return &[];
+    }
+    let s = phys_to_ptr::<u8>(boot_info.cmdline.into());
+    let mut len = 0;
+    while unsafe { *s.add(len) } != 0 {
+        len += 1;
+    }
+    unsafe { slice::from_raw_parts(s, len) }
+}
+
 pub unsafe fn early_init(boot_info_phys: u64) {
     let boot_info_addr = phys_to_addr(boot_info_phys);
     let boot_info = unsafe { addr_to_boot_info(boot_info_addr) };
@@ -181,13 +199,31 @@ pub unsafe fn init() {
         unsafe { page_slice_mut(phys_to_ptr_mut(r.start), phys_to_ptr_mut(r.end)) }
     }
 
-    use crate::kalloc::free_pages;
-    for entry in mem_map() {
-        if entry.typ != MemType::Memory || entry.end < EARLY_FREE_END {
+    use crate::kalloc::{self, free_pages};
+    // Page reference counts, for all of memory, are kept at the
+    // start of the first region with room for them.
+    let memory = || {
+        mem_map()
+            .iter()
+            .filter(|entry| entry.typ == MemType::Memory)
+    };
+    let npages = memory().map(|entry| entry.end).max().unwrap_or(0) as usize / PAGE_SIZE;
+    let refs_len = (npages.div_ceil(kalloc::REFS_PER_PAGE) * PAGE_SIZE) as u64;
+    let mut refs_made = false;
+    for entry in memory() {
+        if entry.end < EARLY_FREE_END {
             continue;
         }
-        crate::println!("freeing pages: {:x?}", entry);
-        let start_phys = cmp::max(entry.start, EARLY_FREE_END);
+        if crate::cmdline::log_enabled(crate::cmdline::LOG_INFO) {
+            crate::println!("freeing pages: {:x?}", entry);
+        }
+        let mut start_phys = cmp::max(entry.start, EARLY_FREE_END);
+        if !refs_made && entry.end - start_phys > refs_len {
+            let refs = start_phys..start_phys + refs_len;
+            unsafe { kalloc::init_refs(phys_to_page_slice_mut(refs)) };
+            start_phys += refs_len;
+            refs_made = true;
+        }
         free_pages(unsafe { phys_to_page_slice_mut(start_phys..entry.end) });
     }
     // Also free conventional memory.
@@ -0,0 +1,463 @@
+// Memory mappings, and the mmap, munmap and mprotect system calls.
+//
+// Between a process's heap, which grows up from 0, and its stack,
+// at the top, lies space for mappings: anonymous memory, private
+// to the process or shared with the children that inherit it, or
+// the contents of a file, read-only.  Each mapping is a `Vma`, and
+// its pages are mapped as they are first touched, as the heap's
+// are, except for shared anonymous memory, which is allocated as
+// it is mapped, so that every process that inherits it sees the
+// same pages.
+
+use crate::Result;
+use crate::arch;
+use crate::fs;
+use crate::fslog;
+use crate::kalloc;
+use crate::param::{MMAPBASE, NVMA, USERGUARD};
+use crate::proc::Proc;
+use crate::vm::PageFlags;
+use core::mem;
+use core::ops::Range;
+use syslib::errno::Errno;
+use syslib::mman::{MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, MAP_SHARED};
+use syslib::mman::{PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Vma {
+    pub start: usize,
+    pub end: usize,
+    prot: u32,
+    shared: bool,
+    // The file mapped, and the offset in it of `start`.
+    file: Option<(&'static fs::Inode, u64)>,
+}
+
+impl Vma {
+    fn contains(&self, va: usize) -> bool {
+        (self.start..self.end).contains(&va)
+    }
+
+    fn overlaps(&self, start: usize, end: usize) -> bool {
+        self.start < end && start < self.end
+    }
+
+    // Whether the kernel may read the mapping for the process.
+    pub fn readable(&self) -> bool {
+        self.prot != PROT_NONE
+    }
+
+    fn page_flags(&self) -> PageFlags {
+        let mut flags = PageFlags::empty();
+        if self.prot != PROT_NONE {
+            flags.insert(PageFlags::USER);
+        }
+        if self.prot & PROT_WRITE != 0 {
+            flags.insert(PageFlags::WRITE);
+        }
+        if self.prot & PROT_EXEC == 0 {
+            flags.insert(PageFlags::NX);
+        }
+        flags
+    }
+
+    // Takes another reference to the file mapped, for a copy.
+    fn dup(&self) -> Vma {
+        if let Some((ip, _)) = self.file {
+            ip.dup();
+        }
+        *self
+    }
+
+    fn release(&self) {
+        if let Some((ip, _)) = self.file {
+            fslog::with_op(|| ip.put().expect("iput mapped file"));
+        }
+    }
+
+    // Resolves a fault at `va`, in the mapping, as `Proc::page_fault`
+    // does: a page first touched is zeroed or read from the file,
+    // and a write to a private page shared copy-on-write copies it.
+    pub fn fault(&self, proc: &Proc, va: usize, write: bool) -> Result<()> {
+        if !self.readable() || (write && self.prot & PROT_WRITE == 0) {
+            return Err(Errno::EFAULT);
+        }
+        let va = arch::page_round_down(va);
+        if proc.with_pgtbl(|pgtbl| pgtbl.entry_for(va)).is_some() {
+            if !write {
+                return Err(Errno::EFAULT);
+            }
+            return proc.with_pgtbl(|pgtbl| pgtbl.copy_on_write(va));
+        }
+        let Some((ip, off)) = self.file else {
+            return proc.with_pgtbl(|pgtbl| pgtbl.map_zeroed(va, self.page_flags()));
+        };
+        let page = kalloc::alloc().ok_or(Errno::ENOMEM)?;
+        let off = off + (va - self.start) as u64;
+        // The part of a page past the end of the file reads as zero.
+        let read = ip.with_lock(|ip| {
+            if off < ip.size() {
+                ip.readi(page.as_mut(), off).map(drop)
+            } else {
+                Ok(())
+            }
+        });
+        let mapped = read.and_then(|()| {
+            proc.with_pgtbl(|pgtbl| pgtbl.map_to(page.phys_addr(), va, self.page_flags()))
+        });
+        if mapped.is_err() {
+            kalloc::free(page);
+        }
+        mapped
+    }
+}
+
+// The mappings of a process, in no particular order.
+pub struct Vmas([Option<Vma>; NVMA]);
+
+impl Vmas {
+    pub const fn new() -> Vmas {
+        Vmas([None; NVMA])
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Vma> {
+        self.0.iter().flatten()
+    }
+
+    pub fn find(&self, va: usize) -> Option<Vma> {
+        self.iter().find(|vma| vma.contains(va)).copied()
+    }
+
+    // The mapped ranges, and whether each is shared, for fork.
+    pub fn regions(&self) -> impl Iterator<Item = (Range<usize>, bool)> + '_ {
+        self.iter().map(|vma| (vma.start..vma.end, vma.shared))
+    }
+
+    fn is_free(&self, start: usize, end: usize) -> bool {
+        !self.iter().any(|vma| vma.overlaps(start, end))
+    }
+
+    // Whether every page from `start` to `end` is mapped.
+    fn covers(&self, start: usize, end: usize) -> bool {
+        let mut va = start;
+        while va < end {
+            match self.find(va) {
+                Some(vma) => va = vma.end,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    // Finds the lowest place for `len` bytes that overlaps no
+    // mapping.
+    fn place(&self, len: usize) -> Option<usize> {
+        let mut start = MMAPBASE;
+        loop {
+            let end = start.checked_add(len).filter(|&end| end <= USERGUARD)?;
+            match self
+                .iter()
+                .filter(|vma| vma.overlaps(start, end))
+                .map(|vma| vma.end)
+                .max()
+            {
+                Some(next) => start = next,
+                None => return Some(start),
+            }
+        }
+    }
+
+    fn insert(&mut self, vma: Vma) -> Result<()> {
+        let slot = self
+            .0
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(Errno::ENOMEM)?;
+        *slot = Some(vma);
+        Ok(())
+    }
+
+    // Splits the mapping that `va` falls inside, if any, in two
+    // at `va`.
+    fn split(&mut self, va: usize) -> Result<()> {
+        let Some(k) = self
+            .0
+            .iter()
+            .position(|slot| slot.is_some_and(|vma| vma.start < va && va < vma.end))
+        else {
+            return Ok(());
+        };
+        let vma = self.0[k].unwrap();
+        let mut upper = vma;
+        upper.start = va;
+        upper.file = vma
+            .file
+            .map(|(ip, off)| (ip, off + (va - vma.start) as u64));
+        self.insert(upper)?;
+        vma.dup();
+        self.0[k].as_mut().unwrap().end = va;
+        Ok(())
+    }
+
+    // Removes the mappings from `start` to `end`, splitting any
+    // that extend beyond, and returns them to be released.
+    fn remove(&mut self, start: usize, end: usize) -> Result<Vmas> {
+        self.split(start)?;
+        self.split(end)?;
+        let mut removed = Vmas::new();
+        for (slot, taken) in self.0.iter_mut().zip(removed.0.iter_mut()) {
+            if slot.is_some_and(|vma| vma.overlaps(start, end)) {
+                *taken = slot.take();
+            }
+        }
+        Ok(removed)
+    }
+
+    // Gives the mappings from `start` to `end` protection `prot`,
+    // and returns the flags for their pages.
+    fn protect(&mut self, start: usize, end: usize, prot: u32) -> Result<PageFlags> {
+        let writes_file = |vma: &Vma| vma.file.is_some() && prot & PROT_WRITE != 0;
+        if self
+            .iter()
+            .any(|vma| vma.overlaps(start, end) && writes_file(vma))
+        {
+            return Err(Errno::EACCES);
+        }
+        self.split(start)?;
+        self.split(end)?;
+        let mut flags = PageFlags::empty();
+        for vma in self.0.iter_mut().flatten() {
+            if vma.overlaps(start, end) {
+                vma.prot = prot;
+                flags = vma.page_flags();
+            }
+        }
+        Ok(flags)
+    }
+
+    // Copies the mappings for a child process.
+    pub fn dup(&self) -> Vmas {
+        let mut vmas = Vmas::new();
+        for (slot, vma) in vmas.0.iter_mut().zip(self.0.iter()) {
+            *slot = vma.as_ref().map(Vma::dup);
+        }
+        vmas
+    }
+
+    // Drops the references the mappings hold to files.
+    fn release(self) {
+        self.iter().for_each(Vma::release);
+    }
+
+    // Removes all the mappings, as on exec or exit.
+    pub fn clear(&mut self) {
+        mem::replace(self, Vmas::new()).release();
+    }
+}
+
+// The most that can be mapped.  Longer lengths are refused
+// before they are rounded up to whole pages, which would overflow.
+const MAXLEN: usize = USERGUARD - MMAPBASE;
+
+// Checks that `addr` and `len` name whole pages in the space for
+// mappings, and returns where they end.
+fn pages(addr: usize, len: usize) -> Result<usize> {
+    if len == 0 || len > MAXLEN || !addr.is_multiple_of(arch::PAGE_SIZE) || addr < MMAPBASE {
+        return Err(Errno::EINVAL);
+    }
+    let end = addr + arch::page_round_up(len);
+    if end > USERGUARD {
+        return Err(Errno::EINVAL);
+    }
+    Ok(end)
+}
+
+fn valid_prot(prot: u32) -> bool {
+    prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) == 0
+}
+
+// Maps `len` bytes at or near `addr`, of the file open as `fd`
+// from `offset` unless anonymous, and returns the address of the
+// mapping.
+pub fn mmap(
+    proc: &Proc,
+    addr: usize,
+    len: usize,
+    prot: u32,
+    flags: u32,
+    fd: i32,
+    offset: u64,
+) -> Result<usize> {
+    let shared = match flags & (MAP_SHARED | MAP_PRIVATE) {
+        MAP_SHARED => true,
+        MAP_PRIVATE => false,
+        _ => return Err(Errno::EINVAL),
+    };
+    if flags & !(MAP_SHARED | MAP_PRIVATE | MAP_FIXED | MAP_ANONYMOUS) != 0
+        || !valid_prot(prot)
+        || len == 0
+        || len > MAXLEN
+    {
+        return Err(Errno::EINVAL);
+    }
+    let len = arch::page_round_up(len);
+    let file = if flags & MAP_ANONYMOUS != 0 {
+        None
+    } else {
+        if prot & PROT_WRITE != 0 {
+            return Err(Errno::EACCES);
+        }
+        if !offset.is_multiple_of(arch::PAGE_SIZE as u64) {
+            return Err(Errno::EINVAL);
+        }
+        let file = proc.get_fd(fd as usize).ok_or(Errno::EBADF)?;
+        Some((file.mappable()?, offset))
+    };
+    let mut vmas = proc.vmas().borrow_mut();
+    let start = if flags & MAP_FIXED != 0 {
+        let end = pages(addr, len)?;
+        vmas.remove(addr, end)?.release();
+        proc.with_pgtbl(|pgtbl| pgtbl.free_user_pages(addr, end));
+        addr
+    } else {
+        match pages(addr, len) {
+            Ok(end) if vmas.is_free(addr, end) => addr,
+            _ => vmas.place(len).ok_or(Errno::ENOMEM)?,
+        }
+    };
+    let vma = Vma {
+        start,
+        end: start + len,
+        prot,
+        shared,
+        file,
+    };
+    vmas.insert(vma)?;
+    vma.dup();
+    if shared && file.is_none() {
+        let populated = (vma.start..vma.end)
+            .step_by(arch::PAGE_SIZE)
+            .try_for_each(|va| proc.with_pgtbl(|pgtbl| pgtbl.map_zeroed(va, vma.page_flags())));
+        if let Err(e) = populated {
+            vmas.remove(vma.start, vma.end)?.release();
+            proc.with_pgtbl(|pgtbl| pgtbl.free_user_pages(vma.start, vma.end));
+            return Err(e);
+        }
+    }
+    Ok(start)
+}
+
+// Removes any mappings from `addr` for `len` bytes.
+pub fn munmap(proc: &Proc, addr: usize, len: usize) -> Result<()> {
+    let end = pages(addr, len)?;
+    proc.vmas().borrow_mut().remove(addr, end)?.release();
+    proc.with_pgtbl(|pgtbl| pgtbl.free_user_pages(addr, end));
+    Ok(())
+}
+
+// Changes the protection of the mappings from `addr` for `len`
+// bytes, which must all be mapped.
+pub fn mprotect(proc: &Proc, addr: usize, len: usize, prot: u32) -> Result<()> {
+    let end = pages(addr, len)?;
+    if !valid_prot(prot) {
+        return Err(Errno::EINVAL);
+    }
+    let mut vmas = proc.vmas().borrow_mut();
+    if !vmas.covers(addr, end) {
+        return Err(Errno::ENOMEM);
+    }
+    let flags = vmas.protect(addr, end, prot)?;
+    proc.with_pgtbl(|pgtbl| pgtbl.protect(addr..end, flags));
+    Ok(())
+}
+
+#[cfg(test)]
+mod mman_tests {
+    use super::*;
+
+    fn anon(start: usize, end: usize) -> Vma {
+        Vma {
+            start,
+            end,
+            prot: PROT_READ | PROT_WRITE,
+            shared: false,
+            file: None,
+        }
+    }
+
+    const PAGE: usize = arch::PAGE_SIZE;
+
+    #[test]
+    fn places_first_fit() {
+        let mut vmas = Vmas::new();
+        assert_eq!(vmas.place(PAGE), Some(MMAPBASE));
+        vmas.insert(anon(MMAPBASE, MMAPBASE + PAGE)).unwrap();
+        vmas.insert(anon(MMAPBASE + 3 * PAGE, MMAPBASE + 4 * PAGE))
+            .unwrap();
+        assert_eq!(vmas.place(2 * PAGE), Some(MMAPBASE + PAGE));
+        assert_eq!(vmas.place(3 * PAGE), Some(MMAPBASE + 4 * PAGE));
+        assert_eq!(vmas.place(USERGUARD), None);
+    }
+
+    #[test]
+    fn splits_on_remove_and_protect() {
+        let mut vmas = Vmas::new();
+        vmas.insert(anon(MMAPBASE, MMAPBASE + 4 * PAGE)).unwrap();
+        let removed = vmas.remove(MMAPBASE + PAGE, MMAPBASE + 2 * PAGE).unwrap();
+        assert_eq!(
+            removed.regions().collect::<Vec<_>>(),
+            [(MMAPBASE + PAGE..MMAPBASE + 2 * PAGE, false)]
+        );
+        assert!(
+            vmas.find(MMAPBASE)
+                .is_some_and(|vma| vma.end == MMAPBASE + PAGE)
+        );
+        assert!(vmas.find(MMAPBASE + PAGE).is_none());
+        assert!(
+            vmas.find(MMAPBASE + 3 * PAGE)
+                .is_some_and(|vma| vma.start == MMAPBASE + 2 * PAGE)
+        );
+        assert!(!vmas.covers(MMAPBASE, MMAPBASE + 3 * PAGE));
+
+        let flags = vmas
+            .protect(MMAPBASE + 3 * PAGE, MMAPBASE + 4 * PAGE, PROT_READ)
+            .unwrap();
+        assert!(flags.contains(PageFlags::USER | PageFlags::NX));
+        assert!(!flags.contains(PageFlags::WRITE));
+        assert_eq!(
+            vmas.find(MMAPBASE + 2 * PAGE).unwrap().prot,
+            PROT_READ | PROT_WRITE
+        );
+        assert_eq!(vmas.find(MMAPBASE + 3 * PAGE).unwrap().prot, PROT_READ);
+        assert_eq!(vmas.regions().count(), 3);
+    }
+
+    #[test]
+    fn runs_out_of_slots() {
+        let mut vmas = Vmas::new();
+        for k in 0..NVMA {
+            let start = MMAPBASE + 2 * k * PAGE;
+            vmas.insert(anon(start, start + PAGE)).unwrap();
+        }
+        assert!(vmas.insert(anon(USERGUARD - PAGE, USERGUARD)).is_err());
+        // Unmapping the middle of a mapping needs a slot to split it.
+        let mut vmas = Vmas::new();
+        vmas.insert(anon(MMAPBASE, MMAPBASE + 3 * PAGE)).unwrap();
+        for k in 1..NVMA {
+            let start = MMAPBASE + 4 * k * PAGE;
+            vmas.insert(anon(start, start + PAGE)).unwrap();
+        }
+        assert!(vmas.remove(MMAPBASE + PAGE, MMAPBASE + 2 * PAGE).is_err());
+    }
+
+    #[test]
+    fn checks_pages() {
+        assert_eq!(pages(MMAPBASE, 1), Ok(MMAPBASE + PAGE));
+        assert_eq!(pages(MMAPBASE, MAXLEN), Ok(USERGUARD));
+        assert_eq!(pages(MMAPBASE + PAGE, MAXLEN), Err(Errno::EINVAL));
+        assert_eq!(pages(MMAPBASE, usize::MAX), Err(Errno::EINVAL));
+        assert_eq!(pages(USERGUARD - PAGE, usize::MAX), Err(Errno::EINVAL));
+        assert_eq!(pages(MMAPBASE + 1, PAGE), Err(Errno::EINVAL));
+        assert_eq!(pages(MMAPBASE, 0), Err(Errno::EINVAL));
+    }
+}
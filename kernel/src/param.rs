@@ -1,12 +1,23 @@
 #![allow(dead_code)]
 
 pub const KERNBASE: usize = 0xFFFF_8000_0000_0000;
-pub const USERSTACK: usize = 0x0000_7FFF_FFFF_C000;
+// User stacks grow down from USEREND as they are touched, to at
+// most MAXSTACK bytes.  The page below, USERGUARD, is never mapped,
+// so a stack that overflows faults, and the heap ends below it.
+pub const MAXSTACK: usize = 8 * 1024 * 1024;
+pub const USERSTACK: usize = USEREND - MAXSTACK;
+pub const USERGUARD: usize = USERSTACK - 4096;
 pub const USEREND: usize = 0x0000_8000_0000_0000;
+// The heap ends where memory mappings begin, which leaves them
+// the space up to the stack's guard page.
+pub const MMAPBASE: usize = 0x0000_4000_0000_0000;
 pub const NPROC: usize = 256;
+// Clock ticks per second; see xapic::init.
+pub const HZ: u64 = 1000;
 pub const NPCICFGMAX: usize = 256;
 pub const NCPUMAX: usize = 256;
 pub const NOFILE: usize = 64;
+pub const NVMA: usize = 16;
 pub const NFILE: usize = 1024;
 pub const NINODE: usize = 1024;
 pub const NDEV: usize = 128;
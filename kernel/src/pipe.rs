@@ -9,6 +9,8 @@ use core::mem;
 use core::ptr;
 use core::sync::atomic::{AtomicBool, Ordering};
 use static_assertions::const_assert;
+use syslib::errno::Errno;
+use syslib::signal::SIGPIPE;
 
 const fn paspace() -> usize {
     const PASIZE: usize = mem::size_of::<PipeAlloc>();
@@ -121,8 +123,8 @@ impl<'a> file::Like for PipeReader<'a> {
     fn read(&self, _file: &file::File, buf: &mut [u8]) -> Result<usize> {
         self.pipe.with_lock(|pipe| {
             while !pipe.readable() {
-                if myproc().dead() {
-                    return Err("dead");
+                if myproc().interrupted() {
+                    return Err(Errno::EINTR);
                 }
                 myproc().sleep(pipe.read_chan(), self.pipe);
             }
@@ -159,7 +161,11 @@ impl<'a> file::Like for PipeWriter<'a> {
             for &b in buf.iter() {
                 while pipe.is_full() {
                     if pipe.broken() {
-                        return Err("broken pipe");
+                        proc::kill(myproc().pid(), SIGPIPE);
+                        return Err(Errno::EPIPE);
+                    }
+                    if myproc().interrupted() {
+                        return Err(Errno::EINTR);
                     }
                     proc::wakeup(pipe.read_chan());
                     myproc().sleep(pipe.write_chan(), self.pipe);
@@ -191,7 +197,7 @@ const_assert!(mem::size_of::<PipeSlab>() <= 64);
 
 impl PipeSlab {
     pub fn new() -> Result<&'static mut PipeSlab> {
-        let page = kalloc::alloc().ok_or("cannot allocate pipe slab")?;
+        let page = kalloc::alloc().ok_or(Errno::ENOMEM)?;
         let ptr = page.as_mut().as_mut_ptr();
         let ps = unsafe { &mut *(ptr as *mut PipeSlab) };
         ps.pipes = unsafe { ptr.add(64) } as *mut PipeAlloc<'_>;
@@ -249,7 +255,7 @@ pub fn alloc() -> Result<(&'static file::File, &'static file::File)> {
         }
         let slab = pipes.take().unwrap();
         assert!(!slab.is_empty());
-        let (r, w) = slab.alloc().ok_or("pipe allocation failed")?;
+        let (r, w) = slab.alloc().ok_or(Errno::ENOMEM)?;
         if !slab.is_empty() {
             *pipes = Some(slab);
         }
@@ -257,10 +263,10 @@ pub fn alloc() -> Result<(&'static file::File, &'static file::File)> {
     };
     let reader_guard = Guard::new(r);
     let writer_guard = Guard::new(w);
-    let reader = file::alloc(file::OpenFlags::Read, r).ok_or("pipe read file alloc failed")?;
+    let reader = file::alloc(file::OpenFlags::Read, r).ok_or(Errno::ENFILE)?;
     let reader_file_guard = file::Guard::new(reader);
     reader_guard.release();
-    let writer = file::alloc(file::OpenFlags::Write, w).ok_or("pipe write file alloc failed")?;
+    let writer = file::alloc(file::OpenFlags::Write, w).ok_or(Errno::ENFILE)?;
     writer_guard.release();
     reader_file_guard.release();
     Ok((reader, writer))
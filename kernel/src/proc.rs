@@ -1,14 +1,22 @@
 use crate::Result;
 use crate::arch;
+use crate::cmdline;
+use crate::console;
 use crate::file;
 use crate::fs;
 use crate::initcode;
+use crate::ipi;
 use crate::kalloc;
 use crate::kmem;
+use crate::mman::Vmas;
 use crate::param;
-use crate::param::{USEREND, USERSTACK};
+use crate::param::{MMAPBASE, USEREND, USERSTACK};
+use crate::resource;
+use crate::sched::{self, AtomicSched, Policy, RunQueue, Sched};
+use crate::signal::{self, Signals};
 use crate::spinlock::{SpinMutex as Mutex, without_intrs};
 use crate::syscall;
+use crate::trap;
 use crate::vm;
 use crate::volatile;
 use core::cell::{Cell, RefCell};
@@ -17,15 +25,44 @@ use core::fmt;
 use core::mem::size_of;
 use core::ptr::{self, null_mut};
 use core::slice;
-use core::sync::atomic::AtomicBool;
-
-static PROCS: Mutex<[Proc; param::NPROC]> =
-    Mutex::new("procs", [const { Proc::new() }; param::NPROC]);
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use syslib::errno::Errno;
+use syslib::resource::{PRIO_PGRP, PRIO_PROCESS, Rusage};
+use syslib::signal::{SIGCHLD, SIGCONT, SIGKILL, SigAction};
+use syslib::wait::{self, WCONTINUED, WNOHANG, WUNTRACED};
+
+// The process table.  Each process's own lock guards its state,
+// its scheduling state and the switch to and from it; TREE guards
+// the allocation of slots, the parent of each process, its process
+// group and session, and the handshake between exit and wait.
+//
+// Locks are taken in the order TREE, then a process's lock, then a
+// run queue's.  A lock passed to `sleep` comes before the process's.
+static PROCS: [Proc; param::NPROC] = [const { Proc::new() }; param::NPROC];
+static TREE: Mutex<()> = Mutex::new("procs", ());
 
 static mut INIT_PROC: usize = 0;
 
+// Each CPU's queue of processes waiting to run, and its length,
+// which may be read without the lock when choosing a CPU to put a
+// process on or to steal one from.
+static RUNQ: [Mutex<RunQueue>; param::NCPUMAX] =
+    [const { Mutex::new("runq", RunQueue::new(sched::Active::new())) }; param::NCPUMAX];
+static LOAD: [AtomicUsize; param::NCPUMAX] = [const { AtomicUsize::new(0) }; param::NCPUMAX];
+
+// The number of CPUs running the scheduler, whether each is, and
+// so answers interrupts, and whether each is halted for want of
+// work, and so must be kicked to look again.
+static ONLINE: AtomicUsize = AtomicUsize::new(0);
+static UP: [AtomicBool; param::NCPUMAX] = [const { AtomicBool::new(false) }; param::NCPUMAX];
+static IDLE: [AtomicBool; param::NCPUMAX] = [const { AtomicBool::new(false) }; param::NCPUMAX];
+
 pub unsafe fn init(kpgtbl: &vm::PageTable) {
-    let page = make_init_user_page(initcode::start_init_slice());
+    let page = make_init_user_page(
+        initcode::start_init_slice(),
+        cmdline::init_path(),
+        cmdline::init_arg(),
+    );
     let mut pgtbl = kpgtbl.dup_kern().expect("init address space alloc failed");
     let perms = vm::PageFlags::USER | vm::PageFlags::WRITE;
     pgtbl
@@ -38,22 +75,28 @@ pub unsafe fn init(kpgtbl: &vm::PageTable) {
             pd.set_name(b"init");
         }
         p.set_parent(p.as_chan());
+        p.pgid.set(p.pid());
+        p.sid.set(p.pid());
         p.set_size(arch::PAGE_SIZE);
         unsafe {
             p.context_mut().set_return(firstret);
             INIT_PROC = p.as_chan();
             p.user_context_mut().set_flags(arch::RFlags::INTR_EN);
         }
-        p.set_state(ProcState::RUNNABLE);
         Some(())
     })
     .expect("allocating init proc failed");
 }
 
-fn make_init_user_page(init_code: &[u8]) -> &'static mut arch::Page {
+fn make_init_user_page(
+    init_code: &[u8],
+    path: &[u8],
+    arg: Option<&[u8]>,
+) -> &'static mut arch::Page {
     let page = kalloc::alloc().expect("init user alloc failed");
     let len = init_code.len();
     volatile::copy_slice(&mut page.as_mut()[..len], init_code);
+    initcode::set_init_args(page.as_mut(), path, arg);
     page
 }
 
@@ -79,6 +122,7 @@ pub enum ProcState {
     SLEEPING(usize),
     RUNNABLE,
     RUNNING,
+    STOPPED,
     ZOMBIE(i32),
 }
 
@@ -103,6 +147,7 @@ impl PerProc {
     pub fn set_name(&mut self, name: &[u8]) {
         let len = cmp::min(name.len(), self.name.len());
         volatile::copy_slice(&mut self.name[..len], &name[..len]);
+        self.name[len..].fill(0);
     }
 
     pub fn context_ptr(&self) -> *const arch::Context {
@@ -119,16 +164,37 @@ impl PerProc {
 }
 
 pub struct Proc {
+    lock: Mutex<()>,
     state: Cell<ProcState>,
     pid: Cell<u32>,
+    pgid: Cell<u32>,
+    sid: Cell<u32>,
     parent: Cell<Option<usize>>,
     killed: AtomicBool,
     data: RefCell<PerProc>,
     size: Cell<usize>,
+    vmas: RefCell<Vmas>,
     files: RefCell<[Option<&'static file::File>; param::NOFILE]>,
     cwd: Cell<Option<&'static fs::Inode>>,
+    signals: Signals,
+    // The controlling terminal: the console acquired by the
+    // session leader, and inherited by its descendants.
+    tty: Cell<Option<usize>>,
+    // Resources used by the process, and by the children it has
+    // reaped.
+    usage: Cell<Rusage>,
+    child_usage: Cell<Rusage>,
+    // A stop or continue not yet reported to the parent by wait.
+    report: Cell<Option<i32>>,
+    sched: AtomicSched,
+    // The CPU the process last ran or was queued on.
+    cpu: Cell<usize>,
 }
 
+// The cells of a process are shared between CPUs under the locks
+// described with PROCS.
+unsafe impl Sync for Proc {}
+
 impl fmt::Debug for Proc {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:x}", (self as *const Self).addr())
@@ -138,14 +204,25 @@ impl fmt::Debug for Proc {
 impl Proc {
     pub const fn new() -> Proc {
         Proc {
+            lock: Mutex::new("proc", ()),
             state: Cell::new(ProcState::UNUSED),
             pid: Cell::new(0),
+            pgid: Cell::new(0),
+            sid: Cell::new(0),
             parent: Cell::new(None),
             killed: AtomicBool::new(false),
             data: RefCell::new(PerProc::new()),
             size: Cell::new(0),
+            vmas: RefCell::new(Vmas::new()),
             files: RefCell::new([None; param::NOFILE]),
             cwd: Cell::new(None),
+            signals: Signals::new(),
+            tty: Cell::new(None),
+            usage: Cell::new(Rusage::new()),
+            child_usage: Cell::new(Rusage::new()),
+            report: Cell::new(None),
+            sched: AtomicSched::new(Sched::new(0)),
+            cpu: Cell::new(0),
         }
     }
 
@@ -184,6 +261,29 @@ impl Proc {
 
     pub fn set_size(&self, size: usize) {
         self.size.set(size);
+        let mut usage = self.usage.get();
+        usage.maxrss = usage.maxrss.max(size as u64 / 1024);
+        self.usage.set(usage);
+    }
+
+    pub fn sched_state(&self) -> Sched {
+        self.sched.load()
+    }
+
+    pub fn usage(&self) -> Rusage {
+        self.usage.get()
+    }
+
+    pub fn child_usage(&self) -> Rusage {
+        self.child_usage.get()
+    }
+
+    // Charges a clock tick to the process, spent in user space or
+    // in the kernel.
+    pub fn tick(&self, user: bool) {
+        let mut usage = self.usage.get();
+        resource::charge_tick(&mut usage, user);
+        self.usage.set(usage);
     }
 
     pub fn kill(&self) {
@@ -201,6 +301,256 @@ impl Proc {
         self.killed.load(Ordering::Relaxed)
     }
 
+    // Whether a sleep should be abandoned: the process has been
+    // killed, or has a signal to act upon.
+    pub fn interrupted(&self) -> bool {
+        self.dead() || self.signals.deliverable()
+    }
+
+    pub fn signals(&self) -> &Signals {
+        &self.signals
+    }
+
+    pub fn vmas(&self) -> &RefCell<Vmas> {
+        &self.vmas
+    }
+
+    pub fn pgid(&self) -> u32 {
+        self.pgid.get()
+    }
+
+    pub fn sid(&self) -> u32 {
+        self.sid.get()
+    }
+
+    pub fn is_session_leader(&self) -> bool {
+        self.sid() == self.pid()
+    }
+
+    pub fn tty(&self) -> Option<usize> {
+        self.tty.get()
+    }
+
+    pub fn set_tty(&self, tty: Option<usize>) {
+        self.tty.set(tty);
+    }
+
+    fn is_init(&self) -> bool {
+        self.as_chan() == init_chan()
+    }
+
+    // Posts `sig` to the process, waking it if it must act on it.
+    // The caller holds TREE.
+    fn signal(&self, sig: u32) {
+        let mut continued = false;
+        self.lock.with_lock(|_| {
+            if sig == SIGCONT && self.state() == ProcState::STOPPED {
+                self.wake();
+                continued = true;
+            }
+            if !self.signals.post(sig, self.is_init()) {
+                return;
+            }
+            if sig == SIGKILL {
+                self.kill();
+            }
+            match self.state() {
+                ProcState::SLEEPING(_) => self.wake(),
+                ProcState::STOPPED if sig == SIGKILL => self.wake(),
+                _ => {}
+            }
+        });
+        if continued {
+            self.report.set(Some(wait::CONTINUED));
+            self.notify_parent();
+        }
+    }
+
+    // Wakes the parent should it be waiting, and sends it SIGCHLD.
+    // The caller holds TREE, but not its own lock.
+    fn notify_parent(&self) {
+        wakeup(self.parent());
+        if let Some(parent) = PROCS.iter().find(|p| p.as_chan() == self.parent()) {
+            parent.signal(SIGCHLD);
+        }
+    }
+
+    // Stops the current process, on signal `sig`, until it is sent
+    // SIGCONT or SIGKILL.
+    pub fn stop(&self, sig: u32) {
+        let tree = TREE.lock();
+        if self.dead() {
+            return;
+        }
+        self.report.set(Some(wait::stopped(sig)));
+        self.notify_parent();
+        self.lock.acquire();
+        drop(tree);
+        self.set_state(ProcState::STOPPED);
+        self.sched();
+        self.lock.release();
+    }
+
+    // Resolves a page fault at user address `va`: the first touch
+    // of a page of the heap or stack, which maps a zeroed page, or
+    // a write to a page shared copy-on-write.  Faults in mappings
+    // are resolved by the mapping (see mman.rs).  Any other fault,
+    // as on the guard page below the stack, is an error.
+    pub fn page_fault(&self, va: usize, write: bool) -> Result<()> {
+        use vm::PageFlags;
+        let vma = self.vmas.borrow().find(va);
+        if let Some(vma) = vma {
+            return vma.fault(self, va, write);
+        }
+        if !self.is_user_addr(va) {
+            return Err(Errno::EFAULT);
+        }
+        self.with_pgtbl(|pgtbl| match pgtbl.entry_for(va) {
+            None if va >= USERSTACK => {
+                pgtbl.map_zeroed(va, PageFlags::USER | PageFlags::WRITE | PageFlags::NX)
+            }
+            None => pgtbl.map_zeroed(va, PageFlags::USER | PageFlags::WRITE),
+            Some(_) if write => pgtbl.copy_on_write(va),
+            Some(_) => Err(Errno::EFAULT),
+        })
+    }
+
+    // Sets the action for `sig`, returning the old one.  Our lock
+    // keeps the actions from changing under `signal`.
+    pub fn set_action(&self, sig: u32, action: &SigAction) -> Result<SigAction> {
+        self.lock
+            .with_lock(|_| self.signals.set_action(sig, action))
+    }
+
+    // Forgets the handlers of the old image on exec.
+    pub fn reset_actions(&self) {
+        self.lock.with_lock(|_| self.signals.exec());
+    }
+
+    // Posts the signal for a fault in user space.
+    pub fn fault(&self, sig: u32) {
+        self.lock.with_lock(|_| self.signals.force(sig));
+    }
+
+    // Sends `sig` as the KILL system call does: to the process
+    // `target` if positive, else to the process group `-target`,
+    // or to our own process group if 0.  A target of -1 sends it,
+    // as POSIX has it, to every process but init and this one.
+    pub fn send(&self, target: i32, sig: u32) -> Result<u32> {
+        if sig != 0 && !signal::valid(sig) {
+            return Err(Errno::EINVAL);
+        }
+        let sent = match target {
+            0 => kill_pgrp(self.pgid(), sig),
+            -1 => kill_all(self, sig),
+            t if t > 0 => kill(t as u32, sig),
+            t => kill_pgrp(t.unsigned_abs(), sig),
+        };
+        sent.ok_or(Errno::ESRCH)
+    }
+
+    // Puts process `pid`, which must be this process or one of its
+    // children in the same session, into process group `pgid`: a
+    // new group if `pgid` is `pid`, else an existing one in the
+    // session.  A pid or pgid of 0 means this process's pid.
+    pub fn setpgid(&self, pid: u32, pgid: u32) -> Result<()> {
+        let pid = if pid == 0 { self.pid() } else { pid };
+        let pgid = if pgid == 0 { pid } else { pgid };
+        let _tree = TREE.lock();
+        let p = PROCS
+            .iter()
+            .find(|p| p.initialized() && p.pid() == pid)
+            .ok_or(Errno::ESRCH)?;
+        if !ptr::eq(p, self) && p.parent() != self.as_chan() {
+            return Err(Errno::ESRCH);
+        }
+        if p.sid() != self.sid() {
+            return Err(Errno::EPERM);
+        }
+        if p.is_session_leader() {
+            return Err(Errno::EPERM);
+        }
+        let exists = || {
+            PROCS
+                .iter()
+                .any(|q| q.initialized() && q.pgid() == pgid && q.sid() == self.sid())
+        };
+        if pgid != pid && !exists() {
+            return Err(Errno::EPERM);
+        }
+        p.pgid.set(pgid);
+        Ok(())
+    }
+
+    pub fn getpgid(&self, pid: u32) -> Result<u32> {
+        if pid == 0 {
+            return Ok(self.pgid());
+        }
+        let _tree = TREE.lock();
+        let p = PROCS
+            .iter()
+            .find(|p| p.initialized() && p.pid() == pid)
+            .ok_or(Errno::ESRCH)?;
+        Ok(p.pgid())
+    }
+
+    // Makes this process the leader of a new session and process
+    // group, without a controlling terminal.  A process group
+    // leader cannot, lest its group span two sessions.
+    pub fn setsid(&self) -> Result<u32> {
+        let _tree = TREE.lock();
+        let pid = self.pid();
+        if PROCS.iter().any(|p| p.initialized() && p.pgid() == pid) {
+            return Err(Errno::EPERM);
+        }
+        self.sid.set(pid);
+        self.pgid.set(pid);
+        self.tty.set(None);
+        Ok(pid)
+    }
+
+    // Whether `p` is among the processes named by `which` and
+    // `who` for SETPRIORITY and GETPRIORITY.
+    fn prio_target(&self, p: &Proc, which: u32, who: u32) -> bool {
+        match which {
+            PRIO_PROCESS => p.pid() == if who == 0 { self.pid() } else { who },
+            PRIO_PGRP => p.pgid() == if who == 0 { self.pgid() } else { who },
+            _ => false,
+        }
+    }
+
+    // Sets the nice value of each of the processes named.
+    pub fn setpriority(&self, which: u32, who: u32, nice: i32) -> Result<()> {
+        let _tree = TREE.lock();
+        let mut found = false;
+        for p in PROCS.iter().filter(|p| p.initialized()) {
+            if self.prio_target(p, which, who) {
+                // The scheduler changes the state under the lock.
+                p.lock.with_lock(|_| {
+                    let mut s = p.sched_state();
+                    s.set_nice(nice);
+                    p.sched.store(s);
+                });
+                found = true;
+            }
+        }
+        if !found {
+            return Err(Errno::ESRCH);
+        }
+        Ok(())
+    }
+
+    // Returns the lowest nice value of the processes named.
+    pub fn getpriority(&self, which: u32, who: u32) -> Result<i32> {
+        let _tree = TREE.lock();
+        PROCS
+            .iter()
+            .filter(|p| p.initialized() && self.prio_target(p, which, who))
+            .map(|p| p.sched_state().nice)
+            .min()
+            .ok_or(Errno::ESRCH)
+    }
+
     pub fn context(&self) -> &arch::Context {
         unsafe {
             self.data
@@ -241,6 +591,14 @@ impl Proc {
         self.data.borrow_mut().mut_ptr_to_context_ptr()
     }
 
+    pub fn name(&self) -> [u8; 16] {
+        self.data.borrow().name
+    }
+
+    pub fn set_name(&self, name: &[u8]) {
+        self.data.borrow_mut().set_name(name);
+    }
+
     pub fn parent(&self) -> usize {
         self.parent.get().unwrap_or(0)
     }
@@ -264,28 +622,39 @@ impl Proc {
     }
 
     pub fn dup_pgtbl(&self) -> Option<vm::PageTable> {
-        self.data.borrow().pgtbl.as_ref()?.dup(self.size())
+        let private = [(0..self.size(), false), (USERSTACK..USEREND, false)];
+        let vmas = self.vmas.borrow();
+        let regions = private.into_iter().chain(vmas.regions());
+        self.data.borrow_mut().pgtbl.as_mut()?.dup(regions)
     }
 
     pub unsafe fn switch_pgtbl(&self, pgtbl: vm::PageTable) -> Option<vm::PageTable> {
+        let previous = self.data.borrow_mut().pgtbl.replace(pgtbl);
         unsafe {
-            pgtbl.switch();
+            self.data.borrow().pgtbl.as_ref().unwrap().switch();
+        }
+        if let Some(previous) = &previous {
+            previous.unload();
         }
-        self.data.borrow_mut().pgtbl.replace(pgtbl)
+        previous
     }
 
-    pub fn fork(&self) -> Option<u32> {
-        alloc(|np| -> Option<()> {
+    pub fn fork(&self) -> Result<u32> {
+        // Our page table is copied before TREE is taken, as making
+        // its pages copy-on-write flushes them from the TLBs of the
+        // other CPUs, which must be free to answer.
+        let pgtbl = self.dup_pgtbl().ok_or(Errno::EAGAIN)?;
+        let pid = alloc(|np| -> Option<()> {
             {
                 let mut pd = np.data.borrow_mut();
-                let pgtbl = self.dup_pgtbl().or_else(|| {
-                    kalloc::free(pd.kstack.take().unwrap());
-                    np.set_state(ProcState::UNUSED);
-                    None
-                })?;
                 pd.pgtbl = Some(pgtbl);
                 pd.set_name(&self.data.borrow().name);
             }
+            np.signals.inherit(&self.signals);
+            np.pgid.set(self.pgid());
+            np.sid.set(self.sid());
+            np.tty.set(self.tty.get());
+            np.sched.store(Sched::new(self.sched_state().nice));
             unsafe {
                 let ctx = self.user_context();
                 let nctx = np.user_context_mut();
@@ -294,6 +663,7 @@ impl Proc {
             }
             np.set_parent(self.as_chan());
             np.set_size(self.size());
+            *np.vmas.borrow_mut() = self.vmas.borrow().dup();
             let mut nfiles = np.files.borrow_mut();
             let files = self.files.borrow();
             for (k, maybe_file) in files.iter().enumerate() {
@@ -301,9 +671,9 @@ impl Proc {
                 nfiles[k] = maybe_file.map(File::dup);
             }
             np.set_cwd(self.cwd().dup());
-            np.set_state(ProcState::RUNNABLE);
             Some(())
-        })
+        });
+        pid.ok_or(Errno::EAGAIN)
     }
 
     pub fn adjsize(&self, delta: isize) -> Result<usize> {
@@ -311,21 +681,25 @@ impl Proc {
         let new_size = old_size.wrapping_add(delta as usize);
         if delta < 0 {
             if new_size > old_size {
-                return Err("grow: underflow");
+                return Err(Errno::ENOMEM);
             }
             self.with_pgtbl(|pgtbl| pgtbl.dealloc_user(old_size, new_size))?;
         } else {
-            if old_size > new_size {
-                return Err("grow: overflow");
+            // Pages are mapped as they are touched, so growing the
+            // heap reserves nothing, and a touch may yet find no
+            // memory.  Growth beyond the free memory fails at once,
+            // though, and the heap may be no larger than memory,
+            // which it could never be backed by.
+            let (npages, nfree) = kalloc::stats();
+            if old_size > new_size
+                || new_size > MMAPBASE
+                || new_size.div_ceil(arch::PAGE_SIZE) > npages
+                || (delta as usize).div_ceil(arch::PAGE_SIZE) > nfree
+            {
+                return Err(Errno::ENOMEM);
             }
-            let perms = vm::PageFlags::USER | vm::PageFlags::WRITE;
-            self.with_pgtbl(|pgtbl| pgtbl.alloc_user(old_size, new_size, perms))?;
         }
         self.set_size(new_size);
-        unsafe {
-            // Flush the TLB.
-            self.data.borrow().pgtbl.as_ref().expect("pgtbl").switch();
-        }
         Ok(old_size)
     }
 
@@ -334,6 +708,9 @@ impl Proc {
     }
 
     fn user_region_end(&self, va: usize) -> Option<usize> {
+        if let Some(vma) = self.vmas.borrow().find(va) {
+            return vma.readable().then_some(vma.end);
+        }
         if self.is_user_addr(va) {
             Some(if va < self.size() {
                 self.size()
@@ -345,11 +722,26 @@ impl Proc {
         }
     }
 
+    // Maps any pages of `len` bytes at `va` not yet touched, and
+    // gives this process its own copy of any shared copy-on-write
+    // if `write`, so the kernel may use them without a fault that
+    // it could not recover from.
+    fn touch(&self, va: usize, len: usize, write: bool) -> Option<()> {
+        for page in (arch::page_round_down(va)..va + len).step_by(arch::PAGE_SIZE) {
+            match self.with_pgtbl(|pgtbl| pgtbl.entry_for(page)) {
+                Some(entry) if !write || entry.is_writable() => {}
+                _ => self.page_fault(page, write).ok()?,
+            }
+        }
+        Some(())
+    }
+
     pub fn fetch_usize(&self, off: usize) -> Option<usize> {
         let rend = self.user_region_end(off)?;
         if size_of::<usize>() > rend - off {
             return None;
         }
+        self.touch(off, size_of::<usize>(), false)?;
         #[allow(clippy::cast_ptr_alignment)]
         let ptr = off as *const usize;
         Some(unsafe { ptr::read_unaligned(ptr) })
@@ -357,9 +749,19 @@ impl Proc {
 
     pub fn fetch_str(&self, off: usize) -> Option<&[u8]> {
         let rend = self.user_region_end(off)?;
-        let mem = unsafe { slice::from_raw_parts(off as *const u8, rend - off) };
-        let pos = mem.iter().position(|b| *b == 0)?;
-        Some(&mem[..pos])
+        // Touch the string's pages one at a time, as it may end well
+        // before the region does.
+        let mut va = off;
+        while va < rend {
+            let next = cmp::min(arch::page_round_down(va) + arch::PAGE_SIZE, rend);
+            self.touch(va, next - va, false)?;
+            let mem = unsafe { slice::from_raw_parts(va as *const u8, next - va) };
+            if let Some(pos) = mem.iter().position(|b| *b == 0) {
+                return Some(unsafe { slice::from_raw_parts(off as *const u8, va + pos - off) });
+            }
+            va = next;
+        }
+        None
     }
 
     pub fn fetch_slice(&self, off: usize, len: usize) -> Option<&[u8]> {
@@ -367,6 +769,7 @@ impl Proc {
         if len > rend - off {
             return None;
         }
+        self.touch(off, len, false)?;
         Some(unsafe { slice::from_raw_parts(off as *const u8, len) })
     }
 
@@ -375,6 +778,7 @@ impl Proc {
         if len > rend - off {
             return None;
         }
+        self.touch(off, len, true)?;
         Some(unsafe { slice::from_raw_parts_mut(off as *mut u8, len) })
     }
 
@@ -383,6 +787,7 @@ impl Proc {
         if (len * size_of::<T>()) > rend - off {
             return None;
         }
+        self.touch(off, len * size_of::<T>(), true)?;
         #[allow(clippy::cast_ptr_alignment)]
         Some(off as *mut T)
     }
@@ -398,47 +803,105 @@ impl Proc {
             file.unwrap().close();
         }
 
+        self.vmas.borrow_mut().clear();
         crate::fslog::with_op(|| self.cwd.take().unwrap().put().expect("iput cwd"));
 
-        let procs = PROCS.lock();
-        wakeup1(&procs[..], self.parent());
-        for p in procs.iter().filter(|&p| p.initialized()) {
+        // A session leader takes its controlling terminal with it.
+        if self.is_session_leader()
+            && let Some(tty) = self.tty()
+        {
+            console::hangup(tty, self.sid());
+        }
+
+        let tree = TREE.lock();
+        self.notify_parent();
+        for p in PROCS.iter().filter(|&p| p.initialized()) {
             if p.parent() == self.as_chan() {
                 p.set_parent(init_chan());
-                if let ProcState::ZOMBIE(_) = p.state() {
-                    wakeup1(&procs[..], init_chan());
+                if p.lock
+                    .with_lock(|_| matches!(p.state(), ProcState::ZOMBIE(_)))
+                {
+                    wakeup(init_chan());
                 }
             }
         }
+        // Our parent may reap us as soon as TREE is released, but
+        // not before our lock is, once we are off this CPU.
+        self.lock.acquire();
         self.set_state(ProcState::ZOMBIE(status));
+        drop(tree);
         self.sched();
         core::unreachable!();
     }
 
-    // Wait for a child process to exit and return its pid.
-    // Return None if this process has no children.
-    pub fn wait(&self, status_ptr: usize) -> Option<u32> {
-        let statusp = self.fetch_ptr_mut::<i32>(status_ptr, 1)?;
-        let (pid, status, zkstack, zpgtbl) = self.wait1()?;
-        kalloc::free(zkstack); // XXX plock held?
-        drop(zpgtbl); // XXX plock held?
-        if statusp.is_null() {
+    // Waits for a child process to exit and returns its pid, as
+    // the WAIT4 system call does.  `pid` selects the children: a
+    // pid if positive, any if -1, else the process group `-pid`,
+    // or our own group if 0.  With WNOHANG, returns 0 rather than
+    // sleep; WUNTRACED and WCONTINUED also report children that
+    // stopped or continued.  The wait status and the resources the
+    // child used are stored at `status_ptr` and `rusage_ptr` unless
+    // they are null.
+    pub fn wait(
+        &self,
+        pid: i32,
+        status_ptr: usize,
+        options: u32,
+        rusage_ptr: usize,
+    ) -> Result<u32> {
+        // Null pointers ask for nothing to be stored.
+        let statusp = match status_ptr {
+            0 => null_mut(),
+            p => self.fetch_ptr_mut::<i32>(p, 1).ok_or(Errno::EFAULT)?,
+        };
+        let rusagep = match rusage_ptr {
+            0 => null_mut(),
+            p => self.fetch_ptr_mut::<Rusage>(p, 1).ok_or(Errno::EFAULT)?,
+        };
+        let Some(waited) = self.wait1(pid, options)? else {
+            return Ok(0);
+        };
+        if let Some((zkstack, zpgtbl)) = waited.remains {
+            kalloc::free(zkstack); // XXX plock held?
+            drop(zpgtbl); // XXX plock held?
+        }
+        if !statusp.is_null() {
+            unsafe {
+                ptr::write_unaligned(statusp, waited.status);
+            }
+        }
+        if !rusagep.is_null() {
             unsafe {
-                ptr::write(statusp, status);
+                ptr::write_unaligned(rusagep, waited.usage);
             }
         }
-        Some(pid)
+        Ok(waited.pid)
+    }
+
+    // Whether `p` is one of our children selected by `pid`, as for
+    // `wait`.
+    fn waits_for(&self, p: &Proc, pid: i32) -> bool {
+        p.parent() == self.as_chan()
+            && match pid {
+                -1 => true,
+                0 => p.pgid() == self.pgid(),
+                pid if pid < 0 => p.pgid() == pid.unsigned_abs(),
+                pid => p.pid() == pid as u32,
+            }
     }
 
-    fn wait1(&self) -> Option<(u32, i32, &mut arch::Page, vm::PageTable)> {
-        let procs = PROCS.lock();
+    fn wait1(&self, pid: i32, options: u32) -> Result<Option<Waited>> {
+        let _tree = TREE.lock();
         loop {
             let mut have_kids = false;
-            for p in procs.iter().filter(|&p| p.initialized()) {
-                if p.parent() != self.as_chan() {
+            for p in PROCS.iter().filter(|&p| p.initialized()) {
+                if !self.waits_for(p, pid) {
                     continue;
                 }
                 have_kids = true;
+                // A zombie's lock is held until it is off its CPU,
+                // after which its stack may be freed.
+                p.lock.acquire();
                 if let ProcState::ZOMBIE(status) = p.state() {
                     let zkstack;
                     let zpgtbl;
@@ -448,40 +911,84 @@ impl Proc {
                         zpgtbl = pd.pgtbl.take().expect("stranded zombie");
                         pd.name = [0; 16];
                     }
+                    let mut usage = p.usage.take();
+                    usage.add(&p.child_usage.take());
+                    let mut child_usage = self.child_usage.get();
+                    child_usage.add(&usage);
+                    self.child_usage.set(child_usage);
                     let pid = p.pid.take();
                     p.parent.set(None);
+                    p.signals.clear();
+                    p.pgid.set(0);
+                    p.sid.set(0);
+                    p.tty.set(None);
+                    p.report.set(None);
+                    p.sched.store(Sched::new(0));
                     p.resurrect();
                     p.set_size(0);
                     p.set_state(ProcState::UNUSED);
-                    return Some((pid, status, zkstack, zpgtbl));
+                    p.lock.release();
+                    return Ok(Some(Waited {
+                        pid,
+                        status,
+                        usage,
+                        remains: Some((zkstack, zpgtbl)),
+                    }));
+                }
+                p.lock.release();
+                let wanted = match p.report.get() {
+                    Some(wait::CONTINUED) => options & WCONTINUED != 0,
+                    Some(_) => options & WUNTRACED != 0,
+                    None => false,
+                };
+                if wanted && let Some(status) = p.report.take() {
+                    let mut usage = p.usage.get();
+                    usage.add(&p.child_usage.get());
+                    return Ok(Some(Waited {
+                        pid: p.pid(),
+                        status,
+                        usage,
+                        remains: None,
+                    }));
                 }
             }
-            if !have_kids || self.dead() {
-                return None;
+            if !have_kids {
+                return Err(Errno::ECHILD);
             }
-            self.sleep(self.as_chan(), &PROCS);
+            if options & WNOHANG != 0 {
+                return Ok(None);
+            }
+            if self.interrupted() {
+                return Err(Errno::EINTR);
+            }
+            self.sleep(self.as_chan(), &TREE);
         }
     }
 
+    // Sleeps on `chan`, releasing `lock`, which the caller holds,
+    // until woken.  Holding our own lock from before `lock` is
+    // released until we are off the CPU means no wakeup is missed.
     pub fn sleep<T>(&self, chan: usize, lock: &Mutex<T>) {
-        let lock_procs = !ptr::eq(lock, &PROCS as *const _ as *const Mutex<T>);
-        if lock_procs {
-            PROCS.acquire();
-            lock.release();
-        }
+        self.lock.acquire();
+        lock.release();
         self.set_state(ProcState::SLEEPING(chan));
         self.sched();
-        if lock_procs {
-            PROCS.release();
-            lock.acquire();
-        }
+        self.lock.release();
+        lock.acquire();
     }
 
+    // Switches to the scheduler.  The caller holds our lock, and
+    // has changed our state from RUNNING.
     pub fn sched(&self) {
-        assert!(PROCS.holding(), "sched proc lock");
+        assert!(self.lock.holding(), "sched proc lock");
         assert_eq!(arch::mycpu().nintr_disable(), 1, "sched locks");
         assert_ne!(self.state(), ProcState::RUNNING, "sched running");
         assert!(!arch::is_intr_enabled(), "sched interruptible");
+        // A process that is still runnable was preempted; any
+        // other gave up the processor of its own accord.
+        let mut usage = self.usage.get();
+        resource::charge_switch(&mut usage, self.state() == ProcState::RUNNABLE);
+        self.usage.set(usage);
         let intr_status = arch::mycpu().saved_intr_status();
         unsafe {
             swtch(self.mut_ptr_to_context_ptr(), arch::mycpu().scheduler());
@@ -489,9 +996,40 @@ impl Proc {
         arch::mycpu_mut().reset_saved_intr_status(intr_status);
     }
 
-    pub fn sched_yield(&self) {
+    // Makes the process runnable on a CPU chosen for it.  The
+    // caller holds its lock.
+    fn wake(&self) {
+        let here = arch::mycpu_id() as usize;
+        let last = self.cpu.get();
+        let load = |cpu: usize| LOAD[cpu].load(Ordering::Relaxed);
+        // Return to the CPU the process last ran on, whose caches
+        // may still hold its memory, unless it is busier than this.
+        let cpu = if load(last) > load(here) { here } else { last };
+        self.enqueue(cpu);
+        kick(cpu);
+    }
+
+    // Makes a new process runnable on the least loaded CPU.
+    fn start(&self) {
+        let online = ONLINE.load(Ordering::Relaxed).max(1);
+        let here = arch::mycpu_id() as usize;
+        let cpu = (0..online)
+            .min_by_key(|&cpu| (LOAD[cpu].load(Ordering::Relaxed), cpu != here))
+            .unwrap_or(here);
+        self.lock.with_lock(|_| self.enqueue(cpu));
+        kick(cpu);
+    }
+
+    fn enqueue(&self, cpu: usize) {
         self.set_state(ProcState::RUNNABLE);
-        self.sched();
+        self.cpu.set(cpu);
+        RUNQ[cpu].lock().push(self.slot());
+        LOAD[cpu].fetch_add(1, Ordering::SeqCst);
+    }
+
+    // Our index in the process table.
+    fn slot(&self) -> usize {
+        (self.as_chan() - PROCS.as_ptr().addr()) / size_of::<Proc>()
     }
 
     pub fn get_fd(&self, fd: usize) -> Option<&file::File> {
@@ -520,21 +1058,61 @@ impl Proc {
     }
 }
 
-pub fn yield_if_running() {
+// A child found by `Proc::wait1`: its pid, wait status and usage,
+// and, if it was reaped, the kernel stack and page table left to
+// free.
+struct Waited {
+    pid: u32,
+    status: i32,
+    usage: Rusage,
+    remains: Option<(&'static mut arch::Page, vm::PageTable)>,
+}
+
+// Charges a clock tick to the running process, which gives up the
+// processor if it has used its time slice, or if the policy would
+// rather run a process waiting for it.
+pub fn preempt() {
     if let Some(proc) = try_myproc() {
-        PROCS.with_lock(|_| {
-            if proc.state() == ProcState::RUNNING {
-                proc.sched_yield();
+        proc.lock.with_lock(|_| {
+            if proc.state() != ProcState::RUNNING {
+                return;
+            }
+            let cpu = arch::mycpu_id() as usize;
+            let runq = RUNQ[cpu].lock();
+            let mut s = proc.sched_state();
+            let expired = runq.policy().tick(&mut s);
+            proc.sched.store(s);
+            let waiting = runq.preempts(&s, |k| PROCS[k].sched_state());
+            drop(runq);
+            if expired || waiting {
+                proc.enqueue(cpu);
+                proc.sched();
             }
         });
     }
 }
 
+// Lets the policy age every process, if it is time; called on
+// each clock tick by the boot CPU.
+pub fn age(now: u64) {
+    let interval = <sched::Active as Policy>::BOOST_INTERVAL;
+    if interval == 0 || !now.is_multiple_of(interval) {
+        return;
+    }
+    for p in PROCS.iter() {
+        p.lock.with_lock(|_| {
+            let mut s = p.sched_state();
+            sched::Active::boost(&mut s);
+            p.sched.store(s);
+        });
+    }
+}
+
 pub fn die_if_dead() {
     if let Some(proc) = try_myproc()
         && proc.dead()
     {
-        proc.exit(1);
+        proc.exit(wait::signaled(SIGKILL));
     }
 }
 
@@ -542,11 +1120,71 @@ unsafe extern "C" {
     fn swtch(from: *mut *mut arch::Context, to: &arch::Context);
 }
 
+// Takes the next process to run from a CPU's queue.
+fn dequeue(cpu: usize) -> Option<&'static Proc> {
+    let k = RUNQ[cpu].lock().pop(|k| PROCS[k].sched_state())?;
+    LOAD[cpu].fetch_sub(1, Ordering::Relaxed);
+    Some(&PROCS[k])
+}
+
+// Takes a process from the busiest other CPU, if any has one
+// waiting.
+fn steal(cpu: usize) -> Option<&'static Proc> {
+    let online = ONLINE.load(Ordering::Relaxed);
+    let busiest = (0..online)
+        .filter(|&other| other != cpu)
+        .max_by_key(|&other| LOAD[other].load(Ordering::Relaxed))?;
+    if LOAD[busiest].load(Ordering::Relaxed) == 0 {
+        return None;
+    }
+    dequeue(busiest)
+}
+
+// Kicks `cpu`, which a process was just queued on, if it is idle;
+// else some idle CPU, which may steal it.
+fn kick(cpu: usize) {
+    let here = arch::mycpu_id() as usize;
+    let idle = |cpu: usize| IDLE[cpu].load(Ordering::SeqCst);
+    let online = ONLINE.load(Ordering::Relaxed);
+    let target = if idle(cpu) {
+        Some(cpu)
+    } else {
+        (0..online).find(|&other| idle(other))
+    };
+    if let Some(target) = target.filter(|&target| target != here) {
+        ipi::resched(target);
+    }
+}
+
+// Halts this CPU until an interrupt, unless some process was
+// queued since we looked.  Interrupts are off from setting IDLE
+// until we halt, so that the kick for a process queued meanwhile
+// wakes us rather than arriving too early.
+fn idle(cpu: usize) {
+    unsafe { arch::intr_disable() };
+    IDLE[cpu].store(true, Ordering::SeqCst);
+    let online = ONLINE.load(Ordering::Relaxed);
+    if (0..online).all(|cpu| LOAD[cpu].load(Ordering::SeqCst) == 0) {
+        trap::idle();
+    }
+    IDLE[cpu].store(false, Ordering::SeqCst);
+}
+
+// Whether `cpu` has started running the scheduler.
+pub fn online(cpu: usize) -> bool {
+    UP[cpu].load(Ordering::Acquire)
+}
+
 pub fn scheduler() {
+    let cpu = arch::mycpu_id() as usize;
+    ONLINE.fetch_add(1, Ordering::Relaxed);
+    UP[cpu].store(true, Ordering::Release);
     loop {
         unsafe { arch::intr_enable() };
-        let procs = PROCS.lock();
-        for p in procs.iter().filter(|p| p.state() == ProcState::RUNNABLE) {
+        if let Some(p) = dequeue(cpu).or_else(|| steal(cpu)) {
+            p.lock.acquire();
+            assert_eq!(p.state(), ProcState::RUNNABLE, "scheduler");
+            p.cpu.set(cpu);
             p.set_state(ProcState::RUNNING);
             arch::mycpu_mut().set_proc(p);
             unsafe {
@@ -554,9 +1192,12 @@ pub fn scheduler() {
                 swtch(arch::mycpu_mut().mut_ptr_to_scheduler_ptr(), p.context());
                 crate::kpgtbl().switch();
             }
+            p.data.borrow().pgtbl.as_ref().unwrap().unload();
             arch::mycpu_mut().clear_proc();
+            p.lock.release();
+            continue;
         }
-        arch::cpu_relax();
+        idle(cpu);
     }
 }
 
@@ -571,16 +1212,17 @@ pub fn myproc() -> &'static Proc {
 }
 
 extern "C" fn forkret() -> u32 {
-    PROCS.release();
+    myproc().lock.release();
     0
 }
 
 extern "C" fn firstret() -> u32 {
     use crate::fslog;
-    PROCS.release();
+    myproc().lock.release();
     unsafe {
-        fs::init(param::ROOTDEV);
-        fslog::init(param::ROOTDEV, fs::superblock());
+        let rootdev = cmdline::root_dev();
+        fs::init(rootdev);
+        fslog::init(rootdev, fs::superblock());
         myproc().set_cwd(fs::namei(b"/").expect("root filesystem exists"));
     }
     0
@@ -613,48 +1255,102 @@ where
         pid
     }
     let stack = kalloc::alloc()?;
-    let procs = PROCS.lock();
-    let Some(p) = procs.iter().find(|&p| p.state() == ProcState::UNUSED) else {
+    let _tree = TREE.lock();
+    let Some(p) = PROCS.iter().find(|&p| p.state() == ProcState::UNUSED) else {
         kalloc::free(stack);
         return None;
     };
     let pid = init_proc(p, stack);
     thunk(p)?;
+    p.start();
     Some(pid)
 }
 
+// Wakes every process sleeping on `channel`.  The caller may
+// hold any lock but a process lock.
 pub fn wakeup(channel: usize) {
-    let procs = PROCS.lock();
-    wakeup1(&procs[..], channel);
+    let me = try_myproc().map(Proc::as_chan);
+    for p in PROCS.iter().filter(|p| Some(p.as_chan()) != me) {
+        p.lock.with_lock(|_| {
+            if p.state() == ProcState::SLEEPING(channel) {
+                p.wake();
+            }
+        });
+    }
 }
 
-pub fn wakeup1(procs: &[Proc], channel: usize) {
-    procs
-        .iter()
-        .filter(|p| p.state() == ProcState::SLEEPING(channel))
-        .for_each(|p| p.set_state(ProcState::RUNNABLE));
-}
-
-// Kill the process with the given pid.
-// Process won't exit until it returns
-// to user space (see trap in trap.c).
-pub fn kill(pid: u32) -> Option<u32> {
-    let procs = PROCS.lock();
-    for p in procs.iter() {
-        if p.pid() == pid {
-            p.kill();
-            if let ProcState::SLEEPING(_) = p.state() {
-                p.set_state(ProcState::RUNNABLE);
-            }
-            return Some(pid);
+// Send signal `sig` to the process with the given pid; signal 0
+// only checks that it exists.  The process won't act on it
+// until it returns to user space (see signal::deliver).
+pub fn kill(pid: u32, sig: u32) -> Option<u32> {
+    if sig != 0 && !signal::valid(sig) {
+        return None;
+    }
+    let _tree = TREE.lock();
+    let p = PROCS.iter().find(|p| p.initialized() && p.pid() == pid)?;
+    if sig != 0 {
+        p.signal(sig);
+    }
+    Some(pid)
+}
+
+// Send `sig` to each process in group `pgid`, returning the
+// pgid if there are any.
+pub fn kill_pgrp(pgid: u32, sig: u32) -> Option<u32> {
+    if sig != 0 && !signal::valid(sig) {
+        return None;
+    }
+    let _tree = TREE.lock();
+    let mut found = false;
+    for p in PROCS.iter().filter(|p| p.initialized() && p.pgid() == pgid) {
+        found = true;
+        if sig != 0 {
+            p.signal(sig);
+        }
+    }
+    found.then_some(pgid)
+}
+
+// Send `sig` to every process but init and `sender`, returning
+// how many there were, if any.
+fn kill_all(sender: &Proc, sig: u32) -> Option<u32> {
+    let _tree = TREE.lock();
+    let mut n = 0;
+    for p in PROCS.iter().filter(|p| p.initialized()) {
+        if p.is_init() || ptr::eq(p, sender) {
+            continue;
+        }
+        n += 1;
+        if sig != 0 {
+            p.signal(sig);
         }
     }
-    None
+    (n > 0).then_some(n)
+}
+
+// Whether `pgid` names a process group in session `sid`.
+pub fn pgrp_in_session(pgid: u32, sid: u32) -> bool {
+    let _tree = TREE.lock();
+    PROCS
+        .iter()
+        .any(|p| p.initialized() && p.pgid() == pgid && p.sid() == sid)
+}
+
+// Calls `f` with each process in use and the pid of its parent.
+pub fn for_each<F: FnMut(&Proc, u32)>(mut f: F) {
+    let _tree = TREE.lock();
+    for p in PROCS.iter().filter(|p| p.state() != ProcState::UNUSED) {
+        let ppid = PROCS
+            .iter()
+            .find(|q| q.as_chan() == p.parent())
+            .map_or(0, |q| q.pid());
+        f(p, ppid);
+    }
 }
 
 pub fn dump() {
-    let ps = PROCS.lock();
-    for p in &ps[..] {
+    let _tree = TREE.lock();
+    for p in &PROCS {
         if let ProcState::UNUSED = p.state() {
             continue;
         }
@@ -0,0 +1,87 @@
+// The times and getrusage system calls, which report the clock
+// ticks and other resources used by a process and its children.
+
+use crate::Result;
+use crate::proc::Proc;
+use crate::trap;
+use core::ptr;
+use syslib::errno::Errno;
+use syslib::resource::{RUSAGE_CHILDREN, RUSAGE_SELF, Rusage, Tms};
+
+fn tms(usage: &Rusage, child_usage: &Rusage) -> Tms {
+    Tms {
+        utime: usage.utime,
+        stime: usage.stime,
+        cutime: child_usage.utime,
+        cstime: child_usage.stime,
+    }
+}
+
+// Charges a clock tick, spent in user space or in the kernel.
+pub fn charge_tick(usage: &mut Rusage, user: bool) {
+    if user {
+        usage.utime += 1;
+    } else {
+        usage.stime += 1;
+    }
+}
+
+// Counts a switch away from a process: involuntary if it was
+// preempted, and voluntary if it gave up the processor of its own
+// accord.
+pub fn charge_switch(usage: &mut Rusage, preempted: bool) {
+    if preempted {
+        usage.nivcsw += 1;
+    } else {
+        usage.nvcsw += 1;
+    }
+}
+
+// Stores our times and those of our children at `addr`, unless
+// it is null, and returns the ticks since boot.
+pub fn times(proc: &Proc, addr: usize) -> Result<u64> {
+    let out = match addr {
+        0 => ptr::null_mut(),
+        p => proc.fetch_ptr_mut::<Tms>(p, 1).ok_or(Errno::EFAULT)?,
+    };
+    if !out.is_null() {
+        let t = tms(&proc.usage(), &proc.child_usage());
+        unsafe { ptr::write_unaligned(out, t) };
+    }
+    Ok(trap::ticks())
+}
+
+pub fn getrusage(proc: &Proc, who: i32, addr: usize) -> Result<()> {
+    let usage = match who {
+        RUSAGE_SELF => proc.usage(),
+        RUSAGE_CHILDREN => proc.child_usage(),
+        _ => return Err(Errno::EINVAL),
+    };
+    let out = proc.fetch_ptr_mut::<Rusage>(addr, 1).ok_or(Errno::EFAULT)?;
+    unsafe { ptr::write_unaligned(out, usage) };
+    Ok(())
+}
+
+#[cfg(test)]
+mod resource_tests {
+    use super::{charge_switch, charge_tick, tms};
+    use syslib::resource::Rusage;
+
+    #[test]
+    fn charges_ticks_and_switches() {
+        let mut own = Rusage::new();
+        for user in [true, true, false, true] {
+            charge_tick(&mut own, user);
+        }
+        charge_switch(&mut own, true);
+        charge_switch(&mut own, false);
+        charge_switch(&mut own, false);
+        assert_eq!((own.utime, own.stime), (3, 1));
+        assert_eq!((own.nvcsw, own.nivcsw), (2, 1));
+        let mut children = Rusage::new();
+        children.add(&own);
+        children.add(&own);
+        let t = tms(&own, &children);
+        assert_eq!((t.utime, t.stime, t.cutime, t.cstime), (3, 1, 6, 2));
+    }
+}
@@ -0,0 +1,351 @@
+// Scheduling policy.  Each CPU keeps a `RunQueue` of the processes
+// waiting to run on it, and the scheduler loop in proc.rs asks the
+// queue's `Policy` which to run next, charging it each clock tick
+// it runs and preempting it when the policy says so.  The kernel
+// uses a multilevel feedback queue; round-robin, as xv6 has, stands
+// in for it in tests.
+//
+// The multilevel feedback queue runs processes by level, highest
+// (0) first, and round-robin within a level.  A process starts at
+// the top level that its nice value allows; once it has used a
+// level's time slice, it drops a level, and the slices grow longer
+// as it falls.  Since sleeping does not reset the time used, a
+// process that keeps the processor busy sinks below those that
+// mostly wait, such as the shell.  Every so often, all processes
+// are boosted back to their top level, so none starve.
+
+use crate::param::NPROC;
+use core::sync::atomic::{AtomicU64, Ordering};
+use syslib::resource::{NICE_MAX, NICE_MIN};
+
+// The number of levels, and the clock ticks in the slice at a
+// process's top level, which doubles at each level below.
+pub const NLEVEL: u32 = 8;
+const QUANTUM: u32 = 10;
+const MAX_DOUBLINGS: u32 = 4;
+
+// Scheduling state kept for each process.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sched {
+    pub nice: i32,
+    pub level: u32,
+    // Clock ticks used of the slice at this level.
+    pub used: u32,
+}
+
+impl Sched {
+    pub const fn new(nice: i32) -> Sched {
+        Sched {
+            nice,
+            level: top_level(nice),
+            used: 0,
+        }
+    }
+
+    // Sets the nice value, restarting at its top level.
+    pub fn set_nice(&mut self, nice: i32) {
+        *self = Sched::new(nice.clamp(NICE_MIN, NICE_MAX));
+    }
+}
+
+// A process's `Sched`, packed into a word so that the run queues,
+// which order processes by it, may read it without the process's
+// lock.  It is changed only under that lock.
+pub struct AtomicSched(AtomicU64);
+
+impl AtomicSched {
+    pub const fn new(s: Sched) -> AtomicSched {
+        AtomicSched(AtomicU64::new(AtomicSched::pack(s)))
+    }
+
+    // The nice value fits a byte, and the level a few bits.
+    const fn pack(s: Sched) -> u64 {
+        ((s.nice as u8 as u64) << 40) | ((s.level as u64) << 32) | s.used as u64
+    }
+
+    pub fn load(&self) -> Sched {
+        let w = self.0.load(Ordering::Relaxed);
+        Sched {
+            nice: i32::from((w >> 40) as u8 as i8),
+            level: (w >> 32) as u8 as u32,
+            used: w as u32,
+        }
+    }
+
+    pub fn store(&self, s: Sched) {
+        self.0.store(AtomicSched::pack(s), Ordering::Relaxed);
+    }
+}
+
+// The highest level a process may reach: nice 0 starts half way
+// down, and -20 at the top.
+const fn top_level(nice: i32) -> u32 {
+    (nice - NICE_MIN) as u32 * NLEVEL / (NICE_MAX - NICE_MIN + 1) as u32
+}
+
+pub trait Policy {
+    // Clock ticks between calls to `boost`, if it does anything.
+    const BOOST_INTERVAL: u64;
+
+    // Chooses the process to run next among the runnable ones,
+    // given by their slot in the process table and their state.
+    fn pick<I: Iterator<Item = (usize, Sched)>>(&mut self, runnable: I) -> Option<usize>;
+
+    // Charges a clock tick to a running process, returning whether
+    // it should now give up the processor.
+    fn tick(&self, s: &mut Sched) -> bool;
+
+    // Whether a runnable process should take the processor from a
+    // running one before its slice is up.
+    fn preempts(&self, waiting: &Sched, running: &Sched) -> bool;
+
+    // Adjusts a process's state every `BOOST_INTERVAL` ticks.
+    fn boost(s: &mut Sched);
+}
+
+// Whether slot `k` is at or before the one last picked, and so
+// comes late in a scan that starts just past it.
+fn wraps(k: usize, last: usize) -> bool {
+    k <= last
+}
+
+pub struct Mlfq {
+    last: usize,
+}
+
+impl Mlfq {
+    pub const fn new() -> Mlfq {
+        Mlfq { last: usize::MAX }
+    }
+
+    fn quantum(s: &Sched) -> u32 {
+        QUANTUM << (s.level - top_level(s.nice)).min(MAX_DOUBLINGS)
+    }
+}
+
+impl Policy for Mlfq {
+    const BOOST_INTERVAL: u64 = 1000;
+
+    fn pick<I: Iterator<Item = (usize, Sched)>>(&mut self, runnable: I) -> Option<usize> {
+        let last = self.last;
+        let (k, _) = runnable.min_by_key(|&(k, s)| (s.level, wraps(k, last), k))?;
+        self.last = k;
+        Some(k)
+    }
+
+    fn tick(&self, s: &mut Sched) -> bool {
+        s.used += 1;
+        if s.used < Mlfq::quantum(s) {
+            return false;
+        }
+        s.used = 0;
+        s.level = (s.level + 1).min(NLEVEL - 1);
+        true
+    }
+
+    fn preempts(&self, waiting: &Sched, running: &Sched) -> bool {
+        waiting.level < running.level
+    }
+
+    fn boost(s: &mut Sched) {
+        s.level = top_level(s.nice);
+        s.used = 0;
+    }
+}
+
+#[allow(dead_code)]
+pub struct RoundRobin {
+    last: usize,
+}
+
+#[allow(dead_code)]
+impl RoundRobin {
+    pub const fn new() -> RoundRobin {
+        RoundRobin { last: usize::MAX }
+    }
+}
+
+impl Policy for RoundRobin {
+    const BOOST_INTERVAL: u64 = 0;
+
+    fn pick<I: Iterator<Item = (usize, Sched)>>(&mut self, runnable: I) -> Option<usize> {
+        let last = self.last;
+        let (k, _) = runnable.min_by_key(|&(k, _)| (wraps(k, last), k))?;
+        self.last = k;
+        Some(k)
+    }
+
+    fn tick(&self, _s: &mut Sched) -> bool {
+        true
+    }
+
+    fn preempts(&self, _waiting: &Sched, _running: &Sched) -> bool {
+        false
+    }
+
+    fn boost(_s: &mut Sched) {}
+}
+
+// The policy the kernel runs.
+pub type Active = Mlfq;
+
+const NWORDS: usize = NPROC.div_ceil(64);
+
+// A set of processes, by slot in the process table, waiting to run
+// on one CPU, with the policy that orders them.
+pub struct RunQueue<P: Policy = Active> {
+    members: [u64; NWORDS],
+    len: usize,
+    policy: P,
+}
+
+impl<P: Policy> RunQueue<P> {
+    pub const fn new(policy: P) -> RunQueue<P> {
+        RunQueue {
+            members: [0; NWORDS],
+            len: 0,
+            policy,
+        }
+    }
+
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    pub fn push(&mut self, k: usize) {
+        let bit = 1 << (k % 64);
+        assert_eq!(self.members[k / 64] & bit, 0, "process queued twice");
+        self.members[k / 64] |= bit;
+        self.len += 1;
+    }
+
+    // Removes and returns the process that the policy would run
+    // next, given the scheduling state of each by `sched`.
+    pub fn pop<F: Fn(usize) -> Sched>(&mut self, sched: F) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+        let waiting = members(&self.members).map(|k| (k, sched(k)));
+        let k = self.policy.pick(waiting)?;
+        self.members[k / 64] &= !(1 << (k % 64));
+        self.len -= 1;
+        Some(k)
+    }
+
+    // Whether one of the processes waiting should take over from
+    // a running one with scheduling state `running`.
+    pub fn preempts<F: Fn(usize) -> Sched>(&self, running: &Sched, sched: F) -> bool {
+        members(&self.members).any(|k| self.policy.preempts(&sched(k), running))
+    }
+}
+
+fn members(set: &[u64; NWORDS]) -> impl Iterator<Item = usize> + '_ {
+    (0..NPROC).filter(|&k| set[k / 64] & (1 << (k % 64)) != 0)
+}
+
+#[cfg(test)]
+mod sched_tests {
+    use super::*;
+
+    fn run<P: Policy>(p: &mut P, procs: &mut [Sched], ticks: usize) -> Vec<usize> {
+        let mut ran = Vec::new();
+        for _ in 0..ticks {
+            let k = p.pick(procs.iter().copied().enumerate()).unwrap();
+            ran.push(k);
+            while !p.tick(&mut procs[k]) {}
+        }
+        ran
+    }
+
+    #[test]
+    fn packs_state() {
+        let a = AtomicSched::new(Sched::new(NICE_MIN));
+        assert_eq!(a.load(), Sched::new(NICE_MIN));
+        let s = Sched {
+            nice: NICE_MAX,
+            level: NLEVEL - 1,
+            used: u32::MAX,
+        };
+        a.store(s);
+        assert_eq!(a.load(), s);
+    }
+
+    #[test]
+    fn round_robin_takes_turns() {
+        let mut procs = [Sched::new(0); 3];
+        let ran = run(&mut RoundRobin::new(), &mut procs, 7);
+        assert_eq!(ran, [0, 1, 2, 0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn hogs_sink() {
+        let mut mlfq = Mlfq::new();
+        let mut hog = Sched::new(0);
+        let top = hog.level;
+        let mut ticks = 0;
+        while !mlfq.tick(&mut hog) {
+            ticks += 1;
+        }
+        assert_eq!(ticks + 1, QUANTUM);
+        assert_eq!(hog.level, top + 1);
+        assert!(!mlfq.tick(&mut hog));
+        assert_eq!(hog.used, 1);
+        // A fresh process at the top runs ahead of the hog.
+        let procs = [hog, Sched::new(0)];
+        assert_eq!(mlfq.pick(procs.iter().copied().enumerate()), Some(1));
+        assert!(mlfq.preempts(&procs[1], &hog));
+        assert!(!mlfq.preempts(&hog, &procs[1]));
+        Mlfq::boost(&mut hog);
+        assert_eq!(hog, Sched::new(0));
+    }
+
+    #[test]
+    fn equals_take_turns() {
+        let mut procs = [Sched::new(0); 3];
+        let ran = run(&mut Mlfq::new(), &mut procs, 6);
+        assert_eq!(ran, [0, 1, 2, 0, 1, 2]);
+        assert!(procs.iter().all(|s| s.level == top_level(0) + 2));
+    }
+
+    #[test]
+    fn nice_sets_top_level() {
+        assert_eq!(top_level(NICE_MIN), 0);
+        assert_eq!(top_level(0), NLEVEL / 2);
+        assert_eq!(top_level(NICE_MAX), NLEVEL - 1);
+        let mut s = Sched::new(0);
+        s.set_nice(-100);
+        assert_eq!(s, Sched::new(NICE_MIN));
+        let mut mlfq = Mlfq::new();
+        let procs = [Sched::new(5), Sched::new(-5)];
+        assert_eq!(mlfq.pick(procs.iter().copied().enumerate()), Some(1));
+    }
+
+    #[test]
+    fn run_queue_orders_by_policy() {
+        let procs = [Sched::new(0), Sched::new(-10), Sched::new(0), Sched::new(0)];
+        let sched = |k: usize| procs[k];
+        let mut q = RunQueue::new(Mlfq::new());
+        assert_eq!(q.pop(sched), None);
+        for k in [3, 0, 1] {
+            q.push(k);
+        }
+        assert!(q.preempts(&Sched::new(0), sched));
+        assert_eq!(q.pop(sched), Some(1));
+        assert!(!q.preempts(&Sched::new(0), sched));
+        assert_eq!(q.pop(sched), Some(3));
+        q.push(2);
+        assert_eq!(q.pop(sched), Some(0));
+        assert_eq!(q.pop(sched), Some(2));
+        assert_eq!(q.pop(sched), None);
+
+        let mut q = RunQueue::new(RoundRobin::new());
+        for k in [200, 7, 64] {
+            q.push(k);
+        }
+        assert_eq!(q.pop(|_| Sched::new(0)), Some(7));
+        q.push(7);
+        assert_eq!(q.pop(|_| Sched::new(0)), Some(64));
+        assert_eq!(q.pop(|_| Sched::new(0)), Some(200));
+        assert_eq!(q.pop(|_| Sched::new(0)), Some(7));
+    }
+}
@@ -540,6 +540,14 @@ pub const INTR_SD0: u32 = 14;
 
 static DRIVE: Mutex<Option<&'static mut Drive>> = Mutex::new("drive", None);
 
+// Returns the model, serial number and size in sectors of the
+// drive, if there is one.  The strings are padded with spaces.
+pub fn identity() -> Option<([u8; 40], [u8; 20], u64)> {
+    let drive = DRIVE.lock();
+    let drive = drive.as_ref()?;
+    Some((drive.model, drive.serial, drive.sectors))
+}
+
 pub unsafe fn init(mut conf: pci::Conf, abar: u64) {
     pci::setup_msi(&mut conf, 0, INTR_SD0);
     // unsafe {
@@ -0,0 +1,425 @@
+// Signals.
+//
+// Each process has a set of pending signals, a set of blocked
+// signals and an action for each signal.  A signal is posted by
+// `proc::kill`, and acted upon when the process next returns to
+// user space, from `trap::trap` or `syscall::syscall`: either its
+// default action is taken, or its handler is called on a signal
+// frame pushed onto the user stack.  The handler returns to a
+// restorer that makes the SIGRETURN system call, which restores
+// the state saved in the frame.
+
+use crate::Result;
+use crate::arch;
+use crate::param::USEREND;
+use crate::proc::Proc;
+use core::cell::Cell;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicU64, Ordering};
+use syslib::errno::Errno;
+use syslib::signal::{
+    NSIG, SA_NODEFER, SA_RESETHAND, SIG_BLOCK, SIG_DFL, SIG_IGN, SIG_SETMASK, SIG_UNBLOCK, SIGCONT,
+    SIGKILL, SIGSEGV, SIGSTOP, SIGTSTP, SIGTTIN, SIGTTOU, SigAction, SigSet, sigbit,
+};
+use syslib::wait;
+
+// What happens to a signal whose action is SIG_DFL.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Disposition {
+    Terminate,
+    Ignore,
+    Stop,
+    Continue,
+}
+
+fn default_action(sig: u32) -> Disposition {
+    use syslib::signal::*;
+    match sig {
+        SIGCHLD | SIGURG | SIGWINCH => Disposition::Ignore,
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => Disposition::Stop,
+        SIGCONT => Disposition::Continue,
+        _ => Disposition::Terminate,
+    }
+}
+
+const STOPS: SigSet = sigbit(SIGSTOP) | sigbit(SIGTSTP) | sigbit(SIGTTIN) | sigbit(SIGTTOU);
+
+// Signals that can be neither caught nor blocked.
+const UNCATCHABLE: SigSet = sigbit(SIGKILL) | sigbit(SIGSTOP);
+
+pub fn valid(sig: u32) -> bool {
+    sig > 0 && sig < NSIG
+}
+
+// Other processes post signals while holding the owner's lock, and
+// so read its actions and blocked set.  The owner changes its
+// actions only while holding its own lock too (see `Proc`'s
+// `set_action`, `reset_actions` and `fault`), and its blocked set
+// atomically.
+pub struct Signals {
+    pending: AtomicU64,
+    blocked: AtomicU64,
+    actions: [Cell<SigAction>; NSIG as usize],
+}
+
+impl Signals {
+    pub const fn new() -> Signals {
+        Signals {
+            pending: AtomicU64::new(0),
+            blocked: AtomicU64::new(0),
+            actions: [const { Cell::new(SigAction::new()) }; NSIG as usize],
+        }
+    }
+
+    pub fn clear(&self) {
+        self.pending.store(0, Ordering::Relaxed);
+        self.blocked.store(0, Ordering::Relaxed);
+        for action in &self.actions {
+            action.set(SigAction::new());
+        }
+    }
+
+    // A child inherits its parent's actions and blocked signals,
+    // but none of its pending signals.
+    pub fn inherit(&self, parent: &Signals) {
+        self.pending.store(0, Ordering::Relaxed);
+        self.blocked.store(parent.blocked(), Ordering::Relaxed);
+        for (action, theirs) in self.actions.iter().zip(&parent.actions) {
+            action.set(theirs.get());
+        }
+    }
+
+    // Exec resets caught signals to their default action, as the
+    // handlers are gone with the old image.  Ignored signals stay
+    // ignored.
+    pub fn exec(&self) {
+        for action in &self.actions {
+            if action.get().handler != SIG_IGN {
+                action.set(SigAction::new());
+            }
+        }
+    }
+
+    pub fn action(&self, sig: u32) -> SigAction {
+        self.actions[sig as usize].get()
+    }
+
+    pub fn ignores(&self, sig: u32) -> bool {
+        match self.action(sig).handler {
+            SIG_IGN => true,
+            SIG_DFL => default_action(sig) == Disposition::Ignore,
+            _ => false,
+        }
+    }
+
+    pub fn set_action(&self, sig: u32, action: &SigAction) -> Result<SigAction> {
+        if !valid(sig) || sigbit(sig) & UNCATCHABLE != 0 {
+            return Err(Errno::EINVAL);
+        }
+        // The handler is returned to, and the restorer returned
+        // through, so both must be user addresses.
+        if action.handler >= USEREND || action.restorer >= USEREND {
+            return Err(Errno::EINVAL);
+        }
+        let old = self.actions[sig as usize].replace(*action);
+        // Setting a signal to be ignored discards it if pending.
+        if self.ignores(sig) {
+            self.pending.fetch_and(!sigbit(sig), Ordering::Relaxed);
+        }
+        Ok(old)
+    }
+
+    pub fn blocked(&self) -> SigSet {
+        self.blocked.load(Ordering::Relaxed)
+    }
+
+    pub fn set_blocked(&self, set: SigSet) {
+        self.blocked.store(set & !UNCATCHABLE, Ordering::Relaxed);
+    }
+
+    // Makes `sig` pending, unless it would be ignored, returning
+    // whether it now needs the process's attention.  SIGCONT and
+    // the stop signals cancel each other.  `init` is sent only
+    // the signals it has asked for, so that it cannot be killed
+    // by accident.
+    pub fn post(&self, sig: u32, init: bool) -> bool {
+        if sig == SIGCONT {
+            self.pending.fetch_and(!STOPS, Ordering::Relaxed);
+        } else if sigbit(sig) & STOPS != 0 {
+            self.pending.fetch_and(!sigbit(SIGCONT), Ordering::Relaxed);
+        }
+        if self.ignores(sig) || (init && self.action(sig).handler == SIG_DFL) {
+            return false;
+        }
+        self.pending.fetch_or(sigbit(sig), Ordering::Relaxed);
+        sigbit(sig) & !self.blocked() != 0
+    }
+
+    // Whether a signal is waiting to be acted upon.  Sleeps that
+    // may take arbitrarily long are cut short when one is.
+    pub fn deliverable(&self) -> bool {
+        self.pending.load(Ordering::Relaxed) & !self.blocked() != 0
+    }
+
+    // Removes and returns the lowest numbered deliverable signal.
+    pub fn take(&self) -> Option<u32> {
+        let ready = self.pending.load(Ordering::Relaxed) & !self.blocked();
+        if ready == 0 {
+            return None;
+        }
+        let sig = ready.trailing_zeros() + 1;
+        self.pending.fetch_and(!sigbit(sig), Ordering::Relaxed);
+        Some(sig)
+    }
+
+    // A fault cannot be deferred: if its signal is blocked or
+    // ignored, the process would only fault again, so the
+    // default action is restored before posting it.
+    pub fn force(&self, sig: u32) {
+        if sigbit(sig) & self.blocked() != 0 || self.action(sig).handler == SIG_IGN {
+            self.blocked.fetch_and(!sigbit(sig), Ordering::Relaxed);
+            self.actions[sig as usize].set(SigAction::new());
+        }
+        self.post(sig, false);
+    }
+}
+
+// The frame pushed on the user stack for a handler.  The handler
+// is entered with the stack pointer at `restorer`, which is thus
+// its return address.
+#[repr(C)]
+struct Frame {
+    restorer: u64,
+    sig: u64,
+    blocked: SigSet,
+    context: arch::TrapFrame,
+}
+
+// The area below the user's stack pointer that leaf functions
+// may use without moving it, by the System V ABI.
+const REDZONE: u64 = 128;
+
+fn push_frame(proc: &Proc, tf: &mut arch::TrapFrame, sig: u32, action: &SigAction) -> Result<()> {
+    let signals = proc.signals();
+    let sp = tf
+        .stack()
+        .checked_sub(REDZONE + mem::size_of::<Frame>() as u64);
+    // On entry to a function, the stack pointer is 8 more than a
+    // multiple of 16, as if a return address had been pushed.
+    let sp = sp.ok_or(Errno::EFAULT)? & !0xF;
+    let sp = sp - 8;
+    let frame = Frame {
+        restorer: action.restorer as u64,
+        sig: u64::from(sig),
+        blocked: signals.blocked(),
+        context: *tf,
+    };
+    let dst = proc
+        .fetch_ptr_mut::<Frame>(sp as usize, 1)
+        .ok_or(Errno::EFAULT)?;
+    unsafe { ptr::write_unaligned(dst, frame) };
+
+    let mut blocked = signals.blocked() | action.mask;
+    if action.flags & SA_NODEFER == 0 {
+        blocked |= sigbit(sig);
+    }
+    signals.set_blocked(blocked);
+    if action.flags & SA_RESETHAND != 0 {
+        proc.set_action(sig, &SigAction::new())?;
+    }
+    tf.set_pc(action.handler as u64);
+    unsafe {
+        tf.set_stack(sp);
+        tf.set_rdi(u64::from(sig));
+    }
+    Ok(())
+}
+
+// Acts on the pending signals of `proc`, which is about to return
+// to user space through `tf`: ignored ones are dropped, default
+// actions taken, and the first signal with a handler arranged to
+// be delivered.  Does not return if the process is terminated.
+pub fn deliver(proc: &Proc, tf: &mut arch::TrapFrame) {
+    loop {
+        if proc.dead() {
+            proc.exit(wait::signaled(SIGKILL));
+        }
+        let Some(sig) = proc.signals().take() else {
+            return;
+        };
+        let action = proc.signals().action(sig);
+        match action.handler {
+            SIG_IGN => {}
+            SIG_DFL => match default_action(sig) {
+                Disposition::Ignore | Disposition::Continue => {}
+                Disposition::Stop => proc.stop(sig),
+                Disposition::Terminate => proc.exit(wait::signaled(sig)),
+            },
+            _ => {
+                if push_frame(proc, tf, sig, &action).is_err() {
+                    proc.exit(wait::signaled(SIGSEGV));
+                }
+                return;
+            }
+        }
+    }
+}
+
+pub fn sigaction(proc: &Proc, sig: usize, act: usize, oldact: usize) -> Result<()> {
+    let sig = u32::try_from(sig).map_err(|_| Errno::EINVAL)?;
+    let old = if act != 0 {
+        let bytes = proc
+            .fetch_slice(act, mem::size_of::<SigAction>())
+            .ok_or(Errno::EFAULT)?;
+        let action = unsafe { ptr::read_unaligned(bytes.as_ptr().cast::<SigAction>()) };
+        proc.set_action(sig, &action)?
+    } else if valid(sig) {
+        proc.signals().action(sig)
+    } else {
+        return Err(Errno::EINVAL);
+    };
+    if oldact != 0 {
+        let dst = proc
+            .fetch_ptr_mut::<SigAction>(oldact, 1)
+            .ok_or(Errno::EFAULT)?;
+        unsafe { ptr::write_unaligned(dst, old) };
+    }
+    Ok(())
+}
+
+pub fn sigprocmask(proc: &Proc, how: usize, set: usize, oldset: usize) -> Result<()> {
+    let signals = proc.signals();
+    let old = signals.blocked();
+    if set != 0 {
+        let bytes = proc
+            .fetch_slice(set, mem::size_of::<SigSet>())
+            .ok_or(Errno::EFAULT)?;
+        let set = unsafe { ptr::read_unaligned(bytes.as_ptr().cast::<SigSet>()) };
+        let blocked = match how {
+            SIG_BLOCK => old | set,
+            SIG_UNBLOCK => old & !set,
+            SIG_SETMASK => set,
+            _ => return Err(Errno::EINVAL),
+        };
+        signals.set_blocked(blocked);
+    }
+    if oldset != 0 {
+        let dst = proc
+            .fetch_ptr_mut::<SigSet>(oldset, 1)
+            .ok_or(Errno::EFAULT)?;
+        unsafe { ptr::write_unaligned(dst, old) };
+    }
+    Ok(())
+}
+
+// Restores the state saved by `push_frame`, the handler having
+// returned, and returns to user space with it.  The frame lies
+// just above the stack pointer, the handler's `ret` having
+// popped the restorer.
+pub fn sigreturn(proc: &Proc) -> ! {
+    let tf = unsafe { proc.user_context_mut() };
+    let addr = tf.stack() as usize - mem::size_of::<u64>();
+    let Some(bytes) = proc.fetch_slice(addr, mem::size_of::<Frame>()) else {
+        proc.exit(wait::signaled(SIGSEGV));
+    };
+    let frame = unsafe { ptr::read_unaligned(bytes.as_ptr().cast::<Frame>()) };
+    if !frame.context.is_user_return() {
+        proc.exit(wait::signaled(SIGSEGV));
+    }
+    *tf = frame.context;
+    tf.sanitize();
+    proc.signals().set_blocked(frame.blocked);
+    deliver(proc, tf);
+    unsafe { arch::trapret(tf) }
+}
+
+#[cfg(test)]
+mod signal_tests {
+    use super::Signals;
+    use crate::param::USEREND;
+    use syslib::signal::*;
+
+    fn handler(addr: usize) -> SigAction {
+        SigAction {
+            handler: addr,
+            ..SigAction::new()
+        }
+    }
+
+    #[test]
+    fn takes_lowest_unblocked() {
+        let s = Signals::new();
+        assert!(s.post(SIGTERM, false));
+        assert!(s.post(SIGINT, false));
+        s.set_blocked(sigbit(SIGINT));
+        assert_eq!(s.take(), Some(SIGTERM));
+        assert_eq!(s.take(), None);
+        assert!(!s.deliverable());
+        s.set_blocked(0);
+        assert_eq!(s.take(), Some(SIGINT));
+    }
+
+    #[test]
+    fn drops_ignored() {
+        let s = Signals::new();
+        assert!(!s.post(SIGCHLD, false));
+        s.set_action(SIGINT, &handler(SIG_IGN)).unwrap();
+        assert!(!s.post(SIGINT, false));
+        assert_eq!(s.take(), None);
+        s.set_action(SIGCHLD, &handler(0x1000)).unwrap();
+        assert!(s.post(SIGCHLD, false));
+        assert_eq!(s.take(), Some(SIGCHLD));
+    }
+
+    #[test]
+    fn handlers_are_user_addresses() {
+        let s = Signals::new();
+        assert!(s.set_action(SIGINT, &handler(USEREND)).is_err());
+        let action = SigAction {
+            restorer: usize::MAX,
+            ..handler(0x1000)
+        };
+        assert!(s.set_action(SIGINT, &action).is_err());
+        assert_eq!(s.action(SIGINT).handler, SIG_DFL);
+        assert!(s.set_action(SIGINT, &handler(USEREND - 1)).is_ok());
+    }
+
+    #[test]
+    fn kill_and_stop_are_uncatchable() {
+        let s = Signals::new();
+        assert!(s.set_action(SIGKILL, &handler(0x1000)).is_err());
+        assert!(s.set_action(SIGSTOP, &handler(SIG_IGN)).is_err());
+        s.set_blocked(!0);
+        assert!(s.post(SIGKILL, false));
+        assert_eq!(s.take(), Some(SIGKILL));
+    }
+
+    #[test]
+    fn continue_cancels_stop() {
+        let s = Signals::new();
+        s.set_action(SIGCONT, &handler(0x1000)).unwrap();
+        s.post(SIGTSTP, false);
+        s.post(SIGCONT, false);
+        assert_eq!(s.take(), Some(SIGCONT));
+        assert_eq!(s.take(), None);
+    }
+
+    #[test]
+    fn init_gets_only_caught_signals() {
+        let s = Signals::new();
+        assert!(!s.post(SIGTERM, true));
+        s.set_action(SIGTERM, &handler(0x1000)).unwrap();
+        assert!(s.post(SIGTERM, true));
+    }
+
+    #[test]
+    fn exec_resets_handlers() {
+        let s = Signals::new();
+        s.set_action(SIGINT, &handler(0x1000)).unwrap();
+        s.set_action(SIGQUIT, &handler(SIG_IGN)).unwrap();
+        s.exec();
+        assert_eq!(s.action(SIGINT).handler, SIG_DFL);
+        assert_eq!(s.action(SIGQUIT).handler, SIG_IGN);
+    }
+}
@@ -1,10 +1,19 @@
 use crate::arch;
+use crate::kbd;
+use crate::mman;
 use crate::println;
-use crate::proc::{self, myproc};
+use crate::proc::myproc;
+use crate::resource;
+use crate::signal;
 use crate::sysfile;
+use crate::sysinfo;
 use crate::trap;
 use core::arch::naked_asm;
 use core::fmt::Debug;
+use syslib::errno::Errno;
+use syslib::signal::SIGSEGV;
+use syslib::syscall::*;
+use syslib::wait;
 
 pub unsafe fn init() {
     const MSR_STAR: u32 = 0xc000_0081;
@@ -24,38 +33,86 @@ where
     v.try_into().unwrap()
 }
 
-extern "C" fn syscall(a0: usize, a1: usize, a2: usize, num: usize) -> i64 {
-    use syslib::syscall::*;
+// A system call that fails returns its negated error number.
+fn err(e: Errno) -> i64 {
+    -(e as i64)
+}
+
+extern "C" fn syscall(a: &Args, num: usize) -> i64 {
     let proc = myproc();
     let r = match num {
-        FORK => proc.fork().map_or(-1, i64::from),
-        EXIT => proc.exit(a0 as i32),
-        WAIT => proc.wait(a0).map_or(-1, i64::from),
-        PIPE => sysfile::pipe(proc, a0).map_or(-1, |_| 0),
-        READ => sysfile::read(proc, a0, a1, a2).map_or(-1, to_i64),
-        KILL => proc::kill(a0 as u32).map_or(-1, |_| 0),
-        EXEC => sysfile::exec(proc, a0, a1).map_or(-1, |_| 0),
-        FSTAT => sysfile::stat(proc, a0, a1).map_or(-1, |_| 0),
-        CHDIR => sysfile::chdir(proc, a0).map_or(-1, |_| 0),
-        DUP => sysfile::dup(proc, a0).map_or(-1, to_i64),
+        FORK => proc.fork().map_or_else(err, i64::from),
+        EXIT => proc.exit(wait::exited(a.get(0))),
+        WAIT => proc.wait(-1, a.get(0), 0, 0).map_or_else(err, i64::from),
+        PIPE => sysfile::pipe(proc, a.get(0)).map_or_else(err, |_| 0),
+        READ => sysfile::read(proc, a.get(0), a.get(1), a.get(2)).map_or_else(err, to_i64),
+        KILL => proc.send(a.get(0), a.get(1)).map_or_else(err, |_| 0),
+        EXEC => sysfile::exec(proc, a.get(0), a.get(1)).map_or_else(err, |_| 0),
+        FSTAT => sysfile::stat(proc, a.get(0), a.get(1)).map_or_else(err, |_| 0),
+        CHDIR => sysfile::chdir(proc, a.get(0)).map_or_else(err, |_| 0),
+        DUP => sysfile::dup(proc, a.get(0)).map_or_else(err, to_i64),
         GETPID => i64::from(proc.pid()),
-        SBRK => proc.adjsize(a0 as isize).map_or(-1, to_i64),
-        SLEEP => trap::ticksleep(proc, a0 as u64).map_or(-1, |_| 0),
+        SBRK => proc.adjsize(a.get(0)).map_or_else(err, to_i64),
+        SLEEP => trap::ticksleep(proc, a.get(0)).map_or_else(err, |_| 0),
         UPTIME => trap::ticks() as i64,
-        OPEN => sysfile::open(proc, a0, a1).map_or(-1, to_i64),
-        WRITE => sysfile::write(proc, a0, a1, a2).map_or(-1, to_i64),
-        MKNOD => sysfile::mknod(proc, a0, a1 as u32, a2 as u32).map_or(-1, |_| 0),
-        UNLINK => sysfile::unlink(proc, a0).map_or(-1, |_| 0),
-        LINK => sysfile::link(proc, a0, a1).map_or(-1, |_| 0),
-        MKDIR => sysfile::mkdir(proc, a0).map_or(-1, |_| 0),
-        CLOSE => sysfile::close(proc, a0).map_or(-1, |_| 0),
+        OPEN => sysfile::open(proc, a.get(0), a.get(1)).map_or_else(err, to_i64),
+        WRITE => sysfile::write(proc, a.get(0), a.get(1), a.get(2)).map_or_else(err, to_i64),
+        MKNOD => sysfile::mknod(proc, a.get(0), a.get(1), a.get(2)).map_or_else(err, |_| 0),
+        UNLINK => sysfile::unlink(proc, a.get(0)).map_or_else(err, |_| 0),
+        LINK => sysfile::link(proc, a.get(0), a.get(1)).map_or_else(err, |_| 0),
+        MKDIR => sysfile::mkdir(proc, a.get(0)).map_or_else(err, |_| 0),
+        CLOSE => sysfile::close(proc, a.get(0)).map_or_else(err, |_| 0),
+        SCREEN => sysfile::screen(proc, a.get(0), a.get(1), a.get(2)).map_or_else(err, |_| 0),
+        KEYMAP => kbd::keymap(proc, a.get(0), a.get(1)).map_or_else(err, |_| 0),
+        IOCTL => sysfile::ioctl(proc, a.get(0), a.get(1), a.get(2)).map_or_else(err, |_| 0),
+        SYSINFO => sysinfo::info(proc, a.get(0), a.get(1), a.get(2)).map_or_else(err, to_i64),
+        SIGACTION => signal::sigaction(proc, a.get(0), a.get(1), a.get(2)).map_or_else(err, |_| 0),
+        SIGPROCMASK => {
+            signal::sigprocmask(proc, a.get(0), a.get(1), a.get(2)).map_or_else(err, |_| 0)
+        }
+        SIGRETURN => signal::sigreturn(proc),
+        SETPGID => proc.setpgid(a.get(0), a.get(1)).map_or_else(err, |_| 0),
+        GETPGID => proc.getpgid(a.get(0)).map_or_else(err, i64::from),
+        SETSID => proc.setsid().map_or_else(err, i64::from),
+        WAIT4 => proc
+            .wait(a.get(0), a.get(1), a.get(2), a.get(3))
+            .map_or_else(err, i64::from),
+        TIMES => resource::times(proc, a.get(0)).map_or_else(err, to_i64),
+        GETRUSAGE => resource::getrusage(proc, a.get(0), a.get(1)).map_or_else(err, |_| 0),
+        SETPRIORITY => proc
+            .setpriority(a.get(0), a.get(1), a.get(2))
+            .map_or_else(err, |_| 0),
+        // As on Linux, 20 - nice, so that no priority looks like -1.
+        GETPRIORITY => proc
+            .getpriority(a.get(0), a.get(1))
+            .map_or_else(err, |n| i64::from(20 - n)),
+        MMAP => mman::mmap(
+            proc,
+            a.get(0),
+            a.get(1),
+            a.get(2),
+            a.get(3),
+            a.get(4),
+            a.get(5),
+        )
+        .map_or_else(err, to_i64),
+        MUNMAP => mman::munmap(proc, a.get(0), a.get(1)).map_or_else(err, |_| 0),
+        MPROTECT => mman::mprotect(proc, a.get(0), a.get(1), a.get(2)).map_or_else(err, |_| 0),
         _ => {
-            println!("syscall number {num}, a0={a0}, a1={a1}, a2={a2}");
-            -1
+            println!("syscall number {num}, args={:x?}", a.0);
+            err(Errno::ENOSYS)
         }
     };
-    if proc.dead() {
-        proc.exit(1);
+    // The result is saved in the frame so that a signal handler
+    // run now returns to it.
+    let frame = unsafe { proc.user_context_mut() };
+    frame.set_rax(r as u64);
+    signal::deliver(proc, frame);
+    // `sysretq` faults in the kernel, on the user's stack, if it
+    // returns to an address that is not canonical, such as a bad
+    // handler or exec entry point would leave.
+    if !frame.is_user_return() {
+        proc.exit(wait::signaled(SIGSEGV));
     }
     r
 }
@@ -66,11 +123,10 @@ unsafe extern "C" fn enter() -> ! {
     naked_asm!(r#"
         swapgs
 
-        // Stash the user stack pointer and set the kernel
-        // stack pointer.  Use %r8 as a scratch register,
-        // since it is callee-save and we clear on return
-        // anyway.
-        movq %rsp, %r8
+        // Stash the user stack pointer in the per-CPU structure,
+        // since every other register may hold an argument, and
+        // set the kernel stack pointer.
+        movq %rsp, %gs:8
         movq %gs:16, %rsp
 
         // We construct a trap frame on the stack, but many of the
@@ -83,7 +139,7 @@ unsafe extern "C" fn enter() -> ! {
         // we can use this to retrieve the Context structure.  We use
         // this in e.g. fork() to copy state from the parent to the child.
         pushq $0    // %ss
-        pushq %r8   // user stack pointer
+        pushq %gs:8 // user stack pointer
         pushq %r11  // user %rflags
 
         movq %cs, %r11
@@ -108,17 +164,29 @@ unsafe extern "C" fn enter() -> ! {
         pushq %r13
         pushq %r12
         pushq $0    // %r11 was trashed
-        pushq $0    // %10 is caller-save
-        pushq $0    // %r9 is caller-save
-        pushq $0    // %r8 is caller-save (and used as scratch)
+        pushq %r10
+        pushq %r9
+        pushq %r8
         pushq %rbp
-        pushq $0    // %rdi is caller-save
-        pushq $0    // %rsi is caller-save
-        pushq $0    // %rdx is caller-save
+        pushq %rdi
+        pushq %rsi
+        pushq %rdx
         pushq $0    // %rcx was trashed
         pushq %rbx
         pushq %rax
 
+        // Push the arguments as a `syslib::syscall::Args`,
+        // which is the first argument to `syscall`; the
+        // system call number is the second.
+        pushq %r9
+        pushq %r8
+        pushq %r10
+        pushq %rdx
+        pushq %rsi
+        pushq %rdi
+        movq %rsp, %rdi
+        movq %rax, %rsi
+
         // Push a dummy word to align the stack.
         pushq $0
 
@@ -127,15 +195,12 @@ unsafe extern "C" fn enter() -> ! {
         pushq %rcx
         movq %r11, %rbp
 
-        // System call number is 4th argument to `syscall` function.
-        movq %rax, %rcx
-
         // Call the handler in Rust.
         // XXX: Could we `sti` here?
         callq {syscall}
 
-        // Pop stack frame and dummy word.
-        addq $(8 * 2), %rsp
+        // Pop stack frame, dummy word and arguments.
+        addq $(8 * 8), %rsp
         jmp {syscallret}
         "#,
         syscall = sym syscall,
@@ -188,8 +253,8 @@ pub unsafe extern "C" fn syscallret() {
         // user flags go in %r11
         popq %r11
 
-        // copy user stack pointer into %r8
-        popq %r8
+        // Stash the user stack pointer in the per-CPU structure.
+        popq %gs:8
 
         // Skip %ss
         addq $8, %rsp
@@ -198,8 +263,7 @@ pub unsafe extern "C" fn syscallret() {
         movq %rsp, %gs:16
 
         // Restore user stack pointer.
-        movq %r8, %rsp
-        xorq %r8, %r8
+        movq %gs:8, %rsp
 
         // Switch kernel, user GSBASE
         swapgs
@@ -1,4 +1,5 @@
 use crate::Result;
+use crate::console;
 use crate::exec;
 use crate::file;
 use crate::fs;
@@ -8,20 +9,23 @@ use crate::pipe;
 use crate::proc::Proc;
 use core::mem;
 use core::ptr;
+use syslib::errno::Errno;
+use syslib::screen;
 use syslib::stat::{FileType, Stat};
 use syslib::syscall;
+use syslib::termios::{self, Termios, Winsize};
 
 fn parse_flags(flags: usize) -> Result<(file::OpenFlags, bool)> {
     let create = flags & syscall::O_CREATE == syscall::O_CREATE;
-    match flags & !syscall::O_CREATE {
+    match flags & !(syscall::O_CREATE | syscall::O_NOCTTY) {
         syscall::O_READ => Ok((file::OpenFlags::Read, create)),
         syscall::O_WRITE => Ok((file::OpenFlags::Write, create)),
         syscall::O_RDWR => Ok((file::OpenFlags::ReadWrite, create)),
-        _ => Err("bad open mode"),
+        _ => Err(Errno::EINVAL),
     }
 }
 pub fn open(proc: &Proc, path_ptr: usize, flags: usize) -> Result<usize> {
-    let path = proc.fetch_str(path_ptr).ok_or("bad path")?;
+    let path = proc.fetch_str(path_ptr).ok_or(Errno::EFAULT)?;
     let (mode, create) = parse_flags(flags)?;
     fslog::with_op(|| {
         let ip = if create {
@@ -33,16 +37,20 @@ pub fn open(proc: &Proc, path_ptr: usize, flags: usize) -> Result<usize> {
         }?;
         let guard = fs::PutLockGuard::new_locked(ip);
         let like = match ip.typ() {
-            FileType::Dir if mode != file::OpenFlags::Read => return Err("open writeable dir"),
+            FileType::Dir if mode != file::OpenFlags::Read => return Err(Errno::EISDIR),
             FileType::Dir | FileType::File => ip,
-            FileType::Dev => file::devsw(ip.major())?,
-            _ => return Err("opening file type none"),
+            FileType::Dev => {
+                let dev = file::devsw(ip.major(), ip.minor())?;
+                if ip.major() == console::CONSOLE_MAJOR && flags & syscall::O_NOCTTY == 0 {
+                    console::attach(proc, ip.minor() as usize);
+                }
+                dev
+            }
+            _ => return Err(Errno::ENOENT),
         };
-        let file = file::alloc(mode, like).ok_or("cannot allocate file")?;
+        let file = file::alloc(mode, like).ok_or(Errno::ENFILE)?;
         let file_guard = file::Guard::new(file);
-        let fd = proc
-            .alloc_fd(file)
-            .ok_or("cannot allocate file descriptor")?;
+        let fd = proc.alloc_fd(file).ok_or(Errno::EMFILE)?;
         file_guard.release();
         guard.release();
         ip.unlock();
@@ -55,33 +63,33 @@ pub fn close(proc: &Proc, fd: usize) -> Result<()> {
         file.close();
         Ok(())
     } else {
-        Err("bad file descriptor")
+        Err(Errno::EBADF)
     }
 }
 
 pub fn write(proc: &Proc, fd: usize, addr: usize, len: usize) -> Result<usize> {
-    let file = proc.get_fd(fd).ok_or("bad file")?;
-    let buf = proc.fetch_slice(addr, len).ok_or("bad pointer")?;
+    let file = proc.get_fd(fd).ok_or(Errno::EBADF)?;
+    let buf = proc.fetch_slice(addr, len).ok_or(Errno::EFAULT)?;
     file.write(buf)
 }
 
 pub fn read(proc: &Proc, fd: usize, addr: usize, len: usize) -> Result<usize> {
-    let file = proc.get_fd(fd).ok_or("bad file")?;
-    let buf = proc.fetch_slice_mut(addr, len).ok_or("bad pointer")?;
+    let file = proc.get_fd(fd).ok_or(Errno::EBADF)?;
+    let buf = proc.fetch_slice_mut(addr, len).ok_or(Errno::EFAULT)?;
     file.read(buf)
 }
 
 pub fn exec(proc: &Proc, path_ptr: usize, args_ptr: usize) -> Result<()> {
-    let path = proc.fetch_str(path_ptr).ok_or("bad path")?;
+    let path = proc.fetch_str(path_ptr).ok_or(Errno::EFAULT)?;
     let mut args = [&[] as &[u8]; param::MAXARG];
     let mut k = 0;
     let mut ptr;
     while {
         let uargp = args_ptr + k * mem::size_of::<usize>();
-        ptr = proc.fetch_usize(uargp).ok_or("bad argv")?;
+        ptr = proc.fetch_usize(uargp).ok_or(Errno::EFAULT)?;
         k < param::MAXARG && ptr != 0
     } {
-        args[k] = proc.fetch_str(ptr).ok_or("bad argument")?;
+        args[k] = proc.fetch_str(ptr).ok_or(Errno::EFAULT)?;
         k += 1;
     }
     let argv = &args[..k];
@@ -89,13 +97,13 @@ pub fn exec(proc: &Proc, path_ptr: usize, args_ptr: usize) -> Result<()> {
 }
 
 pub fn stat(proc: &Proc, fd: usize, addr: usize) -> Result<()> {
-    let file = proc.get_fd(fd).ok_or("bad file")?;
+    let file = proc.get_fd(fd).ok_or(Errno::EBADF)?;
     let sb = file.stat()?;
     // By fetching the slice, we assert that there is enough space
     // in the process to accommodate the entire Stat structure.
     let user_sb_slice = proc
         .fetch_slice_mut(addr, mem::size_of::<Stat>())
-        .ok_or("bad pointer")?;
+        .ok_or(Errno::EFAULT)?;
     unsafe {
         use core::intrinsics::volatile_copy_memory;
         volatile_copy_memory(
@@ -108,13 +116,13 @@ pub fn stat(proc: &Proc, fd: usize, addr: usize) -> Result<()> {
 }
 
 pub fn link(proc: &Proc, path_ptr: usize, new_path_ptr: usize) -> Result<()> {
-    let path = proc.fetch_str(path_ptr).ok_or("bad path")?;
-    let new_name = proc.fetch_str(new_path_ptr).ok_or("bad new path")?;
+    let path = proc.fetch_str(path_ptr).ok_or(Errno::EFAULT)?;
+    let new_name = proc.fetch_str(new_path_ptr).ok_or(Errno::EFAULT)?;
     fslog::with_op(|| {
         let ip = fs::namei(path)?;
         let guard = fs::PutLockGuard::new(ip);
         if ip.typ() == FileType::Dir {
-            return Err("link dir");
+            return Err(Errno::EPERM);
         }
         ip.nlink_inc();
         ip.update()?;
@@ -132,7 +140,7 @@ pub fn link(proc: &Proc, path_ptr: usize, new_path_ptr: usize) -> Result<()> {
         let (dp, name) = fs::namei_parent(new_name)?;
         let guard = fs::PutLockGuard::new(dp);
         if dp.dev() != dev {
-            return error("cross-device link");
+            return error(Errno::EXDEV);
         }
         if let Err(e) = dp.dir_link(name, inum) {
             return error(e);
@@ -143,18 +151,18 @@ pub fn link(proc: &Proc, path_ptr: usize, new_path_ptr: usize) -> Result<()> {
 }
 
 pub fn unlink(proc: &Proc, path_ptr: usize) -> Result<()> {
-    let path = proc.fetch_str(path_ptr).ok_or("bad path")?;
+    let path = proc.fetch_str(path_ptr).ok_or(Errno::EFAULT)?;
     fslog::with_op(|| {
         let (dp, name) = fs::namei_parent(path)?;
         if name == b"." || name == b".." {
-            return Err("unlink . or ..");
+            return Err(Errno::EINVAL);
         }
         dp.dir_unlink(name)
     })
 }
 
 pub fn mkdir(proc: &Proc, path_ptr: usize) -> Result<()> {
-    let path = proc.fetch_str(path_ptr).ok_or("bad path")?;
+    let path = proc.fetch_str(path_ptr).ok_or(Errno::EFAULT)?;
     fslog::with_op(|| {
         let ip = fs::create(path, fs::CreateType::Dir)?;
         ip.unlock_put()
@@ -162,7 +170,7 @@ pub fn mkdir(proc: &Proc, path_ptr: usize) -> Result<()> {
 }
 
 pub fn mknod(proc: &Proc, path_ptr: usize, major: u32, minor: u32) -> Result<()> {
-    let path = proc.fetch_str(path_ptr).ok_or("bad path")?;
+    let path = proc.fetch_str(path_ptr).ok_or(Errno::EFAULT)?;
     fslog::with_op(|| {
         let ip = fs::create(path, fs::CreateType::Dev(major, minor))?;
         ip.unlock_put()
@@ -170,12 +178,12 @@ pub fn mknod(proc: &Proc, path_ptr: usize, major: u32, minor: u32) -> Result<()>
 }
 
 pub fn chdir(proc: &Proc, path_ptr: usize) -> Result<()> {
-    let path = proc.fetch_str(path_ptr).ok_or("bad path")?;
+    let path = proc.fetch_str(path_ptr).ok_or(Errno::EFAULT)?;
     let ip = fslog::with_op(|| {
         let ip = fs::namei(path)?;
         let guard = fs::PutLockGuard::new(ip);
         if ip.typ() != FileType::Dir {
-            return Err("chdir to non-directory");
+            return Err(Errno::ENOTDIR);
         }
         guard.release();
         ip.unlock();
@@ -188,28 +196,92 @@ pub fn chdir(proc: &Proc, path_ptr: usize) -> Result<()> {
 }
 
 pub fn dup(proc: &'static Proc, fd: usize) -> Result<usize> {
-    let file = proc.get_fd(fd).ok_or("bad file")?;
-    let fd = proc
-        .alloc_fd(file)
-        .ok_or("cannot allocate file descriptor")?;
+    let file = proc.get_fd(fd).ok_or(Errno::EBADF)?;
+    let fd = proc.alloc_fd(file).ok_or(Errno::EMFILE)?;
     file.dup();
     Ok(fd)
 }
 
+// Draws on the screen of the console open on `fd`.  Moving the
+// cursor also determines where subsequent output to the
+// console, including the echo of typed input, will appear.
+pub fn screen(proc: &Proc, fd: usize, op: usize, req_ptr: usize) -> Result<()> {
+    let file = proc.get_fd(fd).ok_or(Errno::EBADF)?;
+    let req = match op {
+        screen::CLEAR => screen::Request::default(),
+        _ => {
+            let bytes = proc
+                .fetch_slice(req_ptr, mem::size_of::<screen::Request>())
+                .ok_or(Errno::EFAULT)?;
+            unsafe { ptr::read_unaligned(bytes.as_ptr().cast::<screen::Request>()) }
+        }
+    };
+    let text = match op {
+        screen::TEXT => proc.fetch_str(req.text).ok_or(Errno::EFAULT)?,
+        _ => &[],
+    };
+    file.screen(op, &req, text)
+}
+
+// Controls the terminal open on `fd`.  The argument is a pointer
+// to a `Termios`, `Winsize` or process group id, read or written
+// according to the request.
+pub fn ioctl(proc: &Proc, fd: usize, request: usize, arg: usize) -> Result<()> {
+    fn copy_in<T>(proc: &Proc, addr: usize) -> Result<T> {
+        let bytes = proc
+            .fetch_slice(addr, mem::size_of::<T>())
+            .ok_or(Errno::EFAULT)?;
+        Ok(unsafe { ptr::read_unaligned(bytes.as_ptr().cast::<T>()) })
+    }
+    fn copy_out<T>(proc: &Proc, addr: usize, val: T) -> Result<()> {
+        let ptr = proc.fetch_ptr_mut::<T>(addr, 1).ok_or(Errno::EFAULT)?;
+        unsafe { ptr::write_unaligned(ptr, val) };
+        Ok(())
+    }
+    let file = proc.get_fd(fd).ok_or(Errno::EBADF)?;
+    match request {
+        termios::TCGETS => {
+            let mut t = Termios::new();
+            file.ioctl(file::Ioctl::GetTermios(&mut t))?;
+            copy_out(proc, arg, t)
+        }
+        termios::TCSETS | termios::TCSETSW | termios::TCSETSF => {
+            let t = copy_in::<Termios>(proc, arg)?;
+            // Output is written as it is made, so there is never
+            // any to wait to drain.
+            if request == termios::TCSETSF {
+                file.ioctl(file::Ioctl::DiscardInput)?;
+            }
+            file.ioctl(file::Ioctl::SetTermios(&t))
+        }
+        termios::TIOCGWINSZ => {
+            let mut ws = Winsize::default();
+            file.ioctl(file::Ioctl::GetWinsize(&mut ws))?;
+            copy_out(proc, arg, ws)
+        }
+        termios::TIOCGPGRP => {
+            let mut pgrp = 0;
+            file.ioctl(file::Ioctl::GetPgrp(&mut pgrp))?;
+            copy_out(proc, arg, pgrp)
+        }
+        termios::TIOCSPGRP => {
+            let pgrp = copy_in::<u32>(proc, arg)?;
+            file.ioctl(file::Ioctl::SetPgrp(pgrp))
+        }
+        _ => Err(Errno::ENOTTY),
+    }
+}
+
 pub fn pipe(proc: &Proc, fd_ptr: usize) -> Result<()> {
-    let fds_ptr = proc
-        .fetch_ptr_mut::<i32>(fd_ptr, 2)
-        .ok_or("bad pipe pointer")?;
+    let fds_ptr = proc.fetch_ptr_mut::<i32>(fd_ptr, 2).ok_or(Errno::EFAULT)?;
     let (r, w) = pipe::alloc()?;
     let rguard = file::Guard::new(r);
     let wguard = file::Guard::new(w);
-    let rfd = proc
-        .alloc_fd(r)
-        .ok_or("cannot allocate pipe read descriptor")?;
+    let rfd = proc.alloc_fd(r).ok_or(Errno::EMFILE)?;
     let maybe = proc.alloc_fd(w);
     if maybe.is_none() {
         proc.free_fd(rfd);
-        return Err("cannot allocate pipe write descriptor");
+        return Err(Errno::EMFILE);
     }
     let wfd = maybe.unwrap();
     rguard.release();
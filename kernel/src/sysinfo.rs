@@ -0,0 +1,151 @@
+// The sysinfo system call, which reports on the state of the
+// running system: the kernel version, CPUs, memory, the disk and
+// the process table.
+
+use crate::Result;
+use crate::acpi;
+use crate::arch;
+use crate::kalloc;
+use crate::kmem::{self, MemType};
+use crate::param;
+use crate::proc::{self, Proc, ProcState};
+use crate::sd;
+use crate::trap;
+use core::ptr;
+use syslib::errno::Errno;
+use syslib::sysinfo::{self, MemRegion, ProcInfo, Sysinfo};
+
+fn system() -> Sysinfo {
+    let mut info = Sysinfo::new();
+    let version = env!("CARGO_PKG_VERSION").as_bytes();
+    let len = version.len().min(info.version.len() - 1);
+    info.version[..len].copy_from_slice(&version[..len]);
+    info.ncpu = unsafe { acpi::cpus() }.len() as u32;
+    proc::for_each(|_, _| info.nproc += 1);
+    info.hz = param::HZ;
+    info.uptime = trap::ticks();
+    info.page_size = arch::PAGE_SIZE as u64;
+    let (total, free) = kalloc::stats();
+    info.total_pages = total as u64;
+    info.free_pages = free as u64;
+    let regions = kmem::mem_map()
+        .iter()
+        .filter(|e| e.typ != MemType::Nothing)
+        .take(sysinfo::MAXREGIONS);
+    for (region, e) in info.regions.iter_mut().zip(regions) {
+        let typ = match e.typ {
+            MemType::Memory => sysinfo::MEM_USABLE,
+            MemType::System => sysinfo::MEM_SYSTEM,
+            _ => sysinfo::MEM_RESERVED,
+        };
+        *region = MemRegion {
+            start: e.start,
+            end: e.end,
+            typ,
+        };
+        info.nregions += 1;
+    }
+    if let Some((model, serial, sectors)) = sd::identity() {
+        info.disk_sectors = sectors;
+        info.disk_model = trim(model);
+        info.disk_serial = trim(serial);
+    }
+    info
+}
+
+// Replaces the trailing spaces of an ATA identity string with
+// NULs.
+fn trim<const N: usize>(mut s: [u8; N]) -> [u8; N] {
+    for b in s.iter_mut().rev().take_while(|b| **b == b' ' || **b == 0) {
+        *b = 0;
+    }
+    s
+}
+
+fn state(state: ProcState) -> u32 {
+    match state {
+        ProcState::UNUSED => 0,
+        ProcState::EMBRYO => sysinfo::PROC_EMBRYO,
+        ProcState::SLEEPING(_) => sysinfo::PROC_SLEEPING,
+        ProcState::RUNNABLE => sysinfo::PROC_RUNNABLE,
+        ProcState::RUNNING => sysinfo::PROC_RUNNING,
+        ProcState::STOPPED => sysinfo::PROC_STOPPED,
+        ProcState::ZOMBIE(_) => sysinfo::PROC_ZOMBIE,
+    }
+}
+
+// The number of `ProcInfo`s gathered at a time: the whole table
+// would not fit on a kernel stack.
+const CHUNK: usize = 8;
+
+// Fills in up to `len` `ProcInfo`s at `addr`, returning the
+// number filled.  The table is gathered under its lock a chunk at
+// a time, and each chunk copied out after, so that copying never
+// faults while holding it.  A process that comes or goes between
+// chunks may be missed or seen twice.
+fn procs(proc: &Proc, addr: usize, len: usize) -> Result<usize> {
+    let out = proc
+        .fetch_ptr_mut::<ProcInfo>(addr, len)
+        .ok_or(Errno::EFAULT)?;
+    let len = len.min(param::NPROC);
+    let mut n = 0;
+    while n < len {
+        let want = CHUNK.min(len - n);
+        let mut chunk = [ProcInfo::default(); CHUNK];
+        let mut got = 0;
+        let mut seen = 0;
+        proc::for_each(|p, ppid| {
+            if seen >= n && got < want {
+                let mut name = p.name();
+                name[name.len() - 1] = 0;
+                chunk[got] = ProcInfo {
+                    pid: p.pid(),
+                    ppid,
+                    state: state(p.state()),
+                    size: p.size() as u64,
+                    name,
+                    utime: p.usage().utime,
+                    stime: p.usage().stime,
+                    nice: p.sched_state().nice,
+                    level: p.sched_state().level,
+                };
+                got += 1;
+            }
+            seen += 1;
+        });
+        for (k, info) in chunk[..got].iter().enumerate() {
+            unsafe { ptr::write_unaligned(out.add(n + k), *info) };
+        }
+        n += got;
+        if got < want {
+            break;
+        }
+    }
+    Ok(n)
+}
+
+pub fn info(proc: &Proc, op: usize, addr: usize, len: usize) -> Result<usize> {
+    match op {
+        sysinfo::SYSTEM => {
+            let out = proc
+                .fetch_ptr_mut::<Sysinfo>(addr, 1)
+                .ok_or(Errno::EFAULT)?;
+            unsafe { ptr::write_unaligned(out, system()) };
+            Ok(0)
+        }
+        sysinfo::PROCS => procs(proc, addr, len),
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+#[cfg(test)]
+mod sysinfo_tests {
+    use super::trim;
+
+    #[test]
+    fn trim_padding() {
+        assert_eq!(&trim(*b"QEMU HARDDISK   "), b"QEMU HARDDISK\0\0\0");
+        assert_eq!(&trim(*b"  QM1 "), b"  QM1\0");
+        assert_eq!(&trim(*b"    "), b"\0\0\0\0");
+    }
+}
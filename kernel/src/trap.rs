@@ -1,25 +1,50 @@
 use crate::Result;
 use crate::arch;
+use crate::ipi;
 use crate::kbd;
+use crate::param;
 use crate::println;
 use crate::proc::{self, Proc};
 use crate::sd;
-use crate::spinlock::SpinMutex as Mutex;
+use crate::signal;
+use crate::spinlock::{SpinMutex as Mutex, without_intrs};
 use crate::uart;
 use crate::volatile;
 use crate::xapic;
 use core::cell::SyncUnsafeCell;
+use core::sync::atomic::{AtomicU64, Ordering};
+use syslib::errno::Errno;
+use syslib::signal::{SIGFPE, SIGILL, SIGSEGV, SIGTRAP};
 
 pub(crate) const INTR0: u32 = 32;
 const KBD_INTR: u32 = INTR0 + kbd::INTR;
 const EIA0_INTR: u32 = INTR0 + uart::INTR_EIA0;
 const TIMER_INTR: u32 = INTR0 + xapic::INTR_TIMER;
 const SD_INTR: u32 = INTR0 + sd::INTR_SD0;
+const RESCHED_INTR: u32 = INTR0 + ipi::INTR_RESCHED;
+const CALL_INTR: u32 = INTR0 + ipi::INTR_CALL;
+const HALT_INTR: u32 = INTR0 + ipi::INTR_HALT;
 
+const DIVIDE_ERROR: u32 = 0;
+const BREAKPOINT: u32 = 3;
+const INVALID_OPCODE: u32 = 6;
 const PAGE_FAULT: u32 = 14;
+const FP_ERROR: u32 = 16;
+const SIMD_FP_ERROR: u32 = 19;
+
+// Page fault error code bits.
+const PF_WRITE: u64 = 1 << 1;
 
 static TICKS: Mutex<u64> = Mutex::new("time", 0);
 
+// The earliest tick that a sleeper has asked to be woken at.
+// The boot CPU keeps time, and when idle, sleeps until then.
+static DEADLINE: AtomicU64 = AtomicU64::new(u64::MAX);
+
+// The longest the boot CPU sleeps when idle, should a wakeup have
+// been missed.
+const MAX_IDLE_TICKS: u64 = param::HZ;
+
 pub fn ticks() -> u64 {
     *TICKS.lock()
 }
@@ -28,13 +53,20 @@ pub fn tickchan() -> usize {
     (&TICKS as *const Mutex<u64>).addr()
 }
 
+// Asks that sleepers on `tickchan` be woken no later than tick
+// `deadline`, even if every CPU is idle.
+pub fn wake_at(deadline: u64) {
+    DEADLINE.fetch_min(deadline, Ordering::SeqCst);
+}
+
 pub fn ticksleep(proc: &Proc, nticks: u64) -> Result<()> {
     let ticks0 = ticks();
     TICKS.with_lock(|ticks| {
         while volatile::read(ticks) - ticks0 < nticks {
-            if proc.dead() {
-                return Err("killed");
+            if proc.interrupted() {
+                return Err(Errno::EINTR);
             }
+            wake_at(ticks0 + nticks);
             proc.sleep(tickchan(), &TICKS)
         }
         Ok(())
@@ -44,16 +76,21 @@ pub fn ticksleep(proc: &Proc, nticks: u64) -> Result<()> {
 pub extern "C" fn trap(vecnum: u32, frame: &mut arch::TrapFrame) {
     match vecnum {
         PAGE_FAULT => {
-            if !frame.is_user() {
-                panic!(
-                    "page fault at {:x}, rip = {:x}, error = {:x}",
-                    arch::fault_addr(),
-                    frame.rip,
-                    frame.error
-                );
+            // The kernel, too, may write to copy-on-write pages.
+            let write = frame.error & PF_WRITE != 0;
+            let resolved =
+                proc::try_myproc().is_some_and(|p| p.page_fault(arch::fault_addr(), write).is_ok());
+            if !resolved {
+                if !frame.is_user() {
+                    panic!(
+                        "page fault at {:x}, rip = {:x}, error = {:x}",
+                        arch::fault_addr(),
+                        frame.rip,
+                        frame.error
+                    );
+                }
+                proc::myproc().fault(SIGSEGV);
             }
-            //println!("trap user pagefault");
-            proc::myproc().kill();
         }
         KBD_INTR => {
             assert!(arch::mycpu_id() == 0);
@@ -64,11 +101,13 @@ pub extern "C" fn trap(vecnum: u32, frame: &mut arch::TrapFrame) {
             uart::interrupt();
         }
         TIMER_INTR => {
+            if let Some(p) = proc::try_myproc() {
+                p.tick(frame.is_user());
+            }
+            // The one-shot timer of an idle CPU counts many ticks.
+            let elapsed = unsafe { xapic::timer_resume() };
             if arch::mycpu_id() == 0 {
-                TICKS.with_lock(|ticks| {
-                    *ticks = ticks.wrapping_add(1);
-                    proc::wakeup(tickchan());
-                });
+                clock(elapsed.max(1));
             }
             unsafe {
                 xapic::eoi();
@@ -78,6 +117,16 @@ pub extern "C" fn trap(vecnum: u32, frame: &mut arch::TrapFrame) {
             assert!(arch::mycpu_id() == 0);
             sd::interrupt();
         }
+        RESCHED_INTR => unsafe {
+            xapic::eoi();
+        },
+        CALL_INTR => {
+            ipi::interrupt();
+            unsafe {
+                xapic::eoi();
+            }
+        }
+        HALT_INTR => arch::halt(),
         _ => {
             if !frame.is_user() || proc::try_myproc().is_none() {
                 println!(
@@ -86,8 +135,13 @@ pub extern "C" fn trap(vecnum: u32, frame: &mut arch::TrapFrame) {
                 );
                 panic!("unanticipated interrupt");
             }
-            let proc = proc::myproc();
-            proc.kill();
+            let sig = match vecnum {
+                DIVIDE_ERROR | FP_ERROR | SIMD_FP_ERROR => SIGFPE,
+                BREAKPOINT => SIGTRAP,
+                INVALID_OPCODE => SIGILL,
+                _ => SIGSEGV,
+            };
+            proc::myproc().fault(sig);
         }
     }
 
@@ -98,16 +152,58 @@ pub extern "C" fn trap(vecnum: u32, frame: &mut arch::TrapFrame) {
         proc::die_if_dead();
     }
 
-    // Force process to give up CPU on clock tick.  If
-    // interrupts were on while locks held, would need to check
-    // nlock.
+    // Charge the clock tick to the running process, which gives
+    // up the CPU if the scheduler says so.  If interrupts were on
+    // while locks held, would need to check nlock.
     if vecnum == TIMER_INTR {
-        proc::yield_if_running();
+        proc::preempt();
     }
 
-    // Check if the process has been killed since we yielded.
+    // Act on any signals, including a kill, that arrived while
+    // we were in the kernel or since we yielded.
     if frame.is_user() {
-        proc::die_if_dead();
+        signal::deliver(proc::myproc(), frame);
+    }
+}
+
+// Advances the clock by `n` ticks on the boot CPU.
+fn clock(n: u64) {
+    let now = TICKS.with_lock(|ticks| {
+        *ticks = ticks.wrapping_add(n);
+        let now = *ticks;
+        // Sleepers that must sleep on ask again.
+        let deadline = DEADLINE.load(Ordering::SeqCst);
+        if deadline <= now {
+            let _ =
+                DEADLINE.compare_exchange(deadline, u64::MAX, Ordering::SeqCst, Ordering::SeqCst);
+        }
+        proc::wakeup(tickchan());
+        now
+    });
+    for tick in now - n + 1..=now {
+        proc::age(tick);
+    }
+}
+
+// Halts this CPU, with its periodic tick stopped, until an
+// interrupt arrives; it returns with interrupts on.  The boot CPU
+// asks for a timer interrupt at the next deadline, and catches up
+// on the ticks it did not take.
+pub fn idle() {
+    let boot = arch::mycpu_id() == 0;
+    let ticks = if boot {
+        let wait = DEADLINE.load(Ordering::SeqCst).saturating_sub(ticks());
+        wait.clamp(1, MAX_IDLE_TICKS)
+    } else {
+        0
+    };
+    unsafe {
+        xapic::timer_idle(ticks);
+    }
+    arch::wait_for_intr();
+    let elapsed = without_intrs(|| unsafe { xapic::timer_resume() });
+    if boot && elapsed > 0 {
+        clock(elapsed);
     }
 }
 
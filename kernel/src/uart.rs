@@ -112,7 +112,7 @@ impl fmt::Write for Uart {
 
 pub fn interrupt() {
     let mut uart = Uart::uart0();
-    console::interrupt(|| uart.getb());
+    console::interrupt(0, || uart.getb());
     unsafe {
         xapic::eoi();
     }
@@ -1,6 +1,7 @@
 use crate::Result;
 use crate::arch;
 use crate::arch::Page;
+use crate::ipi;
 use crate::kalloc;
 use crate::kmem;
 use crate::param;
@@ -9,7 +10,10 @@ use bitflags::bitflags;
 use core::cmp;
 use core::fmt;
 use core::marker::PhantomData;
-use core::ptr::null_mut;
+use core::ops::Range;
+use core::ptr::{self, null_mut};
+use core::sync::atomic::{AtomicU64, Ordering};
+use syslib::errno::Errno;
 
 bitflags! {
     #[derive(Clone, Copy, Debug)]
@@ -23,6 +27,8 @@ bitflags! {
         const DIRTY   = 1 << 6;
         const HUGE    = 1 << 7;
         const GLOBAL  = 1 << 8;
+        // Shared read-only until written; see PageTable::dup.
+        const COW     = 1 << 9;
         const NX      = 1 << 63;
     }
 }
@@ -57,6 +63,10 @@ impl Entry {
         self.0 == 0
     }
 
+    pub fn is_writable(self) -> bool {
+        self.flags().contains(PageFlags::WRITE)
+    }
+
     fn phys_page_addr(self) -> u64 {
         self.0 & Self::PHYS_PAGE_MASK
     }
@@ -149,6 +159,16 @@ where
         Some(unsafe { &*(raw_ptr as *const Table<L::EntryType>) })
     }
 
+    // As `next`, for changing existing mappings.
+    fn next_present_mut(&mut self, va: usize) -> Option<&mut Table<L::EntryType>> {
+        let entry = self.entries[L::index(va)];
+        if !entry.is_present() {
+            return None;
+        }
+        let raw_ptr = entry.virt_page_addr();
+        Some(unsafe { &mut *(raw_ptr as *mut Table<L::EntryType>) })
+    }
+
     fn next_mut(&mut self, va: usize) -> Option<&mut Table<L::EntryType>> {
         let index = L::index(va);
         let mut entry = self.entries[index];
@@ -168,8 +188,43 @@ where
     }
 }
 
+impl Table<Level4> {
+    // Calls `f` with the address and entry of each page mapped in
+    // `range`, stopping if it returns None.  Tables that are not
+    // there are skipped whole, so that a large, sparse range costs
+    // only as much as is mapped in it.
+    fn for_each_mapped<F>(&mut self, range: Range<usize>, mut f: F) -> Option<()>
+    where
+        F: FnMut(usize, &mut Entry) -> Option<()>,
+    {
+        // The start of the next block of `size` bytes after `va`.
+        let skip = |va: usize, size: usize| (va & !(size - 1)) + size;
+        let mut va = range.start;
+        while va < range.end {
+            let Some(p3) = self.next_present_mut(va) else {
+                va = skip(va, 512 * GIB);
+                continue;
+            };
+            let Some(p2) = p3.next_present_mut(va) else {
+                va = skip(va, GIB);
+                continue;
+            };
+            let Some(p1) = p2.next_present_mut(va) else {
+                va = skip(va, 2 * MIB);
+                continue;
+            };
+            let entry = &mut p1.entries[Level1::index(va)];
+            if entry.is_present() {
+                f(va, entry)?;
+            }
+            va += arch::PAGE_SIZE;
+        }
+        Some(())
+    }
+}
+
 impl Table<Level3> {
-    fn free_user_pages(&mut self, start: usize, end: usize) {
+    fn free_user_pages(&mut self, start: usize, end: usize, sd: &mut Shootdown) {
         if start < end {
             assert_eq!(start % arch::PAGE_SIZE, 0);
             assert_eq!(end % arch::PAGE_SIZE, 0);
@@ -184,10 +239,10 @@ impl Table<Level3> {
                 }
                 let raw_ptr = entry.virt_page_addr();
                 let next_table = unsafe { &mut *(raw_ptr as *mut Table<Level2>) };
-                next_table.free_user_pages(cmp::max(start, va), end);
+                next_table.free_user_pages(cmp::max(start, va), end, sd);
                 if next_table.is_empty() {
-                    kalloc::free(unsafe { &mut *(raw_ptr as *mut arch::Page) });
                     entry.clear();
+                    sd.free(va, unsafe { &mut *(raw_ptr as *mut arch::Page) });
                 }
             }
         }
@@ -195,7 +250,7 @@ impl Table<Level3> {
 }
 
 impl Table<Level2> {
-    fn free_user_pages(&mut self, start: usize, end: usize) {
+    fn free_user_pages(&mut self, start: usize, end: usize, sd: &mut Shootdown) {
         if start < end {
             assert_eq!(start % arch::PAGE_SIZE, 0);
             assert_eq!(end % arch::PAGE_SIZE, 0);
@@ -210,10 +265,10 @@ impl Table<Level2> {
                 }
                 let raw_ptr = entry.virt_page_addr();
                 let next_table = unsafe { &mut *(raw_ptr as *mut Table<Level1>) };
-                next_table.free_user_pages(cmp::max(start, va), end);
+                next_table.free_user_pages(cmp::max(start, va), end, sd);
                 if next_table.is_empty() {
-                    kalloc::free(unsafe { &mut *(raw_ptr as *mut arch::Page) });
                     entry.clear();
+                    sd.free(va, unsafe { &mut *(raw_ptr as *mut arch::Page) });
                 }
             }
         }
@@ -238,7 +293,7 @@ impl Table<Level1> {
         self.entries.iter().all(|entry| entry.is_zero())
     }
 
-    fn free_user_pages(&mut self, start: usize, end: usize) {
+    fn free_user_pages(&mut self, start: usize, end: usize, sd: &mut Shootdown) {
         if start < end {
             assert_eq!(start % arch::PAGE_SIZE, 0);
             assert_eq!(end % arch::PAGE_SIZE, 0);
@@ -250,26 +305,156 @@ impl Table<Level1> {
                     continue;
                 }
                 let raw_ptr = entry.virt_page_addr();
-                kalloc::free(unsafe { &mut *(raw_ptr as *mut arch::Page) });
                 entry.clear();
+                sd.free(va, unsafe { &mut *(raw_ptr as *mut arch::Page) });
+            }
+        }
+    }
+}
+
+// A set of CPUs, by index.
+pub struct CpuSet([AtomicU64; param::NCPUMAX / 64]);
+
+impl CpuSet {
+    pub const fn new() -> CpuSet {
+        CpuSet([const { AtomicU64::new(0) }; param::NCPUMAX / 64])
+    }
+
+    pub fn insert(&self, cpu: usize) {
+        self.0[cpu / 64].fetch_or(1 << (cpu % 64), Ordering::SeqCst);
+    }
+
+    pub fn remove(&self, cpu: usize) {
+        self.0[cpu / 64].fetch_and(!(1 << (cpu % 64)), Ordering::SeqCst);
+    }
+
+    pub fn contains(&self, cpu: usize) -> bool {
+        self.0[cpu / 64].load(Ordering::SeqCst) & (1 << (cpu % 64)) != 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..param::NCPUMAX).filter(|&cpu| self.contains(cpu))
+    }
+}
+
+// The number of pages a shootdown invalidates one at a time, and
+// frees at once, before it flushes.
+const BATCH: usize = 16;
+
+// A batch of user mappings removed or downgraded in a page table.
+// Each CPU that has the table loaded may still hold them in its
+// TLB, so they are invalidated there, by IPI on other CPUs, before
+// any page they mapped is freed for reuse.
+pub struct Shootdown<'a> {
+    cpus: &'a CpuSet,
+    vas: [usize; BATCH],
+    nva: usize,
+    // Too many pages to invalidate one at a time: flush all.
+    all: bool,
+    pages: [*mut Page; BATCH],
+    npage: usize,
+}
+
+impl<'a> Shootdown<'a> {
+    pub fn new(cpus: &'a CpuSet) -> Shootdown<'a> {
+        Shootdown {
+            cpus,
+            vas: [0; BATCH],
+            nva: 0,
+            all: false,
+            pages: [null_mut(); BATCH],
+            npage: 0,
+        }
+    }
+
+    pub fn invalidate_all(&mut self) {
+        self.all = true;
+    }
+
+    // Notes that the mapping of `va` was removed or downgraded.
+    pub fn invalidate(&mut self, va: usize) {
+        if self.nva == BATCH {
+            self.all = true;
+        } else {
+            self.vas[self.nva] = va;
+            self.nva += 1;
+        }
+    }
+
+    // Notes that `page`, mapped at `va` or holding a page table
+    // for it, was unmapped, and frees it once that is flushed.
+    fn free(&mut self, va: usize, page: &'static mut Page) {
+        if self.npage == BATCH {
+            self.flush();
+        }
+        self.invalidate(va);
+        self.pages[self.npage] = page;
+        self.npage += 1;
+    }
+
+    fn flush_local(&self) {
+        unsafe {
+            if self.all {
+                arch::flush_tlb();
+            } else {
+                self.vas[..self.nva].iter().for_each(|&va| arch::invlpg(va));
+            }
+        }
+    }
+
+    // Invalidates the batch on every CPU that has the table loaded,
+    // then frees its pages.
+    pub fn flush(&mut self) {
+        fn flush_remote(arg: usize) {
+            let sd = unsafe { &*ptr::with_exposed_provenance::<Shootdown>(arg) };
+            sd.flush_local();
+        }
+        if self.nva != 0 || self.all {
+            let me = arch::mycpu_id() as usize;
+            for cpu in self.cpus.iter() {
+                if cpu == me {
+                    self.flush_local();
+                } else {
+                    let arg = (self as *const Shootdown).expose_provenance();
+                    ipi::call(ipi::Target::Cpu(cpu), flush_remote, arg);
+                }
             }
         }
+        for &page in &self.pages[..self.npage] {
+            kalloc::free(unsafe { &mut *page });
+        }
+        self.nva = 0;
+        self.all = false;
+        self.npage = 0;
     }
 }
 
-pub struct PageTable(*mut Table<Level4>);
+impl Drop for Shootdown<'_> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+pub struct PageTable {
+    root: *mut Table<Level4>,
+    // The CPUs that have the table loaded.
+    cpus: CpuSet,
+}
 
 impl PageTable {
     pub const fn empty() -> PageTable {
-        PageTable(null_mut())
+        PageTable {
+            root: null_mut(),
+            cpus: CpuSet::new(),
+        }
     }
 
     fn as_ref(&self) -> Option<&Table<Level4>> {
-        unsafe { self.0.as_ref() }
+        unsafe { self.root.as_ref() }
     }
 
     fn as_mut(&mut self) -> Option<&mut Table<Level4>> {
-        unsafe { self.0.as_mut() }
+        unsafe { self.root.as_mut() }
     }
 
     #[allow(dead_code)]
@@ -292,10 +477,18 @@ impl PageTable {
             .and_then(|p1| p1.entry(va))
     }
 
+    fn entry_mut_for(&mut self, va: usize) -> Option<&mut Entry> {
+        self.as_mut()?
+            .next_present_mut(va)
+            .and_then(|p3| p3.next_present_mut(va))
+            .and_then(|p2| p2.next_present_mut(va))
+            .and_then(|p1| p1.entry_mut(va))
+    }
+
     pub fn map_to(&mut self, pa: u64, va: usize, flags: PageFlags) -> Result<()> {
         if let Some(entry) = self
             .as_mut()
-            .ok_or("No page table to map into")?
+            .ok_or(Errno::ENOMEM)?
             .next_mut(va)
             .and_then(|p3| p3.next_mut(va))
             .and_then(|p2| p2.next_mut(va))
@@ -306,7 +499,7 @@ impl PageTable {
             volatile::write(entry, new_entry);
             return Ok(());
         }
-        Err("Allocation failed")
+        Err(Errno::ENOMEM)
     }
 
     pub fn map_phys_range(&mut self, start: u64, end: u64, flags: PageFlags) -> Result<()> {
@@ -328,35 +521,125 @@ impl PageTable {
         let table = unsafe { &mut *(l4page as *mut _ as *mut Table<Level4>) };
         // Copy kernel portion.
         table.entries[256..512].copy_from_slice(&src.entries[256..512]);
-        Some(PageTable(table))
-    }
-
-    pub fn dup(&self, size: usize) -> Option<PageTable> {
-        fn copy_region(
-            src: &PageTable,
+        Some(PageTable {
+            root: table,
+            cpus: CpuSet::new(),
+        })
+    }
+
+    // Copies the user part of the table, in `regions`, for a child
+    // process.  The pages are shared, and in the regions that are
+    // not shared with the child, those that are writable become
+    // read-only in both tables until the first write to them
+    // copies them; see `copy_on_write`.  Such pages that cannot be
+    // shared are copied now, and those never touched are left for
+    // the child to fault in.
+    pub fn dup<I>(&mut self, regions: I) -> Option<PageTable>
+    where
+        I: IntoIterator<Item = (Range<usize>, bool)>,
+    {
+        fn share_region(
+            src: &mut PageTable,
             dst: &mut PageTable,
-            range: core::ops::Range<usize>,
+            range: Range<usize>,
+            shared: bool,
         ) -> Option<()> {
-            for k in range.step_by(arch::PAGE_SIZE) {
-                let entry = src.entry_for(k).expect("entry should exist");
-                assert!(entry.is_present(), "dup: page not present");
-                let page = kalloc::alloc()?;
-                unsafe {
-                    use core::intrinsics::volatile_copy_memory;
-                    let src = entry.virt_page_addr() as *const arch::Page;
-                    volatile_copy_memory(page, src, 1);
-                }
-                if dst.map_to(page.phys_addr(), k, entry.flags()).is_err() {
+            src.as_mut()?.for_each_mapped(range, |k, entry| {
+                let page = unsafe { &mut *(entry.virt_page_addr() as *mut Page) };
+                let mut flags = entry.flags();
+                let page = if kalloc::share(page) {
+                    if !shared && flags.contains(PageFlags::WRITE) {
+                        flags.remove(PageFlags::WRITE);
+                        flags.insert(PageFlags::COW);
+                        volatile::write(entry, Entry::new(page.phys_addr(), flags));
+                    }
+                    page
+                } else if shared {
+                    return None;
+                } else {
+                    let copy = kalloc::alloc()?;
+                    unsafe {
+                        use core::intrinsics::volatile_copy_memory;
+                        volatile_copy_memory(copy, page, 1);
+                    }
+                    copy
+                };
+                if dst.map_to(page.phys_addr(), k, flags).is_err() {
                     kalloc::free(page);
                     return None;
                 }
-            }
-            Some(())
+                Some(())
+            })
         }
         let mut table = self.dup_kern()?;
-        copy_region(self, &mut table, 0..size)?;
-        copy_region(self, &mut table, param::USERSTACK..param::USEREND)?;
-        Some(table)
+        let shared = regions
+            .into_iter()
+            .try_for_each(|(range, shared)| share_region(self, &mut table, range, shared));
+        // Our TLB may hold entries that were writable.
+        self.shootdown().invalidate_all();
+        shared.map(|()| table)
+    }
+
+    // Resolves a write fault at `va` on a page shared copy-on-write
+    // by giving this table a writable copy of its own, or by making
+    // the page writable if no other table still shares it.
+    pub fn copy_on_write(&mut self, va: usize) -> Result<()> {
+        let va = arch::page_round_down(va);
+        let entry = self
+            .entry_mut_for(va)
+            .filter(|entry| entry.is_present() && entry.flags().contains(PageFlags::COW))
+            .ok_or(Errno::EFAULT)?;
+        let old = unsafe { &mut *(entry.virt_page_addr() as *mut Page) };
+        let mut flags = entry.flags();
+        flags.remove(PageFlags::COW);
+        flags.insert(PageFlags::WRITE);
+        if !kalloc::shared(old) {
+            // The fault flushed the read-only entry from our TLB.
+            volatile::write(entry, Entry::new(old.phys_addr(), flags));
+            return Ok(());
+        }
+        let page = kalloc::alloc().ok_or(Errno::ENOMEM)?;
+        unsafe {
+            use core::intrinsics::volatile_copy_memory;
+            volatile_copy_memory(page, old, 1);
+        }
+        volatile::write(entry, Entry::new(page.phys_addr(), flags));
+        self.shootdown().free(va, old);
+        Ok(())
+    }
+
+    // Maps a zeroed page at `va`, where there is none, on the first
+    // touch of a page that the process may use but has not.
+    pub fn map_zeroed(&mut self, va: usize, flags: PageFlags) -> Result<()> {
+        let va = arch::page_round_down(va);
+        if self.entry_for(va).is_some() {
+            return Err(Errno::EEXIST);
+        }
+        let page = kalloc::alloc().ok_or(Errno::ENOMEM)?;
+        if let Err(status) = self.map_to(page.phys_addr(), va, flags) {
+            kalloc::free(page);
+            return Err(status);
+        }
+        Ok(())
+    }
+
+    // Gives the pages mapped in `range` the protection in `flags`,
+    // except that those shared copy-on-write stay read-only.
+    pub fn protect(&mut self, range: Range<usize>, flags: PageFlags) {
+        let Some(root) = (unsafe { self.root.as_mut() }) else {
+            return;
+        };
+        let mut sd = Shootdown::new(&self.cpus);
+        root.for_each_mapped(range, |va, entry| {
+            let cow = entry.flags() & PageFlags::COW;
+            let mut new = flags | cow | PageFlags::PRESENT;
+            if !cow.is_empty() {
+                new.remove(PageFlags::WRITE);
+            }
+            volatile::write(entry, Entry::new(entry.phys_page_addr(), new));
+            sd.invalidate(va);
+            Some(())
+        });
     }
 
     pub fn alloc_user(
@@ -366,7 +649,7 @@ impl PageTable {
         flags: PageFlags,
     ) -> Result<usize> {
         if new_size > param::USEREND {
-            return Err("alloc_user: new size extends into kernel");
+            return Err(Errno::ENOMEM);
         }
         if new_size <= old_size {
             return Ok(old_size);
@@ -376,7 +659,7 @@ impl PageTable {
         for user_addr in (old_end..new_end).step_by(arch::PAGE_SIZE) {
             let Some(page) = kalloc::alloc() else {
                 self.dealloc_user(new_size, old_size).expect("user dealloc");
-                return Err("alloc_user: failed to alloc user page");
+                return Err(Errno::ENOMEM);
             };
             if let Err(status) = self.map_to(page.phys_addr(), user_addr, flags | PageFlags::USER) {
                 self.dealloc_user(new_size, old_size).expect("user dealloc");
@@ -398,7 +681,8 @@ impl PageTable {
         if start < end {
             let start = arch::page_round_up(start);
             let end = arch::page_round_up(end);
-            let pgtbl = unsafe { self.0.as_mut().unwrap() };
+            let pgtbl = unsafe { self.root.as_mut().unwrap() };
+            let mut sd = Shootdown::new(&self.cpus);
             let lstart = start & !(512 * GIB - 1);
             for va in (lstart..end).step_by(512 * GIB) {
                 let end = cmp::min(end, va + 512 * GIB);
@@ -409,19 +693,19 @@ impl PageTable {
                 }
                 let raw_ptr = entry.virt_page_addr();
                 let next_table = unsafe { &mut *(raw_ptr as *mut Table<Level3>) };
-                next_table.free_user_pages(cmp::max(start, va), end);
+                next_table.free_user_pages(cmp::max(start, va), end, &mut sd);
                 if next_table.is_empty() {
-                    kalloc::free(unsafe { &mut *(raw_ptr as *mut arch::Page) });
                     entry.clear();
+                    sd.free(va, unsafe { &mut *(raw_ptr as *mut arch::Page) });
                 }
             }
         }
     }
 
     pub fn user_addr_to_kern_page(&self, va: usize) -> Result<&'static mut Page> {
-        let entry = self.entry_for(va).ok_or("no mapping for user address")?;
+        let entry = self.entry_for(va).ok_or(Errno::EFAULT)?;
         if !entry.is_present() || !entry.is_user() {
-            return Err("bad user address");
+            return Err(Errno::EFAULT);
         }
         Ok(unsafe { &mut *(entry.virt_page_addr() as *mut Page) })
     }
@@ -445,15 +729,27 @@ impl PageTable {
     /// The caller must ensure that the table is valid and correctly
     /// maps the address space.
     pub unsafe fn switch(&self) {
+        self.cpus.insert(arch::mycpu_id() as usize);
         unsafe {
             switch(self);
         }
     }
+
+    // Notes that this CPU has since loaded another table.
+    pub fn unload(&self) {
+        self.cpus.remove(arch::mycpu_id() as usize);
+    }
+
+    // Starts a batch of changes to the user mappings, which are
+    // flushed from the TLBs that may hold them when it is dropped.
+    pub fn shootdown(&self) -> Shootdown<'_> {
+        Shootdown::new(&self.cpus)
+    }
 }
 
 impl fmt::Debug for PageTable {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:x}", self.0.addr())
+        write!(f, "{:x}", self.root.addr())
     }
 }
 
@@ -471,7 +767,7 @@ pub unsafe fn init(kpage_table: &mut PageTable) {
 
     let kpage_root = kalloc::alloc().expect("alloc kernel page table root");
     kpage_root.clear();
-    kpage_table.0 = kpage_root as *mut _ as *mut Table<Level4>;
+    kpage_table.root = kpage_root as *mut _ as *mut Table<Level4>;
 
     unsafe {
         init_pat();
@@ -553,9 +849,7 @@ pub unsafe fn init(kpage_table: &mut PageTable) {
 }
 
 pub fn new_pgtbl() -> Result<PageTable> {
-    crate::kpgtbl()
-        .dup_kern()
-        .ok_or("exec: cannot allocate new page table")
+    crate::kpgtbl().dup_kern().ok_or(Errno::ENOMEM)
 }
 
 pub unsafe fn switch(kpage_table: &PageTable) {
@@ -566,7 +860,7 @@ pub unsafe fn switch(kpage_table: &PageTable) {
 
 pub fn free(pgtbl: &mut PageTable) {
     pgtbl.free_user_pages(0, param::USEREND);
-    let raw_ptr = pgtbl.0.addr();
+    let raw_ptr = pgtbl.root.addr();
     kalloc::free(unsafe { &mut *(raw_ptr as *mut arch::Page) });
 }
 
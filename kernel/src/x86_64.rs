@@ -1,5 +1,6 @@
 use crate::FromZeros;
 use crate::kmem;
+use crate::param;
 use crate::proc;
 use crate::spinlock::without_intrs;
 use crate::trap::trap;
@@ -279,7 +280,7 @@ mod segment {
     pub const _NULL_SEL: u16 = 0 << 3;
     pub const KTEXT_SEL: u16 = 1 << 3;
     pub const _KDATA_SEL: u16 = 2 << 3;
-    pub const _UDATA_SEL: u16 = 3 << 3;
+    pub const UDATA_SEL: u16 = 3 << 3;
     pub const UTEXT_SEL: u16 = 4 << 3;
     pub const TASK_SEL: u16 = 6 << 3;
 
@@ -377,6 +378,7 @@ unsafe fn lidt(idt: &'static IDT) {
 #[repr(C, align(4096))]
 pub struct CPU {
     self_ptr: *mut CPU,
+    // The user stack pointer, while entering or leaving a system call.
     ureg: u64,
     kstack: u64,
     id: u32,
@@ -598,6 +600,19 @@ pub unsafe fn load_page_table(pt: u64) {
     }
 }
 
+pub unsafe fn invlpg(va: usize) {
+    unsafe {
+        asm!("invlpg ({})", in(reg) va, options(att_syntax, nostack));
+    }
+}
+
+// Flushes all but global translations from the TLB.
+pub unsafe fn flush_tlb() {
+    unsafe {
+        asm!("movq %cr3, {0}; movq {0}, %cr3", out(reg) _, options(att_syntax, nostack));
+    }
+}
+
 pub fn fault_addr() -> usize {
     let addr: usize;
     unsafe {
@@ -619,6 +634,23 @@ pub fn cpu_relax() {
     }
 }
 
+// Enables interrupts and waits for one.  An interrupt that is
+// pending wakes us, since `sti` takes effect after `hlt` starts.
+pub fn wait_for_intr() {
+    unsafe {
+        asm!("sti; hlt");
+    }
+}
+
+// Stops this CPU for good.
+pub fn halt() -> ! {
+    loop {
+        unsafe {
+            asm!("cli; hlt");
+        }
+    }
+}
+
 pub unsafe fn rdmsr(index: u32) -> u64 {
     let val_lo: u32;
     let val_hi: u32;
@@ -766,6 +798,119 @@ impl TrapFrame {
     pub unsafe fn set_rdi(&mut self, rdi: u64) {
         self.rdi = rdi;
     }
+
+    pub fn set_rax(&mut self, rax: u64) {
+        self.rax = rax;
+    }
+
+    pub fn stack(&self) -> u64 {
+        self.rsp
+    }
+
+    pub fn set_pc(&mut self, pc: u64) {
+        self.rip = pc;
+    }
+
+    // Whether the frame returns to, and with its stack in, the
+    // user's half of the address space.  Returning to an address
+    // outside it faults in the kernel, with the user's GS loaded.
+    pub fn is_user_return(&self) -> bool {
+        let end = param::USEREND as u64;
+        self.rip < end && self.rsp < end
+    }
+
+    // Makes a frame that came from user space safe to return
+    // through: the segments are forced to the user's, and the
+    // flags to those a user can set, with interrupts enabled.
+    pub fn sanitize(&mut self) {
+        const RPL_USER: u64 = 0x3;
+        let user = RFlags::CARRY
+            | RFlags::PARITY
+            | RFlags::ADJUST
+            | RFlags::ZERO
+            | RFlags::SIGN
+            | RFlags::DIRECTION
+            | RFlags::OVERFLOW;
+        self.cs = u64::from(segment::UTEXT_SEL) | RPL_USER;
+        self.ss = u64::from(segment::UDATA_SEL) | RPL_USER;
+        self.ds = self.ss;
+        self.es = self.ss;
+        self.fs = self.ss;
+        self.gs = self.ss;
+        self.rflags = (self.rflags & user.bits()) | RFlags::INTR_EN.bits() | 2;
+    }
+}
+
+#[cfg(test)]
+mod trapframe_tests {
+    use super::{RFlags, TrapFrame, segment};
+
+    #[test]
+    fn sanitizes_hostile_frame() {
+        let mut tf: TrapFrame = unsafe { core::mem::zeroed() };
+        tf.cs = u64::from(segment::KTEXT_SEL);
+        tf.ss = 0;
+        tf.ds = 0xffff;
+        tf.es = u64::from(segment::_KDATA_SEL);
+        tf.fs = 0x1234;
+        tf.gs = !0;
+        tf.rflags = !0;
+        tf.sanitize();
+        let udata = u64::from(segment::UDATA_SEL) | 3;
+        assert_eq!(tf.cs, u64::from(segment::UTEXT_SEL) | 3);
+        assert_eq!([tf.ss, tf.ds, tf.es, tf.fs, tf.gs], [udata; 5]);
+        assert!(tf.is_user());
+        let flags = RFlags::from_bits_retain(tf.rflags);
+        assert!(flags.contains(RFlags::INTR_EN));
+        assert!(!flags.intersects(RFlags::TRAP | RFlags::IOPL));
+
+        tf.rip = 0x0000_8000_0000_0000;
+        tf.rsp = 0x1000;
+        assert!(!tf.is_user_return());
+        tf.rip = 0x1000;
+        assert!(tf.is_user_return());
+        tf.rsp = !0;
+        assert!(!tf.is_user_return());
+    }
+}
+
+// Returns to user space restoring every register from `frame`,
+// which must be the one at the top of the current kernel stack.
+// System calls normally return through `syscallret`, which does
+// not restore the registers a system call may clobber; this is
+// for when the frame has been replaced wholesale, as by
+// sigreturn.
+#[unsafe(naked)]
+pub unsafe extern "C" fn trapret(frame: *const TrapFrame) -> ! {
+    naked_asm!(
+        r#"
+        cli
+        movq %rdi, %rsp
+        swapgs
+        popq %rax
+        popq %rbx
+        popq %rcx
+        popq %rdx
+        popq %rsi
+        popq %rdi
+        popq %rbp
+        popq %r8
+        popq %r9
+        popq %r10
+        popq %r11
+        popq %r12
+        popq %r13
+        popq %r14
+        popq %r15
+        movw 8(%rsp), %es
+        movw (%rsp), %ds
+        addq $32, %rsp
+        // Pop vector and error.
+        addq $16, %rsp
+        iretq
+        "#,
+        options(att_syntax)
+    );
 }
 
 const TRAPFRAME_VECTOR_OFFSET: usize = 0x98;
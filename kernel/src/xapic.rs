@@ -5,11 +5,12 @@
 
 use crate::arch;
 use crate::param;
+use crate::spinlock::without_intrs;
 use crate::trap;
 use crate::volatile;
 use bitflags::bitflags;
 use core::ptr::null_mut;
-use core::sync::atomic::{AtomicPtr, Ordering};
+use core::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
 use core::time::Duration;
 
 enum XAPICRegs {
@@ -27,7 +28,7 @@ enum XAPICRegs {
     _LINT1 = 0x0360 / 4,
     _ERROR = 0x0370 / 4,
     TICR = 0x0380 / 4,
-    _TCCR = 0x0390 / 4,
+    TCCR = 0x0390 / 4,
     TDCR = 0x03E0 / 4,
 }
 const SIZE: usize = (0x03E0 + 4) / 4;
@@ -38,7 +39,13 @@ const LEVEL: u32 = 1 << 15; // Level (vs edge).
 const ASSERT: u32 = 1 << 14; // Assert interrupt (vs deassert)
 const DEASSERT: u32 = 0; // Assert interrupt (vs deassert)
 const DELIVS: u32 = 0x0000_1000; // Delivery status
+const ALL: u32 = 0b10 << 18; // All including self
+const OTHERS: u32 = 0b11 << 18; // All excluding self
 const PERIODIC: u32 = 0x0002_0000;
+const MASKED: u32 = 0x0001_0000;
+
+// Timer counts per clock tick; we assume a 2GHz clock.
+const PERIOD: u32 = 2_000_000_000 / param::HZ as u32;
 
 pub const INTR_TIMER: u32 = 8;
 const SPURIOUS_VEC: u32 = trap::INTR0 + 31;
@@ -47,6 +54,19 @@ type XAPICMMIO = [u32; SIZE];
 
 static XAPIC: AtomicPtr<XAPICMMIO> = AtomicPtr::new(null_mut());
 
+// For each CPU whose periodic tick is stopped, the count its timer
+// was started from then, if any.
+static IDLE_COUNT: [AtomicU32; param::NCPUMAX] = [const { AtomicU32::new(0) }; param::NCPUMAX];
+const STOPPED: u32 = u32::MAX;
+
+// The destination of an interprocessor interrupt.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dest {
+    Apic(u32),
+    All,
+    Others,
+}
+
 bitflags! {
     pub struct SVRFlags: u32 {
         const ENABLE = 0x0000_0100;
@@ -68,8 +88,8 @@ pub unsafe fn init() {
         write(XAPICRegs::SVR, SVRFlags::ENABLE.bits() | SPURIOUS_VEC);
 
         write(XAPICRegs::TDCR, 0xb);
-        write(XAPICRegs::TIMER, PERIODIC | trap::INTR0 + INTR_TIMER);
-        write(XAPICRegs::TICR, 2_000_000_000 / 1000); // assume 2GHz clock
+        write(XAPICRegs::TIMER, PERIODIC | (trap::INTR0 + INTR_TIMER));
+        write(XAPICRegs::TICR, PERIOD);
 
         write(XAPICRegs::ESR, 0);
         write(XAPICRegs::ESR, 0);
@@ -80,6 +100,11 @@ pub unsafe fn init() {
     }
 }
 
+// Whether this CPU's APIC may be used yet.
+pub fn ready() -> bool {
+    !XAPIC.load(Ordering::Acquire).is_null()
+}
+
 fn xapic_ref() -> &'static XAPICMMIO {
     let xapic = XAPIC.load(Ordering::Acquire);
     assert_ne!(xapic, null_mut());
@@ -137,3 +162,60 @@ pub unsafe fn send_sipi(apic_id: u32, vector: u8) {
         wait_delivery();
     }
 }
+
+// Sends a fixed interrupt on `vector` to `dest`.  Interrupts are
+// off so that a handler cannot send one between our writes.
+pub unsafe fn send_ipi(dest: Dest, vector: u32) {
+    let (hi, lo) = match dest {
+        Dest::Apic(apic_id) => (apic_id << 24, vector),
+        Dest::All => (0, ALL | vector),
+        Dest::Others => (0, OTHERS | vector),
+    };
+    without_intrs(|| unsafe {
+        write(XAPICRegs::ICRHI, hi);
+        write(XAPICRegs::ICRLO, lo);
+        wait_delivery();
+    });
+}
+
+// The most clock ticks a one-shot timer can count.
+const MAX_ONESHOT: u64 = (u32::MAX / PERIOD) as u64;
+
+// Stops this CPU's periodic tick, and asks for a single timer
+// interrupt after `ticks` ticks, or none if 0.
+pub unsafe fn timer_idle(ticks: u64) {
+    let cpu = arch::mycpu_id() as usize;
+    unsafe {
+        if ticks == 0 {
+            IDLE_COUNT[cpu].store(STOPPED, Ordering::Relaxed);
+            write(XAPICRegs::TIMER, MASKED | (trap::INTR0 + INTR_TIMER));
+            write(XAPICRegs::TICR, 0);
+        } else {
+            let count = ticks.min(MAX_ONESHOT) as u32 * PERIOD;
+            IDLE_COUNT[cpu].store(count, Ordering::Relaxed);
+            write(XAPICRegs::TIMER, trap::INTR0 + INTR_TIMER);
+            write(XAPICRegs::TICR, count);
+        }
+    }
+}
+
+// Restarts this CPU's periodic tick if it was stopped, returning
+// the number of ticks, to the nearest, that the one-shot timer
+// counted meanwhile.
+pub unsafe fn timer_resume() -> u64 {
+    let cpu = arch::mycpu_id() as usize;
+    let count = IDLE_COUNT[cpu].swap(0, Ordering::Relaxed);
+    if count == 0 {
+        return 0;
+    }
+    unsafe {
+        let elapsed = if count == STOPPED {
+            0
+        } else {
+            count - read(XAPICRegs::TCCR)
+        };
+        write(XAPICRegs::TIMER, PERIODIC | (trap::INTR0 + INTR_TIMER));
+        write(XAPICRegs::TICR, PERIOD);
+        u64::from((elapsed + PERIOD / 2) / PERIOD)
+    }
+}
@@ -0,0 +1,28 @@
+// Operations for the KEYMAP system call, which changes how the
+// keyboard's keys are translated into input for the console.
+//
+// SELECT makes one of the kernel's built-in keymaps current,
+// given its name: `us` (the default) or `trq` (Turkish Q).
+// LOAD makes a `Keymap` supplied by the caller current.
+pub const SELECT: usize = 0;
+pub const LOAD: usize = 1;
+
+// The number of keys a keymap covers: those with one-byte PC
+// scancodes (set 1).  Keys with extended scancodes, such as the
+// arrows, mean the same in every layout.
+pub const NKEYS: usize = 128;
+
+// The byte each key produces, indexed by scancode: alone, with
+// Shift, and with AltGr (the right-hand Alt key).  0 means that
+// the key produces nothing; keys without an AltGr meaning behave
+// as though AltGr were not held.  Caps Lock applies Shift to keys
+// whose unshifted byte is a lower-case ASCII letter, or above
+// 0x7F: a layout's national letters.  Bytes above 0x7F are
+// characters in the console's code page, e.g. CP857 for Turkish.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keymap {
+    pub normal: [u8; NKEYS],
+    pub shift: [u8; NKEYS],
+    pub altgr: [u8; NKEYS],
+}
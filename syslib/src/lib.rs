@@ -1,4 +1,13 @@
 #![no_std]
 
+pub mod errno;
+pub mod keymap;
+pub mod mman;
+pub mod resource;
+pub mod screen;
+pub mod signal;
 pub mod stat;
 pub mod syscall;
+pub mod sysinfo;
+pub mod termios;
+pub mod wait;
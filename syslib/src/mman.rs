@@ -0,0 +1,14 @@
+// Protections for MMAP and MPROTECT, as on Linux.
+pub const PROT_NONE: u32 = 0;
+pub const PROT_READ: u32 = 1;
+pub const PROT_WRITE: u32 = 2;
+pub const PROT_EXEC: u32 = 4;
+
+// Flags for MMAP.  A mapping is either shared with the children
+// that inherit it or private to each process; FIXED places it at
+// exactly the address given, and ANONYMOUS maps memory, zeroed,
+// rather than a file.
+pub const MAP_SHARED: u32 = 0x01;
+pub const MAP_PRIVATE: u32 = 0x02;
+pub const MAP_FIXED: u32 = 0x10;
+pub const MAP_ANONYMOUS: u32 = 0x20;
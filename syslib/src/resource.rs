@@ -0,0 +1,59 @@
+// Whose usage GETRUSAGE reports: the calling process's own, or
+// that of the children it has waited for.
+pub const RUSAGE_SELF: i32 = 0;
+pub const RUSAGE_CHILDREN: i32 = -1;
+
+// Whose priority SETPRIORITY and GETPRIORITY concern: a process,
+// or a process group.  A `who` of 0 means the caller's own.
+pub const PRIO_PROCESS: u32 = 0;
+pub const PRIO_PGRP: u32 = 1;
+
+// The range of nice values; lower values run first.
+pub const NICE_MIN: i32 = -20;
+pub const NICE_MAX: i32 = 19;
+
+// Resource usage of a process: the clock ticks it spent running
+// in user space and in the kernel, the largest its memory grew,
+// in KiB, and how often it gave up the processor, by sleeping or
+// by being preempted.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rusage {
+    pub utime: u64,
+    pub stime: u64,
+    pub maxrss: u64,
+    pub nvcsw: u64,
+    pub nivcsw: u64,
+}
+
+impl Rusage {
+    pub const fn new() -> Rusage {
+        Rusage {
+            utime: 0,
+            stime: 0,
+            maxrss: 0,
+            nvcsw: 0,
+            nivcsw: 0,
+        }
+    }
+
+    // Adds the usage of `other`, as when a child is reaped.
+    pub fn add(&mut self, other: &Rusage) {
+        self.utime += other.utime;
+        self.stime += other.stime;
+        self.maxrss = self.maxrss.max(other.maxrss);
+        self.nvcsw += other.nvcsw;
+        self.nivcsw += other.nivcsw;
+    }
+}
+
+// Process times for TIMES, in clock ticks: our own, and those of
+// the children we have waited for.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tms {
+    pub utime: u64,
+    pub stime: u64,
+    pub cutime: u64,
+    pub cstime: u64,
+}
@@ -0,0 +1,24 @@
+// Operations for the SCREEN system call, which lets programs
+// draw directly on the screen of a console they have open for
+// writing.
+pub const CLEAR: usize = 0;
+pub const TEXT: usize = 1;
+pub const BOX: usize = 2;
+pub const CURSOR: usize = 3;
+
+pub const WIDTH: u32 = 80;
+pub const HEIGHT: u32 = 25;
+
+// The arguments of an operation.  TEXT and CURSOR use only the
+// position, and BOX the position and size; CLEAR ignores the
+// request entirely.  For TEXT, `text` is the address of a
+// NUL-terminated string.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Request {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+    pub text: usize,
+}
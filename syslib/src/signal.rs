@@ -0,0 +1,73 @@
+// Signal numbers, as on Linux.
+pub const SIGHUP: u32 = 1;
+pub const SIGINT: u32 = 2;
+pub const SIGQUIT: u32 = 3;
+pub const SIGILL: u32 = 4;
+pub const SIGTRAP: u32 = 5;
+pub const SIGABRT: u32 = 6;
+pub const SIGBUS: u32 = 7;
+pub const SIGFPE: u32 = 8;
+pub const SIGKILL: u32 = 9;
+pub const SIGUSR1: u32 = 10;
+pub const SIGSEGV: u32 = 11;
+pub const SIGUSR2: u32 = 12;
+pub const SIGPIPE: u32 = 13;
+pub const SIGALRM: u32 = 14;
+pub const SIGTERM: u32 = 15;
+pub const SIGCHLD: u32 = 17;
+pub const SIGCONT: u32 = 18;
+pub const SIGSTOP: u32 = 19;
+pub const SIGTSTP: u32 = 20;
+pub const SIGTTIN: u32 = 21;
+pub const SIGTTOU: u32 = 22;
+pub const SIGURG: u32 = 23;
+pub const SIGWINCH: u32 = 28;
+
+// Signals are numbered from 1 to NSIG - 1.
+pub const NSIG: u32 = 32;
+
+// A set of signals has bit `sig - 1` set for each member.
+pub type SigSet = u64;
+
+pub const fn sigbit(sig: u32) -> SigSet {
+    1 << (sig - 1)
+}
+
+// Special values of `SigAction::handler`.
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+// Flags in `SigAction::flags`.  With SA_NODEFER, a signal is not
+// blocked while its handler runs; with SA_RESETHAND, the action
+// reverts to SIG_DFL once the handler is called.
+pub const SA_NODEFER: u64 = 0x4000_0000;
+pub const SA_RESETHAND: u64 = 0x8000_0000;
+
+// How SIGPROCMASK changes the set of blocked signals.
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+// What to do with a signal.  The handler is called with the
+// signal number, and returns to `restorer`, which must make the
+// SIGRETURN system call; ulib supplies it.  `mask` is added to
+// the blocked signals while the handler runs.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SigAction {
+    pub handler: usize,
+    pub flags: u64,
+    pub mask: SigSet,
+    pub restorer: usize,
+}
+
+impl SigAction {
+    pub const fn new() -> SigAction {
+        SigAction {
+            handler: SIG_DFL,
+            flags: 0,
+            mask: 0,
+            restorer: 0,
+        }
+    }
+}
@@ -19,8 +19,59 @@ pub const UNLINK: usize = 18;
 pub const LINK: usize = 19;
 pub const MKDIR: usize = 20;
 pub const CLOSE: usize = 21;
+pub const SCREEN: usize = 22;
+pub const KEYMAP: usize = 23;
+pub const IOCTL: usize = 24;
+pub const SYSINFO: usize = 25;
+pub const SIGACTION: usize = 26;
+pub const SIGPROCMASK: usize = 27;
+pub const SIGRETURN: usize = 28;
+pub const SETPGID: usize = 29;
+pub const GETPGID: usize = 30;
+pub const SETSID: usize = 31;
+pub const WAIT4: usize = 32;
+pub const TIMES: usize = 33;
+pub const GETRUSAGE: usize = 34;
+pub const SETPRIORITY: usize = 35;
+pub const GETPRIORITY: usize = 36;
+pub const MMAP: usize = 37;
+pub const MUNMAP: usize = 38;
+pub const MPROTECT: usize = 39;
+
+// The arguments of a system call, in the registers that carry them
+// as on Linux: %rdi, %rsi, %rdx, %r10, %r8 and %r9.  The number is
+// in %rax, and the result is returned there.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Args(pub [usize; 6]);
+
+impl Args {
+    // Decodes argument `k` as the type the system call takes.
+    pub fn get<T: Arg>(&self, k: usize) -> T {
+        T::from_reg(self.0[k])
+    }
+}
+
+// A type a system call argument may be taken as, from the low bits
+// of its register.
+pub trait Arg {
+    fn from_reg(reg: usize) -> Self;
+}
+
+macro_rules! arg {
+    ($($t:ty),*) => {
+        $(impl Arg for $t {
+            fn from_reg(reg: usize) -> $t {
+                reg as $t
+            }
+        })*
+    };
+}
+
+arg!(usize, isize, u64, i64, u32, i32);
 
 pub const O_READ: usize = 0x0000;
 pub const O_WRITE: usize = 0x0001;
 pub const O_RDWR: usize = 0x0002;
+pub const O_NOCTTY: usize = 0x0100;
 pub const O_CREATE: usize = 0x0200;
@@ -0,0 +1,97 @@
+// Operations for the SYSINFO system call, which describes the
+// running system.  SYSTEM fills in a `Sysinfo`; PROCS fills in
+// an array of `ProcInfo`, one for each process, returning how
+// many it filled.
+pub const SYSTEM: usize = 0;
+pub const PROCS: usize = 1;
+
+pub const MAXREGIONS: usize = 16;
+
+// Kinds of memory region, from the boot loader's memory map.
+pub const MEM_USABLE: u32 = 1;
+pub const MEM_SYSTEM: u32 = 2;
+pub const MEM_RESERVED: u32 = 3;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemRegion {
+    pub start: u64,
+    pub end: u64,
+    pub typ: u32,
+}
+
+// Strings are NUL-padded.  `uptime` is in clock ticks, of which
+// there are `hz` a second.  The disk fields are zero when there
+// is no disk.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Sysinfo {
+    pub version: [u8; 16],
+    pub ncpu: u32,
+    pub nproc: u32,
+    pub hz: u64,
+    pub uptime: u64,
+    pub page_size: u64,
+    pub total_pages: u64,
+    pub free_pages: u64,
+    pub nregions: u32,
+    pub regions: [MemRegion; MAXREGIONS],
+    pub disk_sectors: u64,
+    pub disk_model: [u8; 40],
+    pub disk_serial: [u8; 20],
+}
+
+impl Sysinfo {
+    pub const fn new() -> Sysinfo {
+        Sysinfo {
+            version: [0; 16],
+            ncpu: 0,
+            nproc: 0,
+            hz: 0,
+            uptime: 0,
+            page_size: 0,
+            total_pages: 0,
+            free_pages: 0,
+            nregions: 0,
+            regions: [MemRegion {
+                start: 0,
+                end: 0,
+                typ: 0,
+            }; MAXREGIONS],
+            disk_sectors: 0,
+            disk_model: [0; 40],
+            disk_serial: [0; 20],
+        }
+    }
+}
+
+impl Default for Sysinfo {
+    fn default() -> Sysinfo {
+        Sysinfo::new()
+    }
+}
+
+// Process states.
+pub const PROC_EMBRYO: u32 = 1;
+pub const PROC_SLEEPING: u32 = 2;
+pub const PROC_RUNNABLE: u32 = 3;
+pub const PROC_RUNNING: u32 = 4;
+pub const PROC_ZOMBIE: u32 = 5;
+pub const PROC_STOPPED: u32 = 6;
+
+// The name is NUL-terminated.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProcInfo {
+    pub pid: u32,
+    pub ppid: u32,
+    pub state: u32,
+    pub size: u64,
+    pub name: [u8; 16],
+    // Clock ticks spent in user space and in the kernel.
+    pub utime: u64,
+    pub stime: u64,
+    // The nice value, and the current scheduling level.
+    pub nice: i32,
+    pub level: u32,
+}
@@ -0,0 +1,55 @@
+// Requests for the IOCTL system call, which controls terminals:
+// the consoles.  The numbers are those Linux uses.
+pub const TCGETS: usize = 0x5401;
+pub const TCSETS: usize = 0x5402;
+// As TCSETS, once output has drained, and discarding unread input.
+pub const TCSETSW: usize = 0x5403;
+pub const TCSETSF: usize = 0x5404;
+pub const TIOCGPGRP: usize = 0x540F;
+pub const TIOCSPGRP: usize = 0x5410;
+pub const TIOCGWINSZ: usize = 0x5413;
+
+// Local modes.  In canonical mode, input is made available a
+// line at a time, once it has been edited; otherwise each byte
+// is available as soon as it is typed.  With ISIG, ^C and ^\
+// send SIGINT and SIGQUIT instead of being read.
+pub const ISIG: u32 = 0x0001;
+pub const ICANON: u32 = 0x0002;
+pub const ECHO: u32 = 0x0008;
+
+// A terminal's settings.  Outside canonical mode, a read waits
+// for at least `vmin` bytes, but gives up `vtime` tenths of a
+// second after the last arrived: with `vmin` 0, `vtime` after the
+// read began.  With both 0, a read returns whatever is there.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Termios {
+    pub lflag: u32,
+    pub vmin: u8,
+    pub vtime: u8,
+}
+
+impl Termios {
+    pub const fn new() -> Termios {
+        Termios {
+            lflag: ISIG | ICANON | ECHO,
+            vmin: 1,
+            vtime: 0,
+        }
+    }
+}
+
+impl Default for Termios {
+    fn default() -> Termios {
+        Termios::new()
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Winsize {
+    pub rows: u16,
+    pub cols: u16,
+    pub xpixel: u16,
+    pub ypixel: u16,
+}
@@ -0,0 +1,22 @@
+// Options for the WAIT4 system call.
+pub const WNOHANG: u32 = 1;
+pub const WUNTRACED: u32 = 2;
+pub const WCONTINUED: u32 = 8;
+
+// A wait status holds the exit code in bits 8..16 for a process
+// that exited, or the signal in the low seven bits for one killed
+// by a signal.  A stopped process reports its stop signal in bits
+// 8..16 with 0x7f below it, and a continued one 0xffff.
+pub const fn exited(code: i32) -> i32 {
+    (code & 0xff) << 8
+}
+
+pub const fn signaled(sig: u32) -> i32 {
+    (sig & 0x7f) as i32
+}
+
+pub const fn stopped(sig: u32) -> i32 {
+    ((sig & 0xff) << 8 | 0x7f) as i32
+}
+
+pub const CONTINUED: i32 = 0xffff;
@@ -0,0 +1,24 @@
+#ifndef KEYMAP_H__
+#define KEYMAP_H__
+
+#include <stdint.h>
+
+// Operations for keymap(); see syslib/src/keymap.rs.
+#define KEYMAP_SELECT 0
+#define KEYMAP_LOAD 1
+
+#define KEYMAP_NKEYS 128
+
+// The bytes produced by each key, indexed by scancode.
+typedef struct Keymap Keymap;
+struct Keymap {
+	uint8_t normal[KEYMAP_NKEYS];
+	uint8_t shift[KEYMAP_NKEYS];
+	uint8_t altgr[KEYMAP_NKEYS];
+};
+
+// KEYMAP_SELECT takes the name of a built-in keymap,
+// KEYMAP_LOAD a pointer to a Keymap.
+int keymap(int op, const void *arg);
+
+#endif // KEYMAP_H__
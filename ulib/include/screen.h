@@ -0,0 +1,27 @@
+#ifndef SCREEN_H__
+#define SCREEN_H__
+
+#include <stdint.h>
+
+// Operations for screen(); see syslib/src/screen.rs.
+#define SCREEN_CLEAR 0
+#define SCREEN_TEXT 1
+#define SCREEN_BOX 2
+#define SCREEN_CURSOR 3
+
+#define SCREEN_WIDTH 80
+#define SCREEN_HEIGHT 25
+
+typedef struct ScreenReq ScreenReq;
+struct ScreenReq {
+	uint32_t x;
+	uint32_t y;
+	uint32_t w;
+	uint32_t h;
+	const char *text;
+};
+
+// Draw on the screen of the console open on fd.
+int screen(int fd, int op, const ScreenReq *req);
+
+#endif // SCREEN_H__
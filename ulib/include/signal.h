@@ -1,10 +1,130 @@
-const int SIGTERM = 15;
+#ifndef SIGNAL_H__
+#define SIGNAL_H__
+
+#include <stddef.h>
+#include <stdint.h>
+
+// Signal numbers; see syslib/src/signal.rs.
+#define SIGHUP 1
+#define SIGINT 2
+#define SIGQUIT 3
+#define SIGILL 4
+#define SIGTRAP 5
+#define SIGABRT 6
+#define SIGBUS 7
+#define SIGFPE 8
+#define SIGKILL 9
+#define SIGUSR1 10
+#define SIGSEGV 11
+#define SIGUSR2 12
+#define SIGPIPE 13
+#define SIGALRM 14
+#define SIGTERM 15
+#define SIGCHLD 17
+#define SIGCONT 18
+#define SIGSTOP 19
+#define SIGTSTP 20
+#define SIGTTIN 21
+#define SIGTTOU 22
+#define SIGURG 23
+#define SIGWINCH 28
+#define NSIG 32
+
+typedef void (*sighandler_t)(int);
+typedef uint64_t sigset_t;
+
+#define SIG_DFL ((sighandler_t)0)
+#define SIG_IGN ((sighandler_t)1)
+#define SIG_ERR ((sighandler_t)-1)
+
+#define SA_NODEFER 0x40000000
+#define SA_RESETHAND 0x80000000
+
+#define SIG_BLOCK 0
+#define SIG_UNBLOCK 1
+#define SIG_SETMASK 2
+
+// sa_restorer is filled in by sigaction().
+struct sigaction {
+	sighandler_t sa_handler;
+	uint64_t sa_flags;
+	sigset_t sa_mask;
+	void (*sa_restorer)(void);
+};
+
+int kill(int pid, int sig);
+int sigprocmask(int how, const sigset_t *set, sigset_t *oldset);
+
+static inline int
+sigaction(int sig, const struct sigaction *act, struct sigaction *oldact)
+{
+	extern int __sigaction(int, const struct sigaction *, struct sigaction *);
+	extern void __sigreturn(void);
+	struct sigaction a;
+
+	if(act != NULL){
+		a = *act;
+		a.sa_restorer = __sigreturn;
+		act = &a;
+	}
+	return __sigaction(sig, act, oldact);
+}
+
+static inline sighandler_t
+signal(int sig, sighandler_t handler)
+{
+	struct sigaction act = { handler, 0, 0, NULL }, old;
+
+	if(sigaction(sig, &act, &old) < 0)
+		return SIG_ERR;
+	return old.sa_handler;
+}
+
+static inline int
+raise(int sig)
+{
+	extern int getpid(void);
+	return kill(getpid(), sig);
+}
+
+static inline int
+sigemptyset(sigset_t *set)
+{
+	*set = 0;
+	return 0;
+}
+
+static inline int
+sigfillset(sigset_t *set)
+{
+	*set = ~(sigset_t)0;
+	return 0;
+}
 
 static inline int
-_kill(int pid, int sig)
+sigaddset(sigset_t *set, int sig)
 {
-	extern int kill(int);
-	(void)sig;
-	return kill(pid);
+	if(sig <= 0 || sig >= NSIG)
+		return -1;
+	*set |= (sigset_t)1 << (sig - 1);
+	return 0;
 }
-#define kill _kill
+
+static inline int
+sigdelset(sigset_t *set, int sig)
+{
+	if(sig <= 0 || sig >= NSIG)
+		return -1;
+	*set &= ~((sigset_t)1 << (sig - 1));
+	return 0;
+}
+
+static inline int
+sigismember(const sigset_t *set, int sig)
+{
+	if(sig <= 0 || sig >= NSIG)
+		return -1;
+	return (*set >> (sig - 1)) & 1;
+}
+
+#endif // SIGNAL_H__
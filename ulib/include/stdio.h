@@ -1,2 +1,3 @@
 int dprintf(int fd, const char *fmt, ...);
 #define printf(...) dprintf(1, __VA_ARGS__)
+void perror(const char *s);
@@ -7,3 +7,4 @@ size_t strlcat(char *dst, const char *restrict src, size_t size);
 size_t strlen(const char *s);
 int strcmp(const char *a, const char *b);
 char *strchr(const char *s, int c);
+char *strerror(int n);
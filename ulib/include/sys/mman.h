@@ -0,0 +1,26 @@
+#ifndef SYS_MMAN_H__
+#define SYS_MMAN_H__
+
+#include <stddef.h>
+#include <stdint.h>
+
+// Protections and flags for mmap(); see syslib/src/mman.rs.
+#define PROT_NONE 0
+#define PROT_READ 1
+#define PROT_WRITE 2
+#define PROT_EXEC 4
+
+#define MAP_SHARED 0x01
+#define MAP_PRIVATE 0x02
+#define MAP_FIXED 0x10
+#define MAP_ANONYMOUS 0x20
+#define MAP_ANON MAP_ANONYMOUS
+
+#define MAP_FAILED ((void *)-1)
+
+// Files may only be mapped read-only.
+void *mmap(void *addr, size_t len, int prot, int flags, int fd, int64_t offset);
+int munmap(void *addr, size_t len);
+int mprotect(void *addr, size_t len, int prot);
+
+#endif // SYS_MMAN_H__
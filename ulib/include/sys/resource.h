@@ -0,0 +1,44 @@
+#ifndef SYS_RESOURCE_H__
+#define SYS_RESOURCE_H__
+
+#include <stdint.h>
+
+// Whose usage getrusage() reports; see syslib/src/resource.rs.
+#define RUSAGE_SELF 0
+#define RUSAGE_CHILDREN (-1)
+
+// Times are in clock ticks, and ru_maxrss in KiB.  ru_nvcsw and
+// ru_nivcsw count the times the processor was given up by
+// sleeping, and taken away by preemption.
+struct rusage {
+	uint64_t ru_utime;
+	uint64_t ru_stime;
+	uint64_t ru_maxrss;
+	uint64_t ru_nvcsw;
+	uint64_t ru_nivcsw;
+};
+
+int getrusage(int who, struct rusage *usage);
+
+// Whose priority setpriority() and getpriority() concern; a who
+// of 0 means the caller's own.
+#define PRIO_PROCESS 0
+#define PRIO_PGRP 1
+
+// Nice values run from NZERO - 20 to NZERO - 1; lower run first.
+#define NZERO 20
+
+int setpriority(int which, int who, int nice);
+
+// Returns the lowest nice value of those named.  The kernel adds
+// NZERO to keep it clear of -1.
+static inline int
+getpriority(int which, int who)
+{
+	extern int __getpriority(int, int);
+	int r = __getpriority(which, who);
+
+	return r < 0 ? -1 : NZERO - r;
+}
+
+#endif // SYS_RESOURCE_H__
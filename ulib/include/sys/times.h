@@ -0,0 +1,19 @@
+#ifndef SYS_TIMES_H__
+#define SYS_TIMES_H__
+
+#include <stdint.h>
+
+typedef long clock_t;
+
+// In clock ticks; sysinfo() reports how many make a second.
+struct tms {
+	uint64_t tms_utime;
+	uint64_t tms_stime;
+	uint64_t tms_cutime;
+	uint64_t tms_cstime;
+};
+
+// Returns the ticks since boot.
+clock_t times(struct tms *buf);
+
+#endif // SYS_TIMES_H__
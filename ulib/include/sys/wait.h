@@ -1 +1,30 @@
+#ifndef SYS_WAIT_H__
+#define SYS_WAIT_H__
+
+#include <sys/resource.h>
+
+// Options for waitpid() and wait4(); see syslib/src/wait.rs.
+#define WNOHANG 1
+#define WUNTRACED 2
+#define WCONTINUED 8
+
+// Decoding the status stored by wait().
+#define WIFEXITED(s) (((s) & 0x7f) == 0)
+#define WEXITSTATUS(s) (((s) >> 8) & 0xff)
+#define WIFSIGNALED(s) (((s) & 0x7f) != 0 && ((s) & 0x7f) != 0x7f)
+#define WTERMSIG(s) ((s) & 0x7f)
+#define WIFSTOPPED(s) (((s) & 0xff) == 0x7f)
+#define WSTOPSIG(s) WEXITSTATUS(s)
+#define WIFCONTINUED(s) ((s) == 0xffff)
+
 extern int wait(int *status);
+
+extern int wait4(int pid, int *status, int options, struct rusage *rusage);
+
+static inline int
+waitpid(int pid, int *status, int options)
+{
+	return wait4(pid, status, options, 0);
+}
+
+#endif // SYS_WAIT_H__
@@ -0,0 +1,68 @@
+#ifndef SYSINFO_H__
+#define SYSINFO_H__
+
+#include <stdint.h>
+
+// Operations for sysinfo(); see syslib/src/sysinfo.rs.
+#define SYSINFO_SYSTEM 0
+#define SYSINFO_PROCS 1
+
+#define SYSINFO_MAXREGIONS 16
+
+// Memory region types.
+#define MEM_USABLE 1
+#define MEM_SYSTEM 2
+#define MEM_RESERVED 3
+
+typedef struct MemRegion MemRegion;
+struct MemRegion {
+	uint64_t start;
+	uint64_t end;
+	uint32_t type;
+};
+
+// Strings are NUL-padded.  uptime is in ticks, hz a second.
+typedef struct Sysinfo Sysinfo;
+struct Sysinfo {
+	char version[16];
+	uint32_t ncpu;
+	uint32_t nproc;
+	uint64_t hz;
+	uint64_t uptime;
+	uint64_t page_size;
+	uint64_t total_pages;
+	uint64_t free_pages;
+	uint32_t nregions;
+	MemRegion regions[SYSINFO_MAXREGIONS];
+	uint64_t disk_sectors;
+	char disk_model[40];
+	char disk_serial[20];
+};
+
+// Process states.
+#define PROC_EMBRYO 1
+#define PROC_SLEEPING 2
+#define PROC_RUNNABLE 3
+#define PROC_RUNNING 4
+#define PROC_ZOMBIE 5
+#define PROC_STOPPED 6
+
+typedef struct ProcInfo ProcInfo;
+struct ProcInfo {
+	uint32_t pid;
+	uint32_t ppid;
+	uint32_t state;
+	uint64_t size;
+	char name[16];
+	uint64_t utime;		// ticks in user space
+	uint64_t stime;		// ticks in the kernel
+	int32_t nice;
+	uint32_t level;		// scheduling level, 0 first
+};
+
+// SYSINFO_SYSTEM fills in the Sysinfo at buf and returns 0;
+// SYSINFO_PROCS fills in up to len ProcInfos at buf and returns
+// how many it filled.
+int sysinfo(int op, void *buf, int len);
+
+#endif // SYSINFO_H__
@@ -0,0 +1,88 @@
+#ifndef TERMIOS_H__
+#define TERMIOS_H__
+
+#include <errno.h>
+#include <stdint.h>
+
+// Requests for ioctl(); see syslib/src/termios.rs.
+#define TCGETS 0x5401
+#define TCSETS 0x5402
+#define TCSETSW 0x5403
+#define TCSETSF 0x5404
+#define TIOCGPGRP 0x540F
+#define TIOCSPGRP 0x5410
+#define TIOCGWINSZ 0x5413
+
+// When tcsetattr() makes its change: at once, once output has
+// drained, or then also discarding unread input.
+#define TCSANOW 0
+#define TCSADRAIN 1
+#define TCSAFLUSH 2
+
+// Local modes.
+#define ISIG 0x0001
+#define ICANON 0x0002
+#define ECHO 0x0008
+
+struct termios {
+	uint32_t c_lflag;
+	uint8_t c_vmin;
+	uint8_t c_vtime;
+};
+
+struct winsize {
+	uint16_t ws_row;
+	uint16_t ws_col;
+	uint16_t ws_xpixel;
+	uint16_t ws_ypixel;
+};
+
+int ioctl(int fd, unsigned long request, void *arg);
+
+static inline int
+tcgetattr(int fd, struct termios *t)
+{
+	return ioctl(fd, TCGETS, t);
+}
+
+static inline int
+tcsetattr(int fd, int optional_actions, const struct termios *t)
+{
+	unsigned long request;
+
+	switch(optional_actions){
+	case TCSANOW:
+		request = TCSETS;
+		break;
+	case TCSADRAIN:
+		request = TCSETSW;
+		break;
+	case TCSAFLUSH:
+		request = TCSETSF;
+		break;
+	default:
+		errno = EINVAL;
+		return -1;
+	}
+	return ioctl(fd, request, (void *)t);
+}
+
+static inline int
+tcgetpgrp(int fd)
+{
+	uint32_t pgrp;
+
+	if(ioctl(fd, TIOCGPGRP, &pgrp) < 0)
+		return -1;
+	return pgrp;
+}
+
+static inline int
+tcsetpgrp(int fd, int pgrp)
+{
+	uint32_t p = pgrp;
+
+	return ioctl(fd, TIOCSPGRP, &p);
+}
+
+#endif // TERMIOS_H__
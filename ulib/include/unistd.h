@@ -2,6 +2,7 @@
 #define UNISTD_H__
 
 #include <stdint.h>
+#include <sys/resource.h>
 
 int getpid(void);
 int dup(int);
@@ -15,6 +16,25 @@ ssize_t write(int fd, const void *buf, size_t count);
 int pipe(int fds[2]);
 void *sbrk(intptr_t delta);
 unsigned int sleep(unsigned int s);
+int setpgid(int pid, int pgid);
+int getpgid(int pid);
+int setsid(void);
+
+static inline int
+nice(int inc)
+{
+	int n = getpriority(PRIO_PROCESS, 0) + inc;
+
+	if(setpriority(PRIO_PROCESS, 0, n) < 0)
+		return -1;
+	return getpriority(PRIO_PROCESS, 0);
+}
+
+static inline int
+getpgrp(void)
+{
+	return getpgid(0);
+}
 
 static inline int
 execvp(const char *argv0, char *argv[])
@@ -6,6 +6,7 @@ use core::cmp;
 use core::ffi;
 use core::ptr;
 use core::slice;
+use syslib::errno::Errno;
 
 mod malloc;
 mod rvdprintf;
@@ -168,6 +169,42 @@ pub unsafe extern "C" fn free(p: *mut u8) {
     }
 }
 
+// The error number of the last system call that failed, which the
+// stubs in sysx86_64.rs store.  A process has only the one thread,
+// so this needs no thread-local storage.
+#[allow(non_upper_case_globals)]
+#[cfg_attr(not(test), unsafe(no_mangle))]
+pub static mut errno: i32 = 0;
+
+fn error_message(n: i32) -> &'static str {
+    Errno::from_i32(n).map_or("Unknown error", Errno::message)
+}
+
+/// # Safety
+/// C interface; the string returned is overwritten by the next call.
+#[cfg_attr(not(test), unsafe(no_mangle))]
+pub unsafe extern "C" fn strerror(n: i32) -> *const u8 {
+    static mut BUF: [u8; 64] = [0; 64];
+    let msg = error_message(n).as_bytes();
+    let buf = unsafe { &mut *ptr::addr_of_mut!(BUF) };
+    buf[..msg.len()].copy_from_slice(msg);
+    buf[msg.len()] = b'\0';
+    buf.as_ptr()
+}
+
+/// # Safety
+/// C strings
+#[cfg_attr(not(test), unsafe(no_mangle))]
+pub unsafe extern "C" fn perror(s: *const u8) {
+    let msg = error_message(unsafe { errno });
+    if !s.is_null() && unsafe { *s } != 0 {
+        rvdprintf::puts(2, unsafe { cstr2slice(s) });
+        rvdprintf::puts(2, b": ");
+    }
+    rvdprintf::puts(2, msg.as_bytes());
+    rvdprintf::puts(2, b"\n");
+}
+
 #[cfg(not(test))]
 #[panic_handler]
 pub fn panic(_info: &core::panic::PanicInfo) -> ! {
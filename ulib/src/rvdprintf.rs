@@ -95,7 +95,7 @@ fn putc(fd: i32, b: u8) {
     }
 }
 
-fn puts(fd: i32, bs: &[u8]) {
+pub fn puts(fd: i32, bs: &[u8]) {
     unsafe {
         write(fd, bs.as_ptr(), bs.len());
     }
@@ -4,9 +4,13 @@ use core::arch::global_asm;
 
 // System call stubs.  Note that the KBI ("kernel binary interface")
 // uses almost the same calling convention as the ABI ("application
-// binary interface").  So system calls are actually pretty
-// straight-forward.  We generate callable assembler functions in a
-// macro.
+// binary interface"): up to six arguments, in the same registers,
+// except that the fourth is in %r10, since `syscall` overwrites
+// %rcx with the return address.  A call that fails returns a
+// negated error number, which the stub stores in `errno` before
+// returning -1.  So system calls are actually pretty
+// straight-forward.  We generate callable assembler functions in
+// a macro.
 #[cfg(not(test))]
 mod syscalls {
     macro_rules! syscall {
@@ -25,7 +29,16 @@ mod syscalls {
                     ".globl ", stringify!($name), "\n",
                     stringify!($name), ":\n",
                     "movq ${num}, %rax\n",
+                    "movq %rcx, %r10\n",
                     "syscall\n",
+                    // Results from -4095 to -1 are negated error numbers.
+                    "cmpq $-4095, %rax\n",
+                    "jae 1f\n",
+                    "retq\n",
+                    "1:\n",
+                    "negl %eax\n",
+                    "movl %eax, errno(%rip)\n",
+                    "movq $-1, %rax\n",
                     "retq\n"
                 ),
                 num = const $num,
@@ -41,7 +54,7 @@ mod syscalls {
     syscall!(write, SYS::WRITE, isize, fd: i32, buf: *const u8, n: usize);
     syscall!(read, SYS::READ, isize, fd: i32, buf: *mut u8, n: usize);
     syscall!(close, SYS::CLOSE, i32, fd: i32);
-    syscall!(kill, SYS::KILL, i32, pid: i32);
+    syscall!(kill, SYS::KILL, i32, pid: i32, sig: i32);
     syscall!(
         exec,
         SYS::EXEC,
@@ -68,6 +81,64 @@ mod syscalls {
     syscall!(sbrk, SYS::SBRK, *mut u8, incr: isize);
     syscall!(sleep, SYS::SLEEP, i32, ticks: i32);
     syscall!(uptime, SYS::UPTIME, i32);
+    syscall!(
+        screen,
+        SYS::SCREEN,
+        i32,
+        fd: i32,
+        op: i32,
+        req: *const u8
+    );
+    syscall!(keymap, SYS::KEYMAP, i32, op: i32, arg: *const u8);
+    syscall!(ioctl, SYS::IOCTL, i32, fd: i32, request: u64, arg: *mut u8);
+    syscall!(sysinfo, SYS::SYSINFO, i32, op: i32, buf: *mut u8, len: i32);
+    syscall!(
+        __sigaction,
+        SYS::SIGACTION,
+        i32,
+        sig: i32,
+        act: *const u8,
+        oldact: *mut u8
+    );
+    syscall!(
+        sigprocmask,
+        SYS::SIGPROCMASK,
+        i32,
+        how: i32,
+        set: *const u64,
+        oldset: *mut u64
+    );
+    // Signal handlers return here; see signal.h.
+    syscall!(__sigreturn, SYS::SIGRETURN, i32);
+    syscall!(setpgid, SYS::SETPGID, i32, pid: i32, pgid: i32);
+    syscall!(getpgid, SYS::GETPGID, i32, pid: i32);
+    syscall!(setsid, SYS::SETSID, i32);
+    syscall!(
+        wait4,
+        SYS::WAIT4,
+        i32,
+        pid: i32,
+        status: *mut i32,
+        options: i32,
+        rusage: *mut u8
+    );
+    syscall!(times, SYS::TIMES, i64, buf: *mut u8);
+    syscall!(getrusage, SYS::GETRUSAGE, i32, who: i32, rusage: *mut u8);
+    syscall!(setpriority, SYS::SETPRIORITY, i32, which: i32, who: i32, nice: i32);
+    syscall!(__getpriority, SYS::GETPRIORITY, i32, which: i32, who: i32);
+    syscall!(
+        mmap,
+        SYS::MMAP,
+        *mut u8,
+        addr: *mut u8,
+        len: usize,
+        prot: i32,
+        flags: i32,
+        fd: i32,
+        offset: i64
+    );
+    syscall!(munmap, SYS::MUNMAP, i32, addr: *mut u8, len: usize);
+    syscall!(mprotect, SYS::MPROTECT, i32, addr: *mut u8, len: usize, prot: i32);
 }
 
 // Note: the very existence of this block of code annoys me.
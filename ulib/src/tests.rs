@@ -205,3 +205,24 @@ mod atoi_tests {
         assert_eq!(unsafe { super::atoi(s.as_ptr()) }, 99999);
     }
 }
+
+mod strerror_tests {
+    use syslib::errno::Errno;
+
+    fn strerror(n: i32) -> &'static [u8] {
+        let s = unsafe { super::strerror(n) };
+        unsafe { super::cstr2slice(s) }
+    }
+
+    #[test]
+    fn known() {
+        assert_eq!(strerror(Errno::ENOENT as i32), b"No such file or directory");
+        assert_eq!(strerror(Errno::EINTR as i32), b"Interrupted system call");
+    }
+
+    #[test]
+    fn unknown() {
+        assert_eq!(strerror(0), b"Unknown error");
+        assert_eq!(strerror(-1), b"Unknown error");
+    }
+}
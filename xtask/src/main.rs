@@ -162,7 +162,7 @@ fn kbuild(profile: Build) -> Result<()> {
     cmd.arg("--workspace");
     cmd.arg("--exclude").arg("xtask");
     cmd.arg("--exclude").arg("ulib");
-    cmd.arg("-Z").arg("build-std=core");
+    cmd.arg("-Z").arg("build-std=core,alloc");
     cmd.arg("--target").arg(format!("lib/{}.json", ktarget()));
     profile.add_build_arg(&mut cmd);
     let status = cmd.status()?;
@@ -195,7 +195,7 @@ fn expand(profile: Build) -> Result<()> {
     let mut cmd = Command::new(cargo());
     cmd.current_dir(subdir);
     cmd.arg("rustc");
-    cmd.arg("-Z").arg("build-std=core");
+    cmd.arg("-Z").arg("build-std=core,alloc");
     cmd.arg("--target")
         .arg(format!("../lib/{}.json", ktarget()));
     cmd.arg("--").arg("--pretty=expanded");
@@ -215,7 +215,7 @@ fn kasm(profile: Build) -> Result<()> {
     cmd.arg("--exclude").arg("xtask");
     cmd.arg("--exclude").arg("ulib");
     cmd.arg("--exclude").arg("syslib");
-    cmd.arg("-Z").arg("build-std=core");
+    cmd.arg("-Z").arg("build-std=core,alloc");
     cmd.arg("--target").arg(format!("lib/{}.json", utarget()));
     cmd.arg("--").arg("--emit").arg("asm");
     profile.add_build_arg(&mut cmd);